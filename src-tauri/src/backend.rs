@@ -0,0 +1,134 @@
+/**
+ * Application Backend
+ *
+ * Owns the `IPCBridge` and the `ProcessManager` of the Node.js backend and
+ * tears them down in a fixed order. Shutting them down separately races:
+ * the restart monitor may bring back the backend that is being stopped.
+ *
+ * `shutdown()`:
+ * 1. stops the monitor threads, so nothing is restarted from here on
+ * 2. drains in-flight requests and flushes queued messages
+ * 3. gracefully stops the backend process
+ * 4. stops the bridge threads and joins them and the restart monitor
+ *
+ * # Example
+ * ```ignore
+ * let mut backend = AppBackend::new(IPCBridge::new(), ProcessManager::new(script, dir));
+ * backend.start()?;
+ * backend.bridge().request("status", json!({}), |result| { ... })?;
+ * backend.shutdown()?;
+ * ```
+ */
+
+use crate::ipc::{IPCBridge, IPCError};
+use crate::process::{ProcessManager, ShutdownOutcome};
+use log::{debug, info, warn};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How long `shutdown` waits for in-flight requests
+const DEFAULT_DRAIN_TIMEOUT_SECS: u64 = 2;
+
+/// How long `shutdown` waits for queued messages to be written
+const DEFAULT_FLUSH_TIMEOUT_SECS: u64 = 1;
+
+/// How long `shutdown` waits for the restart monitor to exit
+const MONITOR_JOIN_TIMEOUT_SECS: u64 = 3;
+
+/// The bridge and the backend process, started and stopped together
+pub struct AppBackend {
+    bridge: Arc<IPCBridge>,
+    process: ProcessManager,
+    /// Listener and timeout checker threads, including those of restarted backends
+    threads: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    drain_timeout: Duration,
+}
+
+impl AppBackend {
+    /// Combine a bridge and a process manager; nothing is started yet
+    pub fn new(bridge: IPCBridge, process: ProcessManager) -> Self {
+        AppBackend {
+            bridge: Arc::new(bridge),
+            process,
+            threads: Arc::new(Mutex::new(Vec::new())),
+            drain_timeout: Duration::from_secs(DEFAULT_DRAIN_TIMEOUT_SECS),
+        }
+    }
+
+    /// Set how long `shutdown` lets in-flight requests finish
+    pub fn with_drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = timeout;
+        self
+    }
+
+    /// The bridge to the backend
+    pub fn bridge(&self) -> &Arc<IPCBridge> {
+        &self.bridge
+    }
+
+    /// The backend process manager
+    pub fn process(&self) -> &ProcessManager {
+        &self.process
+    }
+
+    /// Start the backend, connect the bridge to it and monitor it
+    ///
+    /// A restarted backend is re-attached to the bridge automatically.
+    pub fn start(&mut self) -> Result<(), String> {
+        let bridge = Arc::clone(&self.bridge);
+        let threads = Arc::clone(&self.threads);
+        self.process.on_restart(move |child| {
+            if let Some(stdin) = child.stdin.take() {
+                bridge.set_stdin(stdin);
+            }
+            if let Some(stdout) = child.stdout.take() {
+                threads.lock().unwrap().push(bridge.start_stdout_listener(stdout, |_| {}));
+            }
+        });
+
+        self.process.start_node_backend()?;
+        let stdin = self.process.take_stdin().ok_or("Backend stdin is not available")?;
+        let stdout = self.process.take_stdout().ok_or("Backend stdout is not available")?;
+        self.bridge.set_stdin(stdin);
+
+        let mut threads = self.threads.lock().unwrap();
+        threads.push(self.bridge.start_stdout_listener(stdout, |_| {}));
+        threads.push(self.bridge.start_timeout_checker());
+        drop(threads);
+
+        self.process.restart_on_crash();
+        info!("Application backend started");
+        Ok(())
+    }
+
+    /// Stop the backend and the bridge in order, see the module docs
+    pub fn shutdown(&mut self) -> Result<ShutdownOutcome, String> {
+        info!("Shutting down application backend");
+        self.process.stop_monitors();
+
+        let cut_off = self.bridge.drain(self.drain_timeout);
+        if cut_off > 0 {
+            warn!("{} request(s) cut off by shutdown", cut_off);
+        }
+        match self.bridge.final_flush(Duration::from_secs(DEFAULT_FLUSH_TIMEOUT_SECS)) {
+            Ok(()) | Err(IPCError::StdinNotAvailable) => {}
+            Err(e) => warn!("Queued messages not delivered before shutdown: {}", e),
+        }
+
+        let outcome = self.process.shutdown_gracefully()?;
+        self.bridge.shutdown();
+
+        let threads: Vec<JoinHandle<()>> = self.threads.lock().unwrap().drain(..).collect();
+        for handle in threads {
+            if handle.join().is_err() {
+                warn!("Backend thread panicked before shutdown");
+            }
+        }
+        if !self.process.wait_for_monitors(Duration::from_secs(MONITOR_JOIN_TIMEOUT_SECS)) {
+            warn!("Restart monitor still running after shutdown");
+        }
+        debug!("Application backend shut down: {:?}", outcome);
+        Ok(outcome)
+    }
+}
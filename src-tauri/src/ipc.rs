@@ -6,6 +6,7 @@
  * Core functionality:
  * - `handle_stdin()`: 监听 Node.js stdout 并解析
  * - `forward_to_frontend()`: 通过 Tauri emit 推送到前端
+ * - `forward_to_frontend_full()`: 推送时保留 id、类型与错误信息
  * - `handle_frontend_invoke()`: 处理前端 invoke 调用并转发到 Node.js stdin
  *
  * Message Flow:
@@ -22,13 +23,55 @@
  * _TaskGroup: 5_
  */
 
+pub mod backpressure;
+pub mod circuit;
+pub mod command;
+pub mod compression;
+pub mod events;
+pub mod jsonrpc;
+pub mod loopback;
+pub mod metrics;
+#[cfg(test)]
+pub mod mock;
+pub mod observer;
+pub mod queue;
+pub mod schema;
+pub mod transport;
+pub mod writer;
+
+pub use backpressure::BackpressureEvent;
+pub use circuit::{CircuitBreakerConfig, CircuitState};
+pub use command::Command;
+pub use compression::{Compression, CompressionConfig};
+pub use events::Event;
+pub use jsonrpc::{encode_batch_jsonrpc, encode_message_for_stdin_jsonrpc, parse_stdin_message_jsonrpc};
+pub use metrics::{IPCMetrics, IPCMetricsSnapshot};
+pub use observer::IpcObserver;
+pub use queue::Priority;
+pub use transport::{StdioTransport, Transport};
+#[cfg(unix)]
+pub use transport::UnixSocketTransport;
+#[cfg(windows)]
+pub use transport::NamedPipeTransport;
+pub use writer::ThreadedWriter;
+
+use backpressure::Backpressure;
+use circuit::CircuitBreaker;
+use compression::CompressionHeader;
+use queue::PriorityQueue;
+
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use std::collections::{HashMap, VecDeque};
-use std::io::{BufRead, BufReader, Write};
-use std::process::{ChildStdin, ChildStdout};
-use std::sync::{Arc, Mutex};
-use std::thread;
+use std::future::Future;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::process::ChildStdin;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, OnceLock, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
 use log::{info, error, warn, debug};
 
@@ -39,6 +82,9 @@ pub enum IPCMessageType {
     Event,
     Request,
     Response,
+    /// Partial result of a streaming request; the final `Response` with the
+    /// same ID ends the stream
+    Chunk,
 }
 
 /// IPC Error types for better error handling
@@ -54,6 +100,19 @@ pub enum IPCError {
     Timeout(String),
     /// Message parsing error
     ParseError(String),
+    /// Request was cancelled before a response arrived
+    Cancelled(String),
+    /// The bridge is draining and no longer accepts requests
+    ShuttingDown,
+    /// The peer uses a protocol version this side cannot handle
+    UnsupportedVersion(u32),
+    /// Node.js answered the request with an error response
+    BackendError {
+        /// Error code from the response payload, if any
+        code: Option<String>,
+        /// Error message from the response
+        message: String,
+    },
     /// Generic error
     Other(String),
 }
@@ -66,6 +125,17 @@ impl std::fmt::Display for IPCError {
             IPCError::SendError(msg) => write!(f, "Send error: {}", msg),
             IPCError::Timeout(msg) => write!(f, "Request timeout: {}", msg),
             IPCError::ParseError(msg) => write!(f, "Parse error: {}", msg),
+            IPCError::Cancelled(msg) => write!(f, "Request cancelled: {}", msg),
+            IPCError::ShuttingDown => write!(f, "IPC bridge is shutting down"),
+            IPCError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported protocol version {} (supported: {}..={})",
+                version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+            ),
+            IPCError::BackendError { code: Some(code), message } => {
+                write!(f, "Backend error [{}]: {}", code, message)
+            }
+            IPCError::BackendError { code: None, message } => write!(f, "Backend error: {}", message),
             IPCError::Other(msg) => write!(f, "IPC error: {}", msg),
         }
     }
@@ -73,6 +143,29 @@ impl std::fmt::Display for IPCError {
 
 impl std::error::Error for IPCError {}
 
+impl IPCError {
+    /// Build the error for a response message that carries `error`
+    ///
+    /// The code is taken from a `code` field in the response payload, when present.
+    fn from_response(msg: &IPCMessage) -> Option<Self> {
+        let message = msg.error.clone()?;
+        let code = match msg.payload.get("code") {
+            Some(Value::String(code)) => Some(code.clone()),
+            Some(Value::Number(code)) => Some(code.to_string()),
+            _ => None,
+        };
+        Some(IPCError::BackendError { code, message })
+    }
+
+    /// Whether retrying the same request may succeed
+    ///
+    /// Timeouts and transport failures are transient; an error reported by
+    /// the backend or a cancellation is not.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, IPCError::Timeout(_) | IPCError::SendError(_) | IPCError::StdinNotAvailable)
+    }
+}
+
 /// Convert IPCError to String for backward compatibility
 impl From<IPCError> for String {
     fn from(err: IPCError) -> String {
@@ -80,6 +173,29 @@ impl From<IPCError> for String {
     }
 }
 
+/// Newest protocol version this side speaks
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Oldest protocol version this side still accepts
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// Event used to negotiate the protocol version
+pub const HELLO_EVENT: &str = "__hello__";
+
+/// Event Node.js sends once its message handler is initialized
+pub const BACKEND_READY_EVENT: &str = "backend_ready";
+
+/// Payload key announcing the length of a binary frame that follows the message
+pub const BINARY_LEN_KEY: &str = "__binary_len__";
+
+/// Event telling Node.js that a request was cancelled
+///
+/// Sent by `IPCBridge::cancel_request` with the payload `{ "id": <request id> }`.
+/// The backend should abort the work for that request (kill subprocesses,
+/// stop scans) and may skip its response, which Rust would drop anyway.
+/// IDs it does not know, e.g. of requests already answered, must be ignored.
+pub const CANCEL_EVENT: &str = "__cancel__";
+
 /// IPC Message structure
 ///
 /// This is the standard message format used for all IPC communication
@@ -97,6 +213,24 @@ pub struct IPCMessage {
     pub payload: Value,
     /// Optional error message
     pub error: Option<String>,
+    /// Outbound sequence number, assigned by the bridge in send order
+    ///
+    /// Messages buffered with different `Priority` levels are flushed out of
+    /// sequence order; within a level the order always holds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    /// Protocol version the message was written for, once negotiated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+    /// Raw bytes sent as a separate frame after the message
+    ///
+    /// Only supported with `WireFormat::MessagePack`; the payload then carries
+    /// the frame length under `__binary_len__`.
+    #[serde(skip)]
+    pub binary: Option<Vec<u8>>,
+    /// Optional headers kept apart from the payload (e.g. session, trace ID)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Map<String, Value>>,
 }
 
 impl IPCMessage {
@@ -108,9 +242,23 @@ impl IPCMessage {
             event: event.to_string(),
             payload,
             error: None,
+            seq: None,
+            version: None,
+            binary: None,
+            meta: None,
         }
     }
 
+    /// Attach a metadata header, creating the `meta` map if needed
+    ///
+    /// ```ignore
+    /// let msg = IPCMessage::event("file_changed", payload).with_meta("session", json!("abc"));
+    /// ```
+    pub fn with_meta(mut self, key: &str, value: Value) -> Self {
+        self.meta.get_or_insert_with(Map::new).insert(key.to_string(), value);
+        self
+    }
+
     /// Create a new request message
     pub fn request(id: &str, event: &str, payload: Value) -> Self {
         IPCMessage {
@@ -119,6 +267,10 @@ impl IPCMessage {
             event: event.to_string(),
             payload,
             error: None,
+            seq: None,
+            version: None,
+            binary: None,
+            meta: None,
         }
     }
 
@@ -130,9 +282,35 @@ impl IPCMessage {
             event: event.to_string(),
             payload,
             error: None,
+            seq: None,
+            version: None,
+            binary: None,
+            meta: None,
         }
     }
 
+    /// Create an event message carrying raw bytes
+    ///
+    /// The payload is the `__binary_len__` marker; the bytes follow as their
+    /// own frame. Requires `WireFormat::MessagePack`.
+    pub fn binary_event(event: &str, bytes: Vec<u8>) -> Self {
+        let mut msg = IPCMessage::event(event, serde_json::json!({ (BINARY_LEN_KEY): bytes.len() }));
+        msg.binary = Some(bytes);
+        msg
+    }
+
+    /// Length announced by a `__binary_len__` marker payload, if any
+    pub fn binary_len(&self) -> Option<usize> {
+        self.payload.get(BINARY_LEN_KEY)?.as_u64().map(|len| len as usize)
+    }
+
+    /// Create a partial result for a streaming request
+    pub fn chunk(id: &str, event: &str, payload: Value) -> Self {
+        let mut msg = IPCMessage::response(id, event, payload);
+        msg.msg_type = IPCMessageType::Chunk;
+        msg
+    }
+
     /// Create an error response message
     pub fn error_response(id: &str, event: &str, error: &str) -> Self {
         IPCMessage {
@@ -141,10 +319,22 @@ impl IPCMessage {
             event: event.to_string(),
             payload: Value::Null,
             error: Some(error.to_string()),
+            seq: None,
+            version: None,
+            binary: None,
+            meta: None,
         }
     }
 }
 
+/// Label a message for logging as `event [id]`, so logs can be grepped by request ID
+fn log_label(msg: &IPCMessage) -> String {
+    match &msg.id {
+        Some(id) => format!("{} [{}]", msg.event, id),
+        None => msg.event.clone(),
+    }
+}
+
 /// Parse a message from stdin (received from Node.js stdout)
 ///
 /// # Arguments
@@ -177,6 +367,205 @@ pub fn encode_message_for_stdin(msg: &IPCMessage) -> Result<String, String> {
         .map_err(|e| format!("Failed to encode message: {}", e))
 }
 
+/// Encode a message as a complete frame for the given wire format
+///
+/// # Arguments
+/// * `msg` - IPC message to encode
+/// * `format` - Wire format to use
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - Newline-terminated JSON, or a MessagePack body preceded
+///   by its 4-byte big-endian length (followed by the binary frame, if any)
+/// * `Err(String)` - Encoding error description
+pub fn encode_message(msg: &IPCMessage, format: WireFormat) -> Result<Vec<u8>, String> {
+    match format {
+        WireFormat::Json if msg.binary.is_some() => {
+            Err("Failed to encode message: binary payloads require WireFormat::MessagePack".to_string())
+        }
+        WireFormat::Json => encode_message_for_stdin(msg).map(String::into_bytes),
+        WireFormat::MessagePack => {
+            let body = rmp_serde::to_vec_named(msg)
+                .map_err(|e| format!("Failed to encode message: {}", e))?;
+            let mut frame = Vec::with_capacity(4 + body.len());
+            push_length_prefixed(&mut frame, &body)?;
+            if let Some(binary) = &msg.binary {
+                push_length_prefixed(&mut frame, binary)?;
+            }
+            Ok(frame)
+        }
+    }
+}
+
+/// Encode a message, compressing it once it reaches the configured threshold
+///
+/// Messages below the threshold, messages carrying a binary frame, and
+/// messages that do not shrink are framed exactly as by `encode_message`.
+/// A compressed message is written as a `CompressionHeader` (a JSON line, or
+/// a length-prefixed MessagePack frame) followed by the compressed body as a
+/// length-prefixed frame.
+pub fn encode_message_compressed(
+    msg: &IPCMessage,
+    format: WireFormat,
+    compression: Option<CompressionConfig>,
+) -> Result<Vec<u8>, String> {
+    let config = match compression {
+        Some(config) if msg.binary.is_none() => config,
+        _ => return encode_message(msg, format),
+    };
+    let body = match format {
+        WireFormat::Json => serde_json::to_vec(msg).map_err(|e| format!("Failed to encode message: {}", e))?,
+        WireFormat::MessagePack => rmp_serde::to_vec_named(msg).map_err(|e| format!("Failed to encode message: {}", e))?,
+    };
+
+    if body.len() >= config.threshold {
+        let compressed = compression::compress(config.codec, &body)?;
+        if compressed.len() < body.len() {
+            let header = CompressionHeader { codec: config.codec, original_len: body.len() };
+            let mut frame = match format {
+                WireFormat::Json => {
+                    let mut line = serde_json::to_vec(&header).map_err(|e| format!("Failed to encode message: {}", e))?;
+                    line.push(b'\n');
+                    line
+                }
+                WireFormat::MessagePack => {
+                    let header = rmp_serde::to_vec_named(&header).map_err(|e| format!("Failed to encode message: {}", e))?;
+                    let mut frame = Vec::with_capacity(8 + header.len() + compressed.len());
+                    push_length_prefixed(&mut frame, &header)?;
+                    frame
+                }
+            };
+            push_length_prefixed(&mut frame, &compressed)?;
+            return Ok(frame);
+        }
+    }
+
+    let mut frame = Vec::with_capacity(4 + body.len());
+    match format {
+        WireFormat::Json => {
+            frame.extend_from_slice(&body);
+            frame.push(b'\n');
+        }
+        WireFormat::MessagePack => push_length_prefixed(&mut frame, &body)?,
+    }
+    Ok(frame)
+}
+
+/// Append `body` to `frame` behind its 4-byte big-endian length
+fn push_length_prefixed(frame: &mut Vec<u8>, body: &[u8]) -> Result<(), String> {
+    let len = u32::try_from(body.len())
+        .map_err(|_| format!("Message too large to frame: {} bytes", body.len()))?;
+    frame.extend_from_slice(&len.to_be_bytes());
+    frame.extend_from_slice(body);
+    Ok(())
+}
+
+/// Decode a single frame body (without delimiter or length prefix)
+///
+/// # Arguments
+/// * `frame` - Raw frame bytes
+/// * `format` - Wire format the frame was encoded with
+///
+/// # Returns
+/// * `Ok(IPCMessage)` - Parsed message
+/// * `Err(String)` - Parse error description
+pub fn decode_message(frame: &[u8], format: WireFormat) -> Result<IPCMessage, String> {
+    match format {
+        WireFormat::Json => {
+            let text = std::str::from_utf8(frame)
+                .map_err(|e| format!("Failed to parse message: {}", e))?;
+            parse_stdin_message(text)
+        }
+        WireFormat::MessagePack => rmp_serde::from_slice(frame)
+            .map_err(|e| format!("Failed to parse message: {}", e)),
+    }
+}
+
+/// Decompress and decode the frame that follows a `CompressionHeader`
+///
+/// The decompressed body is parsed as by `decode_message`, so a JSON body goes
+/// through `parse_stdin_message`. `max_bytes` bounds the decompressed size.
+pub fn decode_compressed_message(
+    header: &CompressionHeader,
+    body: &[u8],
+    format: WireFormat,
+    max_bytes: usize,
+) -> Result<IPCMessage, String> {
+    let data = compression::decompress(header, body, max_bytes)?;
+    decode_message(&data, format)
+}
+
+/// Result of reading one frame from a size-limited stream
+#[derive(Debug, PartialEq)]
+enum RawFrame {
+    /// A complete frame within the size limit
+    Complete(Vec<u8>),
+    /// A frame of the given size that exceeded the limit and was discarded
+    TooLong(usize),
+    /// The stream ended
+    Eof,
+}
+
+/// Read one length-prefixed frame, discarding frames larger than `max_bytes`
+fn read_length_prefixed_frame<R: Read>(reader: &mut R, max_bytes: usize) -> std::io::Result<RawFrame> {
+    let mut len_buf = [0u8; 4];
+    match reader.read_exact(&mut len_buf) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(RawFrame::Eof),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_be_bytes(len_buf) as usize;
+    if len > max_bytes {
+        std::io::copy(&mut reader.take(len as u64), &mut std::io::sink())?;
+        return Ok(RawFrame::TooLong(len));
+    }
+    let mut frame = vec![0u8; len];
+    reader.read_exact(&mut frame)?;
+    Ok(RawFrame::Complete(frame))
+}
+
+/// Read one newline-terminated line without buffering more than `max_bytes`
+///
+/// The newline is not included. An over-long line is consumed up to and
+/// including its newline and reported as `RawFrame::TooLong`.
+fn read_line_limited<R: BufRead>(reader: &mut R, max_bytes: usize) -> std::io::Result<RawFrame> {
+    let mut line = Vec::new();
+    let mut total = 0;
+    let mut too_long = false;
+
+    loop {
+        let available = match reader.fill_buf() {
+            Ok(available) => available,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if available.is_empty() {
+            return Ok(match (total, too_long) {
+                (0, _) => RawFrame::Eof,
+                (_, true) => RawFrame::TooLong(total),
+                (_, false) => RawFrame::Complete(line),
+            });
+        }
+
+        let newline = available.iter().position(|&b| b == b'\n');
+        let chunk = &available[..newline.unwrap_or(available.len())];
+        total += chunk.len();
+        if !too_long {
+            if total > max_bytes {
+                too_long = true;
+                line = Vec::new();
+            } else {
+                line.extend_from_slice(chunk);
+            }
+        }
+
+        let consumed = chunk.len() + newline.map(|_| 1).unwrap_or(0);
+        reader.consume(consumed);
+        if newline.is_some() {
+            return Ok(if too_long { RawFrame::TooLong(total) } else { RawFrame::Complete(line) });
+        }
+    }
+}
+
 /// Extract event name and payload for forwarding to frontend
 ///
 /// This function prepares the data needed for Tauri's emit API
@@ -187,471 +576,6134 @@ pub fn encode_message_for_stdin(msg: &IPCMessage) -> Result<String, String> {
 /// # Returns
 /// * Tuple of (event_name, payload)
 pub fn forward_to_frontend(msg: &IPCMessage) -> (String, Value) {
-    let event_name = msg.event.clone();
-
-    // Construct the payload to send to frontend
-    let mut payload = msg.payload.clone();
+    (msg.event.clone(), msg.payload.clone())
+}
 
-    // If payload is an object, keep it as-is
-    // If it's null or a primitive, wrap it or pass directly
-    if !payload.is_object() {
-        payload = payload;
+/// Extract event name and an envelope carrying the full message context
+///
+/// Unlike `forward_to_frontend`, the payload is wrapped as
+/// `{ id, msg_type, error, payload }` so the frontend can correlate
+/// responses and see errors. Metadata, if any, is added under `meta`.
+///
+/// # Arguments
+/// * `msg` - IPC message to forward
+///
+/// # Returns
+/// * Tuple of (event_name, envelope)
+pub fn forward_to_frontend_full(msg: &IPCMessage) -> (String, Value) {
+    let mut envelope = serde_json::json!({
+        "id": msg.id,
+        "msg_type": msg.msg_type,
+        "error": msg.error,
+        "payload": msg.payload,
+    });
+    if let Some(meta) = &msg.meta {
+        envelope["meta"] = Value::Object(meta.clone());
     }
+    (msg.event.clone(), envelope)
+}
 
-    (event_name, payload)
+/// Encoding used for messages on the stdin/stdout pipes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WireFormat {
+    /// Newline-delimited JSON (default)
+    #[default]
+    Json,
+    /// MessagePack, framed with a 4-byte big-endian length prefix
+    MessagePack,
 }
 
-/// IPC Bridge manager for handling communication
-pub struct IPCBridge {
-    stdin: Arc<Mutex<Option<ChildStdin>>>,
-    pending_requests: Arc<Mutex<HashMap<String, PendingRequest>>>,
-    event_handlers: Arc<Mutex<HashMap<String, Vec<Box<dyn Fn(Value) + Send + 'static>>>>>,
-    /// Message queue for buffered sending when stdin is not ready
-    message_queue: Arc<Mutex<VecDeque<IPCMessage>>>,
-    /// Default request timeout in seconds
-    request_timeout_secs: u64,
+/// How the Node.js stdout stream is split into IPC messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FramingMode {
+    /// One JSON message per newline-terminated line (default)
+    #[default]
+    Newline,
+    /// Accumulate bytes until a complete top-level JSON object is available,
+    /// so messages may span lines and interleaved plain-text logs are skipped
+    JsonStream,
 }
 
-/// Default timeout for requests (30 seconds)
-const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
+/// What to do when the outgoing message queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QueueOverflowPolicy {
+    /// Evict the oldest queued message to make room (default)
+    #[default]
+    DropOldest,
+    /// Discard the new message and keep the queue as is
+    DropNewest,
+    /// Refuse the new message and return `IPCError::SendError`
+    Reject,
+}
 
-struct PendingRequest {
-    #[allow(dead_code)]
-    event: String,
-    callback: Box<dyn FnOnce(Result<Value, String>) + Send + 'static>,
-    /// When the request was created
-    created_at: Instant,
-    /// Timeout duration for this request
-    timeout: Duration,
+/// Entry in the outbound queue
+#[derive(Debug, Clone)]
+enum Queued {
+    /// Message encoded when it is written
+    Message(IPCMessage),
+    /// Pre-encoded bytes written verbatim, without a sequence number
+    Raw(Vec<u8>),
 }
 
-impl IPCBridge {
-    /// Create a new IPC bridge
-    pub fn new() -> Self {
-        info!("Creating new IPC Bridge");
-        IPCBridge {
-            stdin: Arc::new(Mutex::new(None)),
-            pending_requests: Arc::new(Mutex::new(HashMap::new())),
-            event_handlers: Arc::new(Mutex::new(HashMap::new())),
-            message_queue: Arc::new(Mutex::new(VecDeque::new())),
-            request_timeout_secs: DEFAULT_REQUEST_TIMEOUT_SECS,
+impl Queued {
+    /// The queued message, `None` for raw bytes
+    fn message(&self) -> Option<&IPCMessage> {
+        match self {
+            Queued::Message(msg) => Some(msg),
+            Queued::Raw(_) => None,
         }
     }
 
-    /// Create a new IPC bridge with custom timeout
-    pub fn with_timeout(timeout_secs: u64) -> Self {
-        info!("Creating new IPC Bridge with timeout: {}s", timeout_secs);
-        IPCBridge {
-            stdin: Arc::new(Mutex::new(None)),
-            pending_requests: Arc::new(Mutex::new(HashMap::new())),
-            event_handlers: Arc::new(Mutex::new(HashMap::new())),
-            message_queue: Arc::new(Mutex::new(VecDeque::new())),
-            request_timeout_secs: timeout_secs,
+    /// Encode the entry for the wire
+    fn encode(&self, format: WireFormat, compression: Option<CompressionConfig>) -> Result<Vec<u8>, String> {
+        match self {
+            Queued::Message(msg) => encode_message_compressed(msg, format, compression),
+            Queued::Raw(bytes) => Ok(bytes.clone()),
         }
     }
 
-    /// Set the Node.js process stdin for sending messages
-    pub fn set_stdin(&self, stdin: ChildStdin) {
-        debug!("Setting Node.js stdin for IPC bridge");
-        *self.stdin.lock().unwrap() = Some(stdin);
-
-        // Flush any queued messages
-        self.flush_message_queue();
+    /// Label the entry for logging, see `log_label`
+    fn label(&self) -> String {
+        match self {
+            Queued::Message(msg) => log_label(msg),
+            Queued::Raw(bytes) => format!("raw line ({} bytes)", bytes.len()),
+        }
     }
+}
 
-    /// Flush queued messages to stdin
-    fn flush_message_queue(&self) {
-        let mut queue = self.message_queue.lock().unwrap();
-        let mut stdin_guard = self.stdin.lock().unwrap();
+impl From<IPCMessage> for Queued {
+    fn from(msg: IPCMessage) -> Self {
+        Queued::Message(msg)
+    }
+}
 
-        if let Some(ref mut stdin) = *stdin_guard {
-            while let Some(msg) = queue.pop_front() {
-                if let Ok(encoded) = encode_message_for_stdin(&msg) {
-                    if let Err(e) = stdin.write_all(encoded.as_bytes()) {
-                        warn!("Failed to flush queued message: {}", e);
-                        // Put the message back at the front of the queue
-                        queue.push_front(msg);
-                        break;
-                    }
-                }
+/// Write queued messages to stdin in order
+///
+/// Messages that fail to encode are dropped; on a write failure the message
+/// is put back at the front of the queue and the error is returned, keeping
+/// its kind. If the writer is backed up the rest stays queued and the count
+/// so far is returned.
+fn write_queued(
+    stdin: &mut NodeWriter,
+    queue: &mut PriorityQueue<Queued>,
+    format: WireFormat,
+    compression: Option<CompressionConfig>,
+    observer: Option<&dyn IpcObserver>,
+    metrics: &IPCMetrics,
+) -> std::io::Result<usize> {
+    let mut flushed = 0;
+    while let Some((priority, entry)) = queue.pop_front() {
+        let encoded = match entry.encode(format, compression) {
+            Ok(encoded) => encoded,
+            Err(e) => {
+                error!("Dropping queued message {} that failed to encode: {}", entry.label(), e);
+                continue;
+            }
+        };
+        if let Err(e) = stdin.write_all(&encoded) {
+            if e.kind() == ErrorKind::WouldBlock {
+                queue.push_front(priority, entry);
+                debug!("Node.js stdin is backed up, {} message(s) left queued", queue.len());
+                return Ok(flushed);
             }
-            let _ = stdin.flush();
+            warn!("Failed to flush queued message {}: {}", entry.label(), e);
+            // Put the message back at the front of the queue
+            queue.push_front(priority, entry);
+            return Err(std::io::Error::new(
+                e.kind(),
+                format!("flushed {} message(s) before write failed: {}", flushed, e),
+            ));
         }
+        metrics.record_bytes_sent(encoded.len());
+        if let (Some(observer), Some(msg)) = (observer, entry.message()) {
+            observer.on_send(msg);
+        }
+        flushed += 1;
     }
+    stdin
+        .flush()
+        .map_err(|e| std::io::Error::new(e.kind(), format!("Failed to flush Node.js stdin: {}", e)))?;
 
-    /// Start listening to Node.js stdout
-    ///
-    /// This spawns a thread that reads from stdout and processes messages
-    pub fn start_stdout_listener<F>(&self, stdout: ChildStdout, on_message: F)
-    where
-        F: Fn(IPCMessage) + Send + 'static,
-    {
-        info!("Starting stdout listener for IPC bridge");
-        let pending_requests = Arc::clone(&self.pending_requests);
-        let event_handlers = Arc::clone(&self.event_handlers);
+    Ok(flushed)
+}
 
-        thread::spawn(move || {
-            let reader = BufReader::new(stdout);
+/// Reader that counts the bytes read from Node.js stdout into the metrics
+struct CountingReader<R> {
+    inner: R,
+    metrics: Arc<IPCMetrics>,
+}
 
-            for line in reader.lines() {
-                match line {
-                    Ok(content) => {
-                        if content.trim().is_empty() {
-                            continue;
-                        }
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.metrics.record_bytes_received(read);
+        Ok(read)
+    }
+}
 
-                        debug!("Received from Node.js: {}", content);
-
-                        match parse_stdin_message(&content) {
-                            Ok(msg) => {
-                                // Handle response messages
-                                if matches!(msg.msg_type, IPCMessageType::Response) {
-                                    if let Some(id) = &msg.id {
-                                        let mut requests = pending_requests.lock().unwrap();
-                                        if let Some(pending) = requests.remove(id) {
-                                            let result = if let Some(err) = &msg.error {
-                                                Err(err.clone())
-                                            } else {
-                                                Ok(msg.payload.clone())
-                                            };
-                                            (pending.callback)(result);
-                                            continue;
-                                        }
-                                    }
-                                }
+/// Incremental splitter for `FramingMode::JsonStream`
+///
+/// Tracks brace depth (ignoring braces inside strings) and yields each
+/// complete top-level object. A line is treated as plain-text log output
+/// unless its first non-whitespace byte opens an object.
+struct JsonFrameDecoder {
+    buf: Vec<u8>,
+    /// Plain-text line currently being skipped
+    text: Vec<u8>,
+    /// Objects growing beyond this many bytes are discarded
+    max_frame_bytes: usize,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    skipping_line: bool,
+    /// Rest of the line belongs to a discarded object
+    discarding: bool,
+}
 
-                                // Handle event messages
-                                {
-                                    let handlers = event_handlers.lock().unwrap();
-                                    if let Some(handlers) = handlers.get(&msg.event) {
-                                        for handler in handlers {
-                                            handler(msg.payload.clone());
-                                        }
-                                    }
-                                }
+/// Item produced by `JsonFrameDecoder`
+#[derive(Debug)]
+enum StreamFrame {
+    /// Complete top-level JSON object
+    Json(String),
+    /// Plain-text line between objects
+    Text(String),
+    /// Object that exceeded the size limit
+    Oversized(IPCError),
+}
 
-                                // Call the general message handler
-                                on_message(msg);
-                            }
-                            Err(e) => {
-                                warn!("Failed to parse message from Node.js: {}", e);
-                            }
+impl JsonFrameDecoder {
+    fn new(max_frame_bytes: usize) -> Self {
+        JsonFrameDecoder {
+            buf: Vec::new(),
+            text: Vec::new(),
+            max_frame_bytes,
+            depth: 0,
+            in_string: false,
+            escaped: false,
+            skipping_line: false,
+            discarding: false,
+        }
+    }
+
+    /// Feed bytes into the decoder, returning any completed frames
+    ///
+    /// An object exceeding the size limit is dropped and reported as
+    /// `StreamFrame::Oversized`; decoding resumes at the next line.
+    fn push(&mut self, bytes: &[u8]) -> Vec<StreamFrame> {
+        let mut frames = Vec::new();
+
+        for &b in bytes {
+            if self.depth == 0 {
+                match b {
+                    b'\n' => {
+                        frames.extend(self.take_text());
+                        self.skipping_line = false;
+                        self.discarding = false;
+                    }
+                    b' ' | b'\t' | b'\r' if self.text.is_empty() => {}
+                    b'{' if !self.skipping_line => {
+                        self.buf.push(b);
+                        self.depth = 1;
+                    }
+                    _ => {
+                        self.skipping_line = true;
+                        if !self.discarding && self.text.len() < self.max_frame_bytes {
+                            self.text.push(b);
                         }
                     }
-                    Err(e) => {
-                        error!("Error reading from Node.js stdout: {}", e);
-                        break;
+                }
+                continue;
+            }
+
+            self.buf.push(b);
+            if self.buf.len() > self.max_frame_bytes {
+                frames.push(StreamFrame::Oversized(IPCError::ParseError(format!(
+                    "message exceeds {} bytes, discarded",
+                    self.max_frame_bytes
+                ))));
+                self.buf = Vec::new();
+                self.depth = 0;
+                self.in_string = false;
+                self.escaped = false;
+                self.skipping_line = b != b'\n';
+                self.discarding = self.skipping_line;
+                continue;
+            }
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if b == b'\\' {
+                    self.escaped = true;
+                } else if b == b'"' {
+                    self.in_string = false;
+                }
+                continue;
+            }
+
+            match b {
+                b'"' => self.in_string = true,
+                b'{' => self.depth += 1,
+                b'}' => {
+                    self.depth -= 1;
+                    if self.depth == 0 {
+                        frames.push(StreamFrame::Json(String::from_utf8_lossy(&self.buf).into_owned()));
+                        self.buf.clear();
                     }
                 }
+                _ => {}
             }
+        }
 
-            info!("stdout listener stopped");
-        });
+        frames
     }
 
-    /// Send an event to Node.js (fire and forget)
-    pub fn emit(&self, event: &str, payload: Value) -> Result<(), String> {
-        let msg = IPCMessage::event(event, payload);
-        self.send_to_node(&msg)
+    /// Flush a trailing plain-text line once the stream has ended
+    fn finish(&mut self) -> Option<StreamFrame> {
+        self.take_text()
     }
 
-    /// Send a request to Node.js and wait for response
-    pub fn request<F>(&self, event: &str, payload: Value, callback: F) -> Result<String, String>
-    where
-        F: FnOnce(Result<Value, String>) + Send + 'static,
-    {
-        let id = generate_request_id();
-        let msg = IPCMessage::request(&id, event, payload);
-
-        // Store the pending request with timeout info
-        {
-            let mut requests = self.pending_requests.lock().unwrap();
-            requests.insert(id.clone(), PendingRequest {
-                event: event.to_string(),
-                callback: Box::new(callback),
-                created_at: Instant::now(),
-                timeout: Duration::from_secs(self.request_timeout_secs),
-            });
+    fn take_text(&mut self) -> Option<StreamFrame> {
+        let text = std::mem::take(&mut self.text);
+        let line = String::from_utf8_lossy(&text);
+        let line = line.trim();
+        if line.is_empty() {
+            None
+        } else {
+            Some(StreamFrame::Text(line.to_string()))
         }
+    }
+}
 
-        // Send the request
-        self.send_to_node(&msg)?;
+/// Registered event handler
+type EventHandler = Box<dyn Fn(Value) + Send + 'static>;
 
-        Ok(id)
-    }
+/// Registered handler together with its ID
+struct HandlerEntry {
+    id: usize,
+    handler: EventHandler,
+    /// Remove the handler after its first invocation
+    once: bool,
+}
 
-    /// Send a request with custom timeout
-    pub fn request_with_timeout<F>(
-        &self,
-        event: &str,
-        payload: Value,
-        timeout_secs: u64,
-        callback: F,
-    ) -> Result<String, String>
-    where
-        F: FnOnce(Result<Value, String>) + Send + 'static,
-    {
-        let id = generate_request_id();
-        let msg = IPCMessage::request(&id, event, payload);
+/// Handler answering requests sent by Node.js
+type RequestHandler = Arc<dyn Fn(Value) -> Result<Value, String> + Send + Sync + 'static>;
 
-        // Store the pending request with custom timeout
-        {
-            let mut requests = self.pending_requests.lock().unwrap();
-            requests.insert(id.clone(), PendingRequest {
-                event: event.to_string(),
-                callback: Box::new(callback),
-                created_at: Instant::now(),
-                timeout: Duration::from_secs(timeout_secs),
-            });
-        }
+/// Destination for messages sent to Node.js (normally the child's stdin)
+type NodeWriter = Box<dyn Write + Send + 'static>;
 
-        // Send the request
-        self.send_to_node(&msg)?;
+/// Event handlers keyed by event name
+type EventHandlerMap = HashMap<String, Vec<HandlerEntry>>;
 
-        Ok(id)
-    }
+/// Registered handler for binary payloads
+type BinaryHandler = Box<dyn Fn(Vec<u8>) + Send + 'static>;
 
-    /// Start a background thread to check for timed out requests
-    pub fn start_timeout_checker(&self) {
-        let pending_requests = Arc::clone(&self.pending_requests);
+/// Binary handlers and their IDs, keyed by event name
+type BinaryHandlerMap = HashMap<String, Vec<(usize, BinaryHandler)>>;
 
-        thread::spawn(move || {
-            loop {
-                thread::sleep(Duration::from_secs(1));
+/// IPC Bridge manager for handling communication
+pub struct IPCBridge {
+    stdin: Arc<Mutex<Option<NodeWriter>>>,
+    pending_requests: Arc<Mutex<HashMap<String, PendingRequest>>>,
+    event_handlers: Arc<Mutex<EventHandlerMap>>,
+    binary_handlers: Arc<Mutex<BinaryHandlerMap>>,
+    /// Handlers answering Node.js requests, keyed by event name
+    request_handlers: Arc<Mutex<HashMap<String, RequestHandler>>>,
+    /// Source of IDs returned by `on`
+    next_handler_id: AtomicUsize,
+    /// Next outbound sequence number
+    next_seq: Arc<AtomicU64>,
+    /// Message queue for buffered sending when stdin is not ready
+    message_queue: Arc<Mutex<PriorityQueue<Queued>>>,
+    /// Maximum number of queued messages
+    max_queue_size: usize,
+    /// What to do when the queue is full
+    overflow_policy: QueueOverflowPolicy,
+    /// Default request timeout in seconds, adjustable at runtime
+    request_timeout_secs: AtomicU64,
+    /// Default timeouts in seconds for specific events, overriding the global one
+    event_timeouts: Mutex<HashMap<String, u64>>,
+    /// How the stdout stream is split into messages
+    framing_mode: FramingMode,
+    /// Encoding used on the pipes
+    wire_format: WireFormat,
+    /// Compression for large outbound messages, if enabled
+    compression: Option<CompressionConfig>,
+    /// Set by `shutdown` to stop the background threads
+    shutdown: Arc<AtomicBool>,
+    /// JSON Schemas for outbound payloads, keyed by event name
+    schemas: Arc<Mutex<HashMap<String, Value>>>,
+    /// Whether registered schemas are enforced
+    schema_validation: bool,
+    /// Request/response counters and latencies
+    metrics: Arc<IPCMetrics>,
+    /// Event name used for heartbeat pings
+    heartbeat_event: String,
+    /// Whether messages should be queued while stdin is unavailable
+    backend_expected: Arc<AtomicBool>,
+    /// Largest message accepted in either direction
+    max_message_bytes: usize,
+    /// Capacity of the buffer the stdout listener reads into
+    read_buffer_size: usize,
+    /// Queue depth watermarks and the `on_backpressure` callback
+    backpressure: Arc<Backpressure>,
+    /// Fails requests fast while the backend keeps timing out
+    circuit: Arc<CircuitBreaker>,
+    /// Requests that identical `request_coalesced` calls attach to
+    coalesced: Arc<Mutex<CoalescedMap>>,
+    /// Set by `drain`; new requests are rejected with `IPCError::ShuttingDown`
+    draining: Arc<AtomicBool>,
+    /// Cap on concurrent requests and the requests held back by it
+    limiter: Arc<RequestLimiter>,
+    /// Consecutive parse failures before `on_stream_corrupt` fires
+    corrupt_stream_threshold: usize,
+    stream_corrupt_callback: Arc<Mutex<Option<StreamCorruptCallback>>>,
+    /// Consecutive unparseable messages read from Node.js
+    parse_failures: Arc<AtomicUsize>,
+    /// Told about each inbound message discarded for exceeding the size limit
+    stream_error_callback: Arc<Mutex<Option<StreamErrorCallback>>>,
+    /// Receives responses that match no pending request
+    orphan_response_callback: Arc<Mutex<Option<OrphanResponseCallback>>>,
+    /// Told why the stdout listener stopped reading a stream
+    listener_ended_callback: Arc<Mutex<Option<ListenerEndedCallback>>>,
+    /// Version agreed by `handshake`, 0 until negotiated
+    negotiated_version: Arc<AtomicU32>,
+    /// Whether queued messages are held until the backend reports ready
+    ready_gate: bool,
+    /// Whether an invalid UTF-8 line ends the stdout listener instead of being decoded lossily
+    strict_utf8: bool,
+    /// Whether the backend may receive messages; always set without the gate
+    ready: Arc<AtomicBool>,
+    /// Told about every message sent or received
+    activity_callback: Arc<Mutex<Option<ActivityCallback>>>,
+    /// Tap into the message lifecycle, see `with_observer`
+    observer: Option<Arc<dyn IpcObserver>>,
+    /// Run in order on every message sent to Node.js
+    outbound_middleware: Arc<Mutex<Vec<Middleware>>>,
+    /// Run in order on every message received from Node.js
+    inbound_middleware: Arc<Mutex<Vec<Middleware>>>,
+    /// How long a write to Node.js may block before `on_write_stalled` fires
+    write_stall_timeout: Duration,
+    write_stalled_callback: Arc<Mutex<Option<WriteStalledCallback>>>,
+}
 
-                let mut requests = pending_requests.lock().unwrap();
-                let mut timed_out_ids = Vec::new();
+/// Default timeout for requests (30 seconds)
+const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
 
-                // Find timed out requests
-                for (id, request) in requests.iter() {
-                    if request.created_at.elapsed() > request.timeout {
-                        timed_out_ids.push(id.clone());
-                    }
-                }
+/// Default cap on a single encoded or received message (16MB)
+const DEFAULT_MAX_MESSAGE_BYTES: usize = 16 * 1024 * 1024;
 
-                // Handle timed out requests
-                for id in timed_out_ids {
-                    if let Some(request) = requests.remove(&id) {
-                        warn!("Request {} timed out after {:?}", id, request.timeout);
-                        (request.callback)(Err(format!(
-                            "Request timed out after {:?}",
-                            request.timeout
-                        )));
-                    }
-                }
-            }
-        });
-    }
+/// Default event name for heartbeat pings
+const DEFAULT_HEARTBEAT_EVENT: &str = "__ping__";
 
-    /// Register an event handler
-    pub fn on<F>(&self, event: &str, handler: F)
-    where
-        F: Fn(Value) + Send + 'static,
-    {
-        let mut handlers = self.event_handlers.lock().unwrap();
-        handlers
-            .entry(event.to_string())
-            .or_insert_with(Vec::new)
-            .push(Box::new(handler));
+/// Default capacity of the stdout read buffer (64KB)
+const DEFAULT_READ_BUFFER_SIZE: usize = 64 * 1024;
 
-        debug!("Registered handler for event: {}", event);
-    }
+/// Default maximum number of messages queued while stdin is unavailable
+const DEFAULT_MAX_QUEUE_SIZE: usize = 1000;
 
-    /// Send a message to Node.js via stdin
-    fn send_to_node(&self, msg: &IPCMessage) -> Result<(), String> {
-        let encoded = encode_message_for_stdin(msg)?;
+/// Default time a write to Node.js may block before it counts as stalled
+const DEFAULT_WRITE_STALL_TIMEOUT_SECS: u64 = 5;
 
-        let mut stdin_guard = self.stdin.lock().unwrap();
-        if let Some(ref mut stdin) = *stdin_guard {
-            stdin.write_all(encoded.as_bytes())
-                .map_err(|e| format!("Failed to write to Node.js stdin: {}", e))?;
-            stdin.flush()
-                .map_err(|e| format!("Failed to flush Node.js stdin: {}", e))?;
+/// Default number of consecutive unparseable messages that marks the stream as corrupt
+const DEFAULT_CORRUPT_STREAM_THRESHOLD: usize = 10;
 
-            debug!("Sent to Node.js: {}", msg.event);
+/// Default interval between timeout checker scans (100ms)
+const DEFAULT_TIMEOUT_CHECK_INTERVAL_MS: u64 = 100;
+
+/// How often blocking waits such as `drain` re-check their condition (10ms)
+const WAIT_POLL_INTERVAL_MS: u64 = 10;
+
+/// Callback invoked with the failure count once the stdout stream looks corrupt
+type StreamCorruptCallback = Box<dyn Fn(usize) + Send + 'static>;
+
+/// Callback for an inbound message discarded by the stdout listener
+type StreamErrorCallback = Box<dyn Fn(IPCError) + Send + 'static>;
+
+/// Callback for responses that match no pending request
+type OrphanResponseCallback = Box<dyn Fn(IPCMessage) + Send + 'static>;
+
+/// Why the stdout listener stopped reading a stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenerEndReason {
+    /// Node.js closed its stdout
+    Eof,
+    /// Reading failed; carries the IO error
+    Error(String),
+    /// `shutdown` was called
+    Shutdown,
+}
+
+/// Callback for the end of a stdout stream
+type ListenerEndedCallback = Box<dyn Fn(ListenerEndReason) + Send + 'static>;
+
+/// Callback told how long a write to Node.js has been blocked
+type WriteStalledCallback = Box<dyn Fn(Duration) + Send + 'static>;
+
+/// Callback run on every message sent or received
+type ActivityCallback = Box<dyn Fn() + Send + 'static>;
+
+/// Transformation applied to messages on their way to or from Node.js
+type Middleware = Arc<dyn Fn(&mut IPCMessage) + Send + Sync + 'static>;
+
+/// Callback receiving each partial result of a streaming request
+type ChunkCallback = Arc<dyn Fn(Value) + Send + Sync + 'static>;
+
+struct PendingRequest {
+    event: String,
+    callback: RequestCallback,
+    /// Set for streaming requests, which accept chunks before the response
+    on_chunk: Option<ChunkCallback>,
+    /// When the request was created
+    created_at: Instant,
+    /// When the timeout checker fails the request
+    deadline: Instant,
+}
+
+impl PendingRequest {
+    /// Time the request was given to complete
+    fn timeout(&self) -> Duration {
+        self.deadline.saturating_duration_since(self.created_at)
+    }
+}
+
+/// When a request expires
+#[derive(Debug, Clone, Copy)]
+enum Expiry {
+    /// A timeout counted from when the request is sent
+    After(Duration),
+    /// An absolute deadline, e.g. shared by several sequential requests
+    At(Instant),
+}
+
+impl Expiry {
+    fn deadline(self, sent_at: Instant) -> Instant {
+        match self {
+            Expiry::After(timeout) => sent_at + timeout,
+            Expiry::At(deadline) => deadline,
+        }
+    }
+}
+
+/// Snapshot of an outstanding request, for debugging
+#[derive(Debug, Clone)]
+pub struct PendingRequestInfo {
+    /// Request ID
+    pub id: String,
+    /// Event name the request was sent with
+    pub event: String,
+    /// Time since the request was sent
+    pub age: Duration,
+    /// Timeout configured for the request
+    pub timeout: Duration,
+}
+
+type RequestCallback = Box<dyn FnOnce(Result<Value, IPCError>) + Send + 'static>;
+
+/// Callback shared across the attempts of a retried request
+type SharedCallback = Arc<Mutex<Option<RequestCallback>>>;
+
+/// In-flight request shared by every `request_coalesced` caller with the same key
+struct CoalescedRequest {
+    id: String,
+    waiters: Vec<RequestCallback>,
+}
+
+/// Coalesced requests keyed by event name and serialized payload
+type CoalescedMap = HashMap<String, CoalescedRequest>;
+
+/// Request accepted by `send_request` but not yet sent
+struct WaitingRequest {
+    /// Sender the request was made through, keeping its priority and mode
+    sender: RequestSender,
+    id: String,
+    event: String,
+    payload: Value,
+    expiry: Expiry,
+    callback: RequestCallback,
+    on_chunk: Option<ChunkCallback>,
+}
+
+impl WaitingRequest {
+    /// Register the request as pending and write it to Node.js
+    ///
+    /// The request holds a concurrency slot until its callback runs or is
+    /// dropped. On a send failure the pending entry is removed and its
+    /// callback handed back. An ID that is already pending is rejected
+    /// rather than overwriting, which would orphan the first callback.
+    fn dispatch(self) -> Result<(), (IPCError, Option<RequestCallback>)> {
+        let slot = SlotGuard(Arc::clone(&self.sender.limiter));
+        let callback = self.callback;
+        let msg = IPCMessage::request(&self.id, &self.event, self.payload);
+
+        // Store the pending request with timeout info
+        {
+            let now = Instant::now();
+            let mut requests = self.sender.pending_requests.lock().unwrap();
+            if requests.contains_key(&self.id) {
+                warn!("Rejecting request {}: duplicate request id", log_label(&msg));
+                return Err((IPCError::Other("duplicate request id".to_string()), Some(callback)));
+            }
+            requests.insert(self.id.clone(), PendingRequest {
+                event: self.event,
+                callback: Box::new(move |result| {
+                    drop(slot);
+                    callback(result);
+                }),
+                on_chunk: self.on_chunk,
+                created_at: now,
+                deadline: self.expiry.deadline(now),
+            });
+        }
+
+        // Send the request, dropping the pending entry if it never went out
+        if let Err(e) = self.sender.send(&msg) {
+            debug!("Request {} not sent: {}", log_label(&msg), e);
+            let pending = self.sender.pending_requests.lock().unwrap().remove(&self.id);
+            return Err((e, pending.map(|pending| pending.callback)));
+        }
+        self.sender.metrics.record_request_sent();
+        Ok(())
+    }
+}
+
+/// Cap on requests in flight, holding back the overflow
+#[derive(Default)]
+struct RequestLimiter {
+    /// Maximum requests in flight, 0 for no limit
+    max: AtomicUsize,
+    /// Requests sent and not yet completed
+    in_flight: AtomicUsize,
+    /// Requests held back until a slot frees, oldest first
+    waiting: Mutex<VecDeque<WaitingRequest>>,
+}
+
+impl RequestLimiter {
+    /// Take a slot for `request`, or hold it back if none is free
+    ///
+    /// Returns the request if it may be sent now. Held-back requests keep
+    /// their order: a new request never overtakes a waiting one.
+    fn admit(&self, request: WaitingRequest) -> Option<WaitingRequest> {
+        let mut waiting = self.waiting.lock().unwrap();
+        let max = self.max.load(Ordering::SeqCst);
+        if max != 0 && (self.in_flight.load(Ordering::SeqCst) >= max || !waiting.is_empty()) {
+            debug!("Holding back request {} [{}], {} in flight", request.event, request.id, self.in_flight.load(Ordering::SeqCst));
+            waiting.push_back(request);
+            return None;
+        }
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        Some(request)
+    }
+
+    /// Give back a slot, sending the next held-back request if any
+    ///
+    /// May run while the pending map is locked (a pending entry being
+    /// dropped), so the waiting requests are sent from another thread.
+    fn release(self: &Arc<Self>) {
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        if !self.waiting.lock().unwrap().is_empty() {
+            let limiter = Arc::clone(self);
+            thread::spawn(move || limiter.pump());
+        }
+    }
+
+    /// Send held-back requests while slots are free
+    fn pump(&self) {
+        loop {
+            let request = {
+                let mut waiting = self.waiting.lock().unwrap();
+                let max = self.max.load(Ordering::SeqCst);
+                if max != 0 && self.in_flight.load(Ordering::SeqCst) >= max {
+                    return;
+                }
+                let Some(request) = waiting.pop_front() else {
+                    return;
+                };
+                self.in_flight.fetch_add(1, Ordering::SeqCst);
+                request
+            };
+            debug!("Sending held-back request {} [{}]", request.event, request.id);
+            if let Err((e, Some(callback))) = request.dispatch() {
+                callback(Err(e));
+            }
+        }
+    }
+
+    /// Remove a held-back request before it is sent
+    fn remove_waiting(&self, id: &str) -> Option<WaitingRequest> {
+        let mut waiting = self.waiting.lock().unwrap();
+        let index = waiting.iter().position(|request| request.id == id)?;
+        waiting.remove(index)
+    }
+}
+
+/// Concurrency slot held by a sent request, released on drop
+struct SlotGuard(Arc<RequestLimiter>);
+
+impl Drop for SlotGuard {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+/// Cloneable handle to the bridge's outgoing path
+///
+/// Lets callbacks running on the listener or timeout-checker thread send
+/// follow-up requests (e.g. retries) without holding a reference to the bridge.
+#[derive(Clone)]
+struct RequestSender {
+    stdin: Arc<Mutex<Option<NodeWriter>>>,
+    message_queue: Arc<Mutex<PriorityQueue<Queued>>>,
+    pending_requests: Arc<Mutex<HashMap<String, PendingRequest>>>,
+    next_seq: Arc<AtomicU64>,
+    max_queue_size: usize,
+    overflow_policy: QueueOverflowPolicy,
+    wire_format: WireFormat,
+    compression: Option<CompressionConfig>,
+    /// Registered payload schemas, `None` when validation is disabled
+    schemas: Option<Arc<Mutex<HashMap<String, Value>>>>,
+    metrics: Arc<IPCMetrics>,
+    /// Cleared when the backend is not expected to start
+    backend_expected: Arc<AtomicBool>,
+    /// Fail instead of queueing when stdin is not available
+    fail_fast: bool,
+    max_message_bytes: usize,
+    backpressure: Arc<Backpressure>,
+    circuit: Arc<CircuitBreaker>,
+    draining: Arc<AtomicBool>,
+    limiter: Arc<RequestLimiter>,
+    negotiated_version: Arc<AtomicU32>,
+    /// Cleared while a ready-gated backend has not reported ready
+    ready: Arc<AtomicBool>,
+    /// Priority of messages this sender queues
+    priority: Priority,
+    activity: Arc<Mutex<Option<ActivityCallback>>>,
+    observer: Option<Arc<dyn IpcObserver>>,
+    outbound_middleware: Arc<Mutex<Vec<Middleware>>>,
+}
+
+impl RequestSender {
+    /// Write a message to stdin, or queue it if stdin is not available yet
+    ///
+    /// The message is stamped with the next sequence number. While older
+    /// messages are still queued it goes behind them, so Node.js always
+    /// receives messages in sequence order (per priority level).
+    fn send(&self, msg: &IPCMessage) -> Result<(), IPCError> {
+        let mut msg = msg.clone();
+        self.transform(&mut msg);
+        self.validate(&msg)?;
+        self.touch();
+
+        // Sequence numbers are assigned under the stdin lock so they match wire order
+        let mut stdin_guard = self.stdin.lock().unwrap();
+        msg.seq = Some(self.next_seq.load(Ordering::SeqCst));
+        let version = self.negotiated_version.load(Ordering::SeqCst);
+        if version != 0 {
+            msg.version = Some(version);
+        }
+
+        let encoded = encode_message_compressed(&msg, self.wire_format, self.compression)
+            .map_err(IPCError::SerializationError)?;
+        if encoded.len() > self.max_message_bytes {
+            return Err(IPCError::SerializationError(format!(
+                "message {} is {} bytes, exceeding the {} byte limit",
+                msg.event,
+                encoded.len(),
+                self.max_message_bytes
+            )));
+        }
+
+        self.deliver(&mut stdin_guard, msg.into(), &encoded)
+    }
+
+    /// Write pre-encoded bytes to stdin as is, or queue them
+    ///
+    /// The bytes skip middleware, validation and compression, and take no
+    /// sequence number, but keep their place in line like any message.
+    fn send_raw(&self, bytes: Vec<u8>) -> Result<(), IPCError> {
+        if bytes.len() > self.max_message_bytes {
+            return Err(IPCError::SerializationError(format!(
+                "raw line is {} bytes, exceeding the {} byte limit",
+                bytes.len(),
+                self.max_message_bytes
+            )));
+        }
+        self.touch();
+
+        let mut stdin_guard = self.stdin.lock().unwrap();
+        self.deliver(&mut stdin_guard, Queued::Raw(bytes.clone()), &bytes)
+    }
+
+    /// Write an encoded entry to stdin, or queue it if stdin is not available
+    ///
+    /// Called under the stdin lock. A message consumes the sequence number it
+    /// was stamped with once it is written or queued.
+    fn deliver(&self, stdin_guard: &mut Option<NodeWriter>, entry: Queued, encoded: &[u8]) -> Result<(), IPCError> {
+        let seq_used = u64::from(entry.message().is_some());
+        if stdin_guard.is_some() && !self.ready.load(Ordering::SeqCst) {
+            debug!("Backend not ready, queueing message: {}", entry.label());
+            self.enqueue(entry)?;
+            self.next_seq.fetch_add(seq_used, Ordering::SeqCst);
+            return Ok(());
+        }
+
+        if let Some(stdin) = stdin_guard.as_mut() {
+            self.next_seq.fetch_add(seq_used, Ordering::SeqCst);
+            match self.flush_ahead(stdin) {
+                Err(e) => return self.write_failed(stdin_guard, e, vec![entry]),
+                Ok(false) => {
+                    debug!("Writer is backed up, queueing message: {}", entry.label());
+                    return self.enqueue(entry);
+                }
+                Ok(true) => {}
+            }
+
+            if let Err(e) = stdin.write_all(encoded).and_then(|_| stdin.flush()) {
+                return self.write_failed(stdin_guard, e, vec![entry]);
+            }
+            self.metrics.record_bytes_sent(encoded.len());
+
+            debug!("Sent to Node.js: {}", entry.label());
+            if let (Some(observer), Some(msg)) = (&self.observer, entry.message()) {
+                observer.on_send(msg);
+            }
             Ok(())
+        } else if self.fail_fast || !self.backend_expected.load(Ordering::SeqCst) {
+            debug!("Stdin not available, failing fast: {}", entry.label());
+            Err(IPCError::StdinNotAvailable)
         } else {
             // Queue the message if stdin is not available yet
-            debug!("Stdin not available, queueing message: {}", msg.event);
-            let mut queue = self.message_queue.lock().unwrap();
-            queue.push_back(msg.clone());
+            debug!("Stdin not available, queueing message: {}", entry.label());
+            self.enqueue(entry)?;
+            self.next_seq.fetch_add(seq_used, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    /// Write several messages with one write and one flush
+    ///
+    /// All messages are validated and encoded before anything is written, so
+    /// an invalid message fails the whole batch. They get consecutive sequence
+    /// numbers and are never interleaved with other threads' messages.
+    fn send_batch(&self, mut msgs: Vec<IPCMessage>) -> Result<(), IPCError> {
+        for msg in &mut msgs {
+            self.transform(msg);
+            self.validate(msg)?;
+        }
+        self.touch();
+
+        let mut stdin_guard = self.stdin.lock().unwrap();
+        let first_seq = self.next_seq.load(Ordering::SeqCst);
+        let version = self.negotiated_version.load(Ordering::SeqCst);
+        let mut batch = Vec::with_capacity(msgs.len());
+        let mut encoded = Vec::new();
+        for (offset, mut msg) in msgs.into_iter().enumerate() {
+            msg.seq = Some(first_seq + offset as u64);
+            if version != 0 {
+                msg.version = Some(version);
+            }
+            let frame = encode_message_compressed(&msg, self.wire_format, self.compression)
+                .map_err(IPCError::SerializationError)?;
+            if frame.len() > self.max_message_bytes {
+                return Err(IPCError::SerializationError(format!(
+                    "message {} is {} bytes, exceeding the {} byte limit",
+                    msg.event,
+                    frame.len(),
+                    self.max_message_bytes
+                )));
+            }
+            encoded.extend_from_slice(&frame);
+            batch.push(msg);
+        }
+        let count = batch.len() as u64;
+
+        if stdin_guard.is_some() && !self.ready.load(Ordering::SeqCst) {
+            debug!("Backend not ready, queueing batch of {}", count);
+            self.enqueue_batch(batch)?;
+            self.next_seq.fetch_add(count, Ordering::SeqCst);
+            return Ok(());
+        }
+
+        if let Some(ref mut stdin) = *stdin_guard {
+            self.next_seq.fetch_add(count, Ordering::SeqCst);
+            match self.flush_ahead(stdin) {
+                Err(e) => return self.write_failed(&mut stdin_guard, e, batch.into_iter().map(Queued::from).collect()),
+                Ok(false) => {
+                    debug!("Writer is backed up, queueing batch of {}", count);
+                    return self.enqueue_batch(batch);
+                }
+                Ok(true) => {}
+            }
+
+            if let Err(e) = stdin.write_all(&encoded).and_then(|_| stdin.flush()) {
+                return self.write_failed(&mut stdin_guard, e, batch.into_iter().map(Queued::from).collect());
+            }
+            self.metrics.record_bytes_sent(encoded.len());
+
+            debug!("Sent batch of {} message(s) to Node.js", count);
+            if let Some(observer) = &self.observer {
+                batch.iter().for_each(|msg| observer.on_send(msg));
+            }
             Ok(())
+        } else if self.fail_fast || !self.backend_expected.load(Ordering::SeqCst) {
+            debug!("Stdin not available, failing fast: batch of {}", count);
+            Err(IPCError::StdinNotAvailable)
+        } else {
+            debug!("Stdin not available, queueing batch of {}", count);
+            self.enqueue_batch(batch)?;
+            self.next_seq.fetch_add(count, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    /// Write what is already queued, so a new message cannot overtake it
+    ///
+    /// Called under the stdin lock. Returns whether the queue was emptied;
+    /// a write error is returned as is for `write_failed`.
+    fn flush_ahead(&self, stdin: &mut NodeWriter) -> std::io::Result<bool> {
+        let mut queue = self.message_queue.lock().unwrap();
+        if queue.is_empty() {
+            return Ok(true);
+        }
+        debug!("Queue not empty, flushing {} queued message(s) first", queue.len());
+        let result = write_queued(stdin, &mut queue, self.wire_format, self.compression, self.observer.as_deref(), &self.metrics);
+        let depth = queue.len();
+        drop(queue);
+        self.backpressure.update(depth);
+        result.map(|_| depth == 0)
+    }
+
+    /// Handle a failed write to stdin
+    ///
+    /// A full writer thread (`WouldBlock`) only queues the entries for the
+    /// next flush. A broken pipe means the backend died before the monitor
+    /// replaced stdin: the stale stdin is cleared, the messages are queued
+    /// again for delivery after reconnect, and `IPCError::StdinNotAvailable`
+    /// is returned. Requests are not re-queued then, since their caller is
+    /// told the send failed and may retry.
+    fn write_failed(
+        &self,
+        stdin: &mut Option<NodeWriter>,
+        err: std::io::Error,
+        entries: Vec<Queued>,
+    ) -> Result<(), IPCError> {
+        match err.kind() {
+            ErrorKind::WouldBlock => {
+                debug!("Node.js stdin is backed up, queueing {} message(s)", entries.len());
+                return self.enqueue_batch(entries);
+            }
+            ErrorKind::BrokenPipe => {}
+            _ => return Err(IPCError::SendError(format!("Failed to write to Node.js stdin: {}", err))),
+        }
+
+        warn!("Node.js stdin is closed, clearing it until the backend reconnects");
+        *stdin = None;
+        for entry in entries {
+            if entry.message().is_some_and(|msg| matches!(msg.msg_type, IPCMessageType::Request)) {
+                continue;
+            }
+            debug!("Re-queueing {} after broken pipe", entry.label());
+            if let Err(e) = self.enqueue(entry) {
+                warn!("Failed to re-queue message after broken pipe: {}", e);
+            }
+        }
+        Err(IPCError::StdinNotAvailable)
+    }
+
+    /// Report traffic to the `on_activity` callback
+    ///
+    /// Runs before the stdin lock is taken, so the callback may reconnect the bridge.
+    fn touch(&self) {
+        if let Some(callback) = self.activity.lock().unwrap().as_ref() {
+            callback();
+        }
+    }
+
+    /// Stamp a message with the next sequence number and queue it
+    ///
+    /// The outbound middleware is not run here; callers queuing a new message run it first.
+    fn queue(&self, mut msg: IPCMessage) -> Result<(), IPCError> {
+        let _stdin_guard = self.stdin.lock().unwrap();
+        msg.seq = Some(self.next_seq.load(Ordering::SeqCst));
+        self.enqueue(msg.into())?;
+        self.next_seq.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Run the outbound middleware over a message, in registration order
+    ///
+    /// The list is copied out first, so a middleware may use the bridge.
+    fn transform(&self, msg: &mut IPCMessage) {
+        let middlewares = self.outbound_middleware.lock().unwrap().clone();
+        for middleware in middlewares {
+            middleware(msg);
+        }
+    }
+
+    /// Check an outbound payload against the schema registered for its event
+    fn validate(&self, msg: &IPCMessage) -> Result<(), IPCError> {
+        if matches!(msg.msg_type, IPCMessageType::Response) {
+            return Ok(());
+        }
+        let schemas = match &self.schemas {
+            Some(schemas) => schemas.lock().unwrap(),
+            None => return Ok(()),
+        };
+        if let Some(event_schema) = schemas.get(&msg.event) {
+            schema::validate(event_schema, &msg.payload).map_err(|e| {
+                IPCError::SerializationError(format!("invalid payload for {}: {}", msg.event, e))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Write queued messages to stdin, unless the backend is not ready yet
+    fn flush_queue(&self) -> Result<usize, IPCError> {
+        // Lock order is stdin then queue, matching the send path
+        let mut stdin_guard = self.stdin.lock().unwrap();
+        let mut queue = self.message_queue.lock().unwrap();
+
+        let stdin = match stdin_guard.as_mut() {
+            Some(stdin) => stdin,
+            None => return Err(IPCError::StdinNotAvailable),
+        };
+        if !self.ready.load(Ordering::SeqCst) {
+            return Ok(0);
+        }
+        let result = write_queued(stdin, &mut queue, self.wire_format, self.compression, self.observer.as_deref(), &self.metrics);
+        let depth = queue.len();
+        drop(queue);
+        self.backpressure.update(depth);
+        result.map_err(|e| IPCError::SendError(e.to_string()))
+    }
+
+    /// Open the ready gate and flush everything held behind it
+    fn mark_ready(&self) {
+        if !self.ready.swap(true, Ordering::SeqCst) {
+            info!("Node.js backend is ready");
+        }
+        match self.flush_queue() {
+            Ok(0) | Err(IPCError::StdinNotAvailable) => {}
+            Ok(count) => debug!("Flushed {} queued message(s)", count),
+            Err(e) => error!("Failed to flush message queue: {}", e),
+        }
+    }
+
+    /// Push a message onto the queue, applying the overflow policy
+    fn enqueue(&self, entry: Queued) -> Result<(), IPCError> {
+        let depth = self.push_queued(entry)?;
+        self.backpressure.update(depth);
+        Ok(())
+    }
+
+    /// Queue a whole batch, or nothing if it does not fit
+    ///
+    /// With `QueueOverflowPolicy::Reject` a batch that exceeds the remaining
+    /// capacity is rejected as a whole; the drop policies make room as they
+    /// do for single messages.
+    fn enqueue_batch<T: Into<Queued>>(&self, batch: Vec<T>) -> Result<(), IPCError> {
+        let mut queue = self.message_queue.lock().unwrap();
+        if self.overflow_policy == QueueOverflowPolicy::Reject && queue.len() + batch.len() > self.max_queue_size {
+            return Err(IPCError::SendError(format!(
+                "message queue full ({} of {} messages), rejected batch of {}",
+                queue.len(),
+                self.max_queue_size,
+                batch.len()
+            )));
+        }
+        for msg in batch {
+            self.push_locked(&mut queue, msg.into())?;
+        }
+        let depth = queue.len();
+        drop(queue);
+        self.backpressure.update(depth);
+        Ok(())
+    }
+
+    /// Push a message onto the queue, returning the resulting depth
+    fn push_queued(&self, entry: Queued) -> Result<usize, IPCError> {
+        let mut queue = self.message_queue.lock().unwrap();
+        self.push_locked(&mut queue, entry)?;
+        Ok(queue.len())
+    }
+
+    /// Push a message onto the locked queue, applying the overflow policy
+    fn push_locked(&self, queue: &mut PriorityQueue<Queued>, entry: Queued) -> Result<(), IPCError> {
+        if queue.len() >= self.max_queue_size {
+            match self.overflow_policy {
+                QueueOverflowPolicy::DropOldest => {
+                    if let Some((_, dropped)) = queue.pop_lowest() {
+                        warn!("Message queue full, dropping oldest message: {}", dropped.label());
+                    }
+                    if self.max_queue_size == 0 {
+                        return Ok(());
+                    }
+                }
+                QueueOverflowPolicy::DropNewest => {
+                    warn!("Message queue full, dropping new message: {}", entry.label());
+                    return Ok(());
+                }
+                QueueOverflowPolicy::Reject => {
+                    return Err(IPCError::SendError(format!(
+                        "message queue full ({} messages), rejected: {}",
+                        self.max_queue_size,
+                        entry.label()
+                    )));
+                }
+            }
+        }
+        debug!("Message queued: {} ({:?} priority), queue size: {}", entry.label(), self.priority, queue.len() + 1);
+        queue.push_back(self.priority, entry);
+        Ok(())
+    }
+
+    /// Send one attempt of a retried request
+    ///
+    /// On an error or timeout the request is re-sent with a fresh ID after
+    /// `backoff`, until `attempts_left` runs out. The shared callback fires once.
+    fn send_attempt(
+        &self,
+        event: String,
+        payload: Value,
+        timeout: Duration,
+        attempts_left: u32,
+        backoff: Duration,
+        callback: SharedCallback,
+    ) -> Result<String, IPCError> {
+        let sender = self.clone();
+        let retry_event = event.clone();
+        let retry_payload = payload.clone();
+
+        self.send_request(&event, payload, timeout, Box::new(move |result| {
+            match result {
+                Err(e) if attempts_left > 1 => {
+                    warn!("Request {} failed: {}. Retrying in {:?} ({} attempt(s) left)",
+                          retry_event, e, backoff, attempts_left - 1);
+                    thread::spawn(move || {
+                        thread::sleep(backoff);
+                        let resent = sender.send_attempt(
+                            retry_event,
+                            retry_payload,
+                            timeout,
+                            attempts_left - 1,
+                            backoff,
+                            Arc::clone(&callback),
+                        );
+                        if let Err(e) = resent {
+                            let callback = callback.lock().unwrap().take();
+                            if let Some(callback) = callback {
+                                callback(Err(e));
+                            }
+                        }
+                    });
+                }
+                result => {
+                    let callback = callback.lock().unwrap().take();
+                    if let Some(callback) = callback {
+                        callback(result);
+                    }
+                }
+            }
+        }))
+    }
+
+    /// Register a pending request and send it
+    fn send_request(
+        &self,
+        event: &str,
+        payload: Value,
+        timeout: Duration,
+        callback: RequestCallback,
+    ) -> Result<String, IPCError> {
+        self.send_stream_request(event, payload, Expiry::After(timeout), None, callback)
+    }
+
+    /// Register a pending request that may receive chunks, and send it
+    fn send_stream_request(
+        &self,
+        event: &str,
+        payload: Value,
+        expiry: Expiry,
+        on_chunk: Option<ChunkCallback>,
+        callback: RequestCallback,
+    ) -> Result<String, IPCError> {
+        if self.draining.load(Ordering::SeqCst) {
+            debug!("Rejecting request {} while draining", event);
+            return Err(IPCError::ShuttingDown);
+        }
+        if !self.circuit.allow() {
+            debug!("Rejecting request {}: circuit open", event);
+            return Err(IPCError::Other("circuit open".to_string()));
+        }
+        let circuit = Arc::clone(&self.circuit);
+        let callback: RequestCallback = Box::new(move |result| {
+            circuit.record(&result);
+            callback(result);
+        });
+        let id = generate_request_id();
+        let request = WaitingRequest {
+            sender: self.clone(),
+            id: id.clone(),
+            event: event.to_string(),
+            payload,
+            expiry,
+            callback,
+            on_chunk,
+        };
+
+        // Requests over the concurrency cap are sent once a slot frees
+        if let Some(request) = self.limiter.admit(request) {
+            request.dispatch().map_err(|(e, _)| {
+                self.circuit.record(&Err(e.clone()));
+                e
+            })?;
+        }
+        Ok(id)
+    }
+}
+
+/// Reads messages from Node.js output and reports what it cannot read
+///
+/// Used by the stdout listener for every framing, and by `process_line`, so
+/// a line is handled the same way by both.
+struct InboundStream {
+    max_message_bytes: usize,
+    strict_utf8: bool,
+    corrupt_stream_threshold: usize,
+    observer: Option<Arc<dyn IpcObserver>>,
+    stream_corrupt_callback: Arc<Mutex<Option<StreamCorruptCallback>>>,
+    stream_error_callback: Arc<Mutex<Option<StreamErrorCallback>>>,
+    /// Consecutive parse failures, reset by any message that parses
+    parse_failures: Arc<AtomicUsize>,
+}
+
+impl InboundStream {
+    fn report_stream_error(&self, err: IPCError) {
+        error!("{}", err);
+        if let Some(callback) = self.stream_error_callback.lock().unwrap().as_ref() {
+            callback(err);
+        }
+    }
+
+    fn report_oversized(&self, len: usize) {
+        self.report_stream_error(IPCError::ParseError(format!(
+            "message of {} bytes exceeds the {} byte limit, discarded",
+            len, self.max_message_bytes
+        )));
+    }
+
+    fn record_parse_failure(&self, raw: &[u8], e: String) {
+        warn!("Failed to parse message from Node.js: {}", e);
+        if let Some(observer) = &self.observer {
+            observer.on_parse_error(raw, &e);
+        }
+        let failures = self.parse_failures.fetch_add(1, Ordering::SeqCst) + 1;
+        if failures == self.corrupt_stream_threshold {
+            error!("{} consecutive unparseable messages from Node.js, stream looks corrupt", failures);
+            if let Some(callback) = self.stream_corrupt_callback.lock().unwrap().as_ref() {
+                callback(failures);
+            }
+        }
+    }
+
+    /// A message parsed, so the stream is not corrupt
+    fn parsed(&self) {
+        self.parse_failures.store(0, Ordering::SeqCst);
+    }
+
+    /// Parse one JSON message and hand it to `deliver`
+    fn handle_message<D: Fn(IPCMessage)>(&self, content: &str, deliver: &D) {
+        debug!("Received from Node.js: {}", content);
+
+        match parse_stdin_message(content) {
+            Ok(msg) => {
+                self.parsed();
+                deliver(msg);
+            }
+            Err(e) => self.record_parse_failure(content.as_bytes(), e),
+        }
+    }
+
+    /// Read and handle one line of newline-framed output
+    ///
+    /// A compression header line is followed by a length-prefixed frame read
+    /// from the same reader. Returns why the stream ended, if it did.
+    fn read_line<B, G, D>(&self, reader: &mut B, on_raw_line: &G, deliver: &D) -> Result<(), ListenerEndReason>
+    where
+        B: BufRead,
+        G: Fn(String),
+        D: Fn(IPCMessage),
+    {
+        let line = match read_line_limited(reader, self.max_message_bytes) {
+            Ok(RawFrame::Complete(line)) => line,
+            Ok(RawFrame::TooLong(len)) => {
+                self.report_oversized(len);
+                return Ok(());
+            }
+            Ok(RawFrame::Eof) => return Err(ListenerEndReason::Eof),
+            Err(e) => return Err(ListenerEndReason::Error(e.to_string())),
+        };
+        let content = match String::from_utf8(line) {
+            Ok(content) => content,
+            Err(_) if self.strict_utf8 => {
+                return Err(ListenerEndReason::Error("stream did not contain valid UTF-8".to_string()));
+            }
+            Err(e) => {
+                warn!("Node.js stdout line is not valid UTF-8, decoding lossily");
+                String::from_utf8_lossy(e.as_bytes()).into_owned()
+            }
+        };
+
+        let trimmed = content.trim();
+        if trimmed.is_empty() {
+            return Ok(());
+        }
+
+        // A compression header line is followed by a length-prefixed frame
+        if let Some(header) = CompressionHeader::from_json(trimmed) {
+            let body = match read_length_prefixed_frame(reader, self.max_message_bytes) {
+                Ok(RawFrame::Complete(body)) => body,
+                Ok(RawFrame::TooLong(len)) => {
+                    self.report_oversized(len);
+                    return Ok(());
+                }
+                Ok(RawFrame::Eof) => return Err(ListenerEndReason::Eof),
+                Err(e) => return Err(ListenerEndReason::Error(e.to_string())),
+            };
+            let text = compression::decompress(&header, &body, self.max_message_bytes).and_then(|data| {
+                String::from_utf8(data).map_err(|e| format!("Failed to parse message: {}", e))
+            });
+            match text {
+                Ok(text) => {
+                    on_raw_line(text.clone());
+                    self.handle_message(&text, deliver);
+                }
+                Err(e) => self.record_parse_failure(&body, e),
+            }
+            return Ok(());
+        }
+        on_raw_line(trimmed.to_string());
+
+        // Plain-text log output is not an IPC message
+        if !trimmed.starts_with('{') {
+            debug!("Skipping non-JSON output from Node.js: {}", trimmed);
+            return Ok(());
         }
+
+        self.handle_message(trimmed, deliver);
+        Ok(())
+    }
+}
+
+/// Routes messages read from Node.js to pending requests and handlers
+///
+/// Holds the bridge state the stdout listener needs, so dispatching works
+/// both on the listener thread and synchronously through `process_line`.
+struct Dispatcher {
+    pending_requests: Arc<Mutex<HashMap<String, PendingRequest>>>,
+    event_handlers: Arc<Mutex<EventHandlerMap>>,
+    binary_handlers: Arc<Mutex<BinaryHandlerMap>>,
+    request_handlers: Arc<Mutex<HashMap<String, RequestHandler>>>,
+    metrics: Arc<IPCMetrics>,
+    observer: Option<Arc<dyn IpcObserver>>,
+    orphan_response_callback: Arc<Mutex<Option<OrphanResponseCallback>>>,
+    inbound_middleware: Arc<Mutex<Vec<Middleware>>>,
+    sender: RequestSender,
+}
+
+impl Dispatcher {
+    /// Deliver a message to its pending request or handlers, then to `on_message`
+    fn dispatch<F: Fn(IPCMessage)>(&self, msg: IPCMessage, on_message: &F) {
+        let msg = self.receive(msg);
+        self.route(msg, on_message);
+    }
+
+    /// Record a received message and run the inbound middleware over it
+    fn receive(&self, mut msg: IPCMessage) -> IPCMessage {
+        self.sender.touch();
+        let middlewares = self.inbound_middleware.lock().unwrap().clone();
+        for middleware in middlewares {
+            middleware(&mut msg);
+        }
+        if let Some(observer) = &self.observer {
+            observer.on_receive(&msg);
+        }
+        msg
+    }
+
+    /// Route a received message to its pending request or handlers, then to `on_message`
+    fn route<F: Fn(IPCMessage)>(&self, msg: IPCMessage, on_message: &F) {
+        // Reject messages written for a protocol version we cannot handle
+        if let Some(version) = msg.version.filter(|v| !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(v)) {
+            let err = IPCError::UnsupportedVersion(version);
+            error!("Rejecting message {} from Node.js: {}", log_label(&msg), err);
+            let pending = match (&msg.msg_type, &msg.id) {
+                (IPCMessageType::Response, Some(id)) => self.pending_requests.lock().unwrap().remove(id),
+                _ => None,
+            };
+            if let Some(pending) = pending {
+                (pending.callback)(Err(err));
+            }
+            return;
+        }
+
+        // Deliver partial results of streaming requests
+        if matches!(msg.msg_type, IPCMessageType::Chunk) {
+            let on_chunk = msg.id.as_ref().and_then(|id| {
+                self.pending_requests.lock().unwrap().get(id).and_then(|pending| pending.on_chunk.clone())
+            });
+            match on_chunk {
+                Some(on_chunk) => on_chunk(msg.payload),
+                None => warn!("Dropping chunk {}: no streaming request pending", log_label(&msg)),
+            }
+            return;
+        }
+
+        // Handle response messages
+        if matches!(msg.msg_type, IPCMessageType::Response) {
+            if let Some(id) = &msg.id {
+                let pending = self.pending_requests.lock().unwrap().remove(id);
+                if let Some(pending) = pending {
+                    let elapsed = pending.created_at.elapsed();
+                    self.metrics.record_response(elapsed);
+                    let result = match IPCError::from_response(&msg) {
+                        Some(err) => Err(err),
+                        None => Ok(msg.payload.clone()),
+                    };
+                    match &result {
+                        Ok(_) => debug!("Response {} received after {:?}", log_label(&msg), elapsed),
+                        Err(e) => debug!("Response {} received after {:?}: {}", log_label(&msg), elapsed, e),
+                    }
+                    (pending.callback)(result);
+                    return;
+                }
+            }
+
+            // Unmatched responses must not masquerade as events
+            match self.orphan_response_callback.lock().unwrap().as_ref() {
+                Some(callback) => callback(msg),
+                None => warn!("Dropping orphan response {}: no pending request", log_label(&msg)),
+            }
+            return;
+        }
+
+        if msg.event == BACKEND_READY_EVENT {
+            self.sender.mark_ready();
+        }
+
+        // Answer requests from Node.js that Rust handles
+        if let (IPCMessageType::Request, Some(id)) = (&msg.msg_type, &msg.id) {
+            let handler = self.request_handlers.lock().unwrap().get(&msg.event).cloned();
+            if let Some(handler) = handler {
+                let mut reply = match handler(msg.payload.clone()) {
+                    Ok(payload) => IPCMessage::response(id, &msg.event, payload),
+                    Err(e) => {
+                        debug!("Request {} failed: {}", log_label(&msg), e);
+                        IPCMessage::error_response(id, &msg.event, &e)
+                    }
+                };
+                // Headers such as trace IDs follow the request into its response
+                reply.meta = msg.meta.clone();
+                if let Err(e) = self.sender.send(&reply) {
+                    error!("Failed to answer request {}: {}", log_label(&msg), e);
+                }
+                return;
+            }
+        }
+
+        // Handle binary payloads
+        if let Some(bytes) = &msg.binary {
+            let handlers = self.binary_handlers.lock().unwrap();
+            if let Some(list) = handlers.get(&msg.event) {
+                for (_, handler) in list.iter() {
+                    handler(bytes.clone());
+                }
+            }
+        }
+
+        // Handle event messages
+        {
+            let mut handlers = self.event_handlers.lock().unwrap();
+            if let Some(list) = handlers.get_mut(&msg.event) {
+                for entry in list.iter() {
+                    (entry.handler)(msg.payload.clone());
+                }
+
+                // One-shot handlers are dropped under the same lock,
+                // so a rapid second event cannot reach them
+                list.retain(|entry| !entry.once);
+                if list.is_empty() {
+                    handlers.remove(&msg.event);
+                }
+            }
+        }
+
+        // Call the general message handler
+        on_message(msg);
+    }
+}
+
+/// Shared completion slot between a `ResponseFuture` and its pending callback
+#[derive(Default)]
+struct ResponseSlot {
+    result: Option<Result<Value, IPCError>>,
+    waker: Option<Waker>,
+}
+
+/// Future returned by `IPCBridge::request_async`
+///
+/// Resolves when Node.js responds or the request times out. Dropping the
+/// future before it resolves removes the request from the pending map, so
+/// a late response is not delivered to a callback nobody is waiting on.
+pub struct ResponseFuture {
+    id: Option<String>,
+    slot: Arc<Mutex<ResponseSlot>>,
+    pending_requests: Arc<Mutex<HashMap<String, PendingRequest>>>,
+    completed: bool,
+}
+
+impl ResponseFuture {
+    /// Get the request ID, if the request was registered
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+}
+
+impl Future for ResponseFuture {
+    type Output = Result<Value, IPCError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.slot.lock().unwrap();
+        if let Some(result) = slot.result.take() {
+            drop(slot);
+            self.completed = true;
+            return Poll::Ready(result);
+        }
+        slot.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for ResponseFuture {
+    fn drop(&mut self) {
+        if self.completed {
+            return;
+        }
+        if let Some(id) = &self.id {
+            if self.pending_requests.lock().unwrap().remove(id).is_some() {
+                debug!("Request {} dropped before completion, removed from pending", id);
+            }
+        }
+    }
+}
+
+/// Chainable configuration for an `IPCBridge`
+///
+/// # Example
+/// ```ignore
+/// let bridge = IPCBridge::builder()
+///     .timeout(10)
+///     .wire_format(WireFormat::MessagePack)
+///     .max_queue(100)
+///     .overflow_policy(QueueOverflowPolicy::Reject)
+///     .build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct IPCBridgeBuilder {
+    request_timeout_secs: u64,
+    framing_mode: FramingMode,
+    wire_format: WireFormat,
+    compression: Option<CompressionConfig>,
+    max_queue_size: usize,
+    overflow_policy: QueueOverflowPolicy,
+    max_message_bytes: usize,
+    read_buffer_size: usize,
+    schema_validation: bool,
+    heartbeat_event: String,
+    /// High- and low-water marks, if backpressure signaling is enabled
+    backpressure: Option<(usize, usize)>,
+    corrupt_stream_threshold: usize,
+    ready_gate: bool,
+    strict_utf8: bool,
+    max_concurrent_requests: usize,
+    circuit_breaker: Option<CircuitBreakerConfig>,
+}
+
+impl IPCBridgeBuilder {
+    /// Create a builder with the default configuration
+    pub fn new() -> Self {
+        IPCBridgeBuilder {
+            request_timeout_secs: DEFAULT_REQUEST_TIMEOUT_SECS,
+            framing_mode: FramingMode::default(),
+            wire_format: WireFormat::default(),
+            compression: None,
+            max_queue_size: DEFAULT_MAX_QUEUE_SIZE,
+            overflow_policy: QueueOverflowPolicy::default(),
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            read_buffer_size: DEFAULT_READ_BUFFER_SIZE,
+            schema_validation: true,
+            heartbeat_event: DEFAULT_HEARTBEAT_EVENT.to_string(),
+            backpressure: None,
+            corrupt_stream_threshold: DEFAULT_CORRUPT_STREAM_THRESHOLD,
+            ready_gate: false,
+            strict_utf8: false,
+            max_concurrent_requests: 0,
+            circuit_breaker: None,
+        }
+    }
+
+    /// Set the request timeout in seconds
+    pub fn timeout(mut self, secs: u64) -> Self {
+        self.request_timeout_secs = secs;
+        self
+    }
+
+    /// Set the framing mode used by the stdout listener
+    pub fn framing_mode(mut self, mode: FramingMode) -> Self {
+        self.framing_mode = mode;
+        self
+    }
+
+    /// Set the encoding used on the pipes
+    pub fn wire_format(mut self, format: WireFormat) -> Self {
+        self.wire_format = format;
+        self
+    }
+
+    /// Compress outbound messages of at least `threshold` encoded bytes
+    pub fn compression(mut self, codec: Compression, threshold: usize) -> Self {
+        self.compression = Some(CompressionConfig { codec, threshold });
+        self
+    }
+
+    /// Set the maximum number of messages queued while Node.js is unavailable
+    pub fn max_queue(mut self, max_size: usize) -> Self {
+        self.max_queue_size = max_size;
+        self
+    }
+
+    /// Set what happens when the queue is full
+    pub fn overflow_policy(mut self, policy: QueueOverflowPolicy) -> Self {
+        self.overflow_policy = policy;
+        self
+    }
+
+    /// Set the largest message, in bytes, that may be sent or received
+    pub fn max_message_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_message_bytes = max_bytes;
+        self
+    }
+
+    /// Set the capacity, in bytes, of the stdout read buffer (at least 1)
+    pub fn read_buffer_size(mut self, bytes: usize) -> Self {
+        self.read_buffer_size = bytes.max(1);
+        self
+    }
+
+    /// Enable or disable payload schema validation
+    pub fn schema_validation(mut self, enabled: bool) -> Self {
+        self.schema_validation = enabled;
+        self
+    }
+
+    /// Limit the number of requests in flight (0, the default, for no limit)
+    pub fn max_concurrent_requests(mut self, max: usize) -> Self {
+        self.max_concurrent_requests = max;
+        self
+    }
+
+    /// Hold queued messages until Node.js sends `BACKEND_READY_EVENT`
+    pub fn ready_gate(mut self, enabled: bool) -> Self {
+        self.ready_gate = enabled;
+        self
+    }
+
+    /// End the stdout listener on a line that is not valid UTF-8
+    pub fn strict_utf8(mut self, enabled: bool) -> Self {
+        self.strict_utf8 = enabled;
+        self
+    }
+
+    /// Set the event name used for heartbeat pings
+    pub fn heartbeat_event(mut self, event: &str) -> Self {
+        self.heartbeat_event = event.to_string();
+        self
+    }
+
+    /// Enable backpressure signaling at the given queue depths
+    pub fn backpressure(mut self, high_water: usize, low_water: usize) -> Self {
+        self.backpressure = Some((high_water, low_water));
+        self
+    }
+
+    /// Set how many consecutive unparseable messages mark the stream as corrupt
+    pub fn corrupt_stream_threshold(mut self, threshold: usize) -> Self {
+        self.corrupt_stream_threshold = threshold;
+        self
+    }
+
+    /// Fail requests fast after repeated timeouts, see `IPCBridge::with_circuit_breaker`
+    pub fn circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.circuit_breaker = Some(config);
+        self
+    }
+
+    /// Create the configured bridge
+    pub fn build(self) -> IPCBridge {
+        info!("Creating new IPC Bridge with timeout: {}s", self.request_timeout_secs);
+        IPCBridge {
+            stdin: Arc::new(Mutex::new(None)),
+            pending_requests: Arc::new(Mutex::new(HashMap::new())),
+            event_handlers: Arc::new(Mutex::new(HashMap::new())),
+            binary_handlers: Arc::new(Mutex::new(HashMap::new())),
+            request_handlers: Arc::new(Mutex::new(HashMap::new())),
+            next_handler_id: AtomicUsize::new(1),
+            next_seq: Arc::new(AtomicU64::new(1)),
+            message_queue: Arc::new(Mutex::new(PriorityQueue::new())),
+            max_queue_size: self.max_queue_size,
+            overflow_policy: self.overflow_policy,
+            request_timeout_secs: AtomicU64::new(self.request_timeout_secs),
+            event_timeouts: Mutex::new(HashMap::new()),
+            framing_mode: self.framing_mode,
+            wire_format: self.wire_format,
+            compression: self.compression,
+            shutdown: Arc::new(AtomicBool::new(false)),
+            schemas: Arc::new(Mutex::new(HashMap::new())),
+            schema_validation: self.schema_validation,
+            metrics: Arc::new(IPCMetrics::new()),
+            heartbeat_event: self.heartbeat_event,
+            backend_expected: Arc::new(AtomicBool::new(true)),
+            coalesced: Arc::new(Mutex::new(HashMap::new())),
+            draining: Arc::new(AtomicBool::new(false)),
+            limiter: Arc::new(RequestLimiter {
+                max: AtomicUsize::new(self.max_concurrent_requests),
+                ..RequestLimiter::default()
+            }),
+            negotiated_version: Arc::new(AtomicU32::new(0)),
+            ready_gate: self.ready_gate,
+            strict_utf8: self.strict_utf8,
+            ready: Arc::new(AtomicBool::new(!self.ready_gate)),
+            activity_callback: Arc::new(Mutex::new(None)),
+            observer: None,
+            outbound_middleware: Arc::new(Mutex::new(Vec::new())),
+            inbound_middleware: Arc::new(Mutex::new(Vec::new())),
+            write_stall_timeout: Duration::from_secs(DEFAULT_WRITE_STALL_TIMEOUT_SECS),
+            write_stalled_callback: Arc::new(Mutex::new(None)),
+            corrupt_stream_threshold: self.corrupt_stream_threshold,
+            stream_corrupt_callback: Arc::new(Mutex::new(None)),
+            parse_failures: Arc::new(AtomicUsize::new(0)),
+            stream_error_callback: Arc::new(Mutex::new(None)),
+            orphan_response_callback: Arc::new(Mutex::new(None)),
+            listener_ended_callback: Arc::new(Mutex::new(None)),
+            max_message_bytes: self.max_message_bytes,
+            read_buffer_size: self.read_buffer_size,
+            backpressure: Arc::new(match self.backpressure {
+                Some((high_water, low_water)) => Backpressure::new(high_water, low_water),
+                None => Backpressure::disabled(),
+            }),
+            circuit: Arc::new(match self.circuit_breaker {
+                Some(config) => CircuitBreaker::new(config),
+                None => CircuitBreaker::disabled(),
+            }),
+        }
+    }
+}
+
+impl Default for IPCBridgeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IPCBridge {
+    /// Create a new IPC bridge
+    pub fn new() -> Self {
+        IPCBridgeBuilder::new().build()
+    }
+
+    /// Create a new IPC bridge with custom timeout
+    pub fn with_timeout(timeout_secs: u64) -> Self {
+        IPCBridgeBuilder::new().timeout(timeout_secs).build()
+    }
+
+    /// Start configuring a bridge with `IPCBridgeBuilder`
+    pub fn builder() -> IPCBridgeBuilder {
+        IPCBridgeBuilder::new()
+    }
+
+    /// Change the default request timeout, in seconds
+    ///
+    /// Applies to requests sent afterwards; pending requests keep the timeout
+    /// they were created with.
+    pub fn set_default_timeout(&self, secs: u64) {
+        info!("Setting default request timeout to {}s", secs);
+        self.request_timeout_secs.store(secs, Ordering::SeqCst);
+    }
+
+    /// Get the default request timeout, in seconds
+    pub fn default_timeout(&self) -> u64 {
+        self.request_timeout_secs.load(Ordering::SeqCst)
+    }
+
+    /// Set the default timeout, in seconds, for requests with this event name
+    ///
+    /// Used by every request method that takes no explicit timeout, in place
+    /// of the global default. Pending requests keep their timeout.
+    pub fn set_event_timeout(&self, event: &str, secs: u64) {
+        debug!("Setting default timeout for {} to {}s", event, secs);
+        self.event_timeouts.lock().unwrap().insert(event.to_string(), secs);
+    }
+
+    /// Limit the number of requests in flight, 0 for no limit
+    ///
+    /// Requests beyond the cap are accepted (their ID is returned) but held
+    /// back and sent in order as earlier requests complete, time out or are
+    /// cancelled. Their timeout starts when they are actually sent;
+    /// `request_blocking` still waits at most its own timeout overall.
+    /// Heartbeat pings are requests too and count against the cap.
+    pub fn set_max_concurrent_requests(&self, max: usize) {
+        info!("Setting max concurrent requests to {}", max);
+        self.limiter.max.store(max, Ordering::SeqCst);
+        // A raised cap may let held-back requests through
+        self.limiter.pump();
+    }
+
+    /// Get the number of requests held back by the concurrency cap
+    pub fn waiting_request_count(&self) -> usize {
+        self.limiter.waiting.lock().unwrap().len()
+    }
+
+    /// Timeout for a request with no explicit one: the event's, else the global default
+    fn timeout_for(&self, event: &str) -> Duration {
+        let secs = self.event_timeouts.lock().unwrap().get(event).copied();
+        Duration::from_secs(secs.unwrap_or_else(|| self.default_timeout()))
+    }
+
+    /// Set the framing mode used by the stdout listener
+    pub fn with_framing_mode(mut self, mode: FramingMode) -> Self {
+        self.framing_mode = mode;
+        self
+    }
+
+    /// Set the encoding used on the pipes
+    ///
+    /// `WireFormat::MessagePack` uses length-prefixed frames and ignores the
+    /// framing mode. Node.js must be switched to the same format.
+    pub fn with_wire_format(mut self, format: WireFormat) -> Self {
+        self.wire_format = format;
+        self
+    }
+
+    /// Compress outbound messages of at least `threshold` encoded bytes
+    ///
+    /// Smaller messages are sent as is to avoid the overhead. Compressed
+    /// messages are received transparently with `FramingMode::Newline` or
+    /// `WireFormat::MessagePack`; Node.js must support the same codec.
+    pub fn with_compression(mut self, codec: Compression, threshold: usize) -> Self {
+        self.compression = Some(CompressionConfig { codec, threshold });
+        self
+    }
+
+    /// Set the largest message, in bytes, that may be sent or received
+    ///
+    /// Oversized outbound messages fail with `IPCError::SerializationError`;
+    /// oversized inbound lines or frames are discarded with a parse error.
+    pub fn with_max_message_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_message_bytes = max_bytes;
+        self
+    }
+
+    /// Set the capacity, in bytes, of the buffer stdout is read into
+    ///
+    /// Defaults to 64KB. A larger buffer (256KB) means fewer read syscalls
+    /// for streaming-heavy output, at the cost of that much memory per
+    /// listener; messages larger than the buffer are still read whole.
+    /// Values below 1 are raised to 1.
+    pub fn with_read_buffer_size(mut self, bytes: usize) -> Self {
+        self.read_buffer_size = bytes.max(1);
+        self
+    }
+
+    /// Set the event name used for heartbeat pings
+    pub fn with_heartbeat_event(mut self, event: &str) -> Self {
+        self.heartbeat_event = event.to_string();
+        self
+    }
+
+    /// Report sends, receives, timeouts and parse errors to an observer
+    ///
+    /// Set it before starting the stdout listener and timeout checker; they
+    /// pick up the observer when started.
+    pub fn with_observer(mut self, observer: Arc<dyn IpcObserver>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// End the stdout listener on a line that is not valid UTF-8
+    ///
+    /// By default (newline framing) such a line is decoded with replacement
+    /// characters and a warning, so a stray binary log line cannot stop
+    /// message reception. In strict mode the listener ends with
+    /// `ListenerEndReason::Error` instead, for callers who treat it as corruption.
+    pub fn with_strict_utf8(mut self) -> Self {
+        self.strict_utf8 = true;
+        self
+    }
+
+    /// Hold queued messages until the backend reports ready
+    ///
+    /// A writable stdin does not mean Node.js has installed its message
+    /// handler, so messages sent right after spawn can be lost. With the gate
+    /// enabled, messages are queued until `BACKEND_READY_EVENT` arrives on the
+    /// stdout listener (or `set_ready` is called), then flushed in order. The
+    /// gate closes again whenever stdin is replaced, e.g. after a restart.
+    pub fn with_ready_gate(mut self) -> Self {
+        self.ready_gate = true;
+        self.ready = Arc::new(AtomicBool::new(false));
+        self
+    }
+
+    /// Enable or disable payload schema validation
+    ///
+    /// Validation is on by default; pass `cfg!(debug_assertions)` to skip it
+    /// in release builds.
+    pub fn with_schema_validation(mut self, enabled: bool) -> Self {
+        self.schema_validation = enabled;
+        self
+    }
+
+    /// Register a JSON Schema for an event's outbound payload
+    ///
+    /// Events without a schema are sent unchanged. A payload that does not
+    /// match is rejected with `IPCError::SerializationError` naming the path.
+    pub fn register_schema(&self, event: &str, schema: Value) {
+        debug!("Registering payload schema for event: {}", event);
+        self.schemas.lock().unwrap().insert(event.to_string(), schema);
+    }
+
+    /// Remove the schema registered for an event
+    pub fn unregister_schema(&self, event: &str) -> bool {
+        self.schemas.lock().unwrap().remove(event).is_some()
+    }
+
+    /// Enable backpressure signaling at the given queue depths
+    ///
+    /// `on_backpressure` fires with `BackpressureEvent::HighWater` once the
+    /// queue reaches `high_water`, and with `LowWater` once it has drained to
+    /// `low_water`. Nothing fires in between, so the signal does not flap.
+    pub fn with_backpressure(mut self, high_water: usize, low_water: usize) -> Self {
+        self.backpressure = Arc::new(Backpressure::new(high_water, low_water));
+        self
+    }
+
+    /// Fail requests fast while the backend is not answering
+    ///
+    /// After `failure_threshold` consecutive timeouts or transport errors
+    /// within `window`, requests fail immediately with
+    /// `IPCError::Other("circuit open")` for `cool_down`. Then one trial
+    /// request is sent; a response closes the circuit, another failure opens
+    /// it again. Error responses from the backend do not count as failures.
+    pub fn with_circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.circuit = Arc::new(CircuitBreaker::new(config));
+        self
+    }
+
+    /// Current circuit breaker state, e.g. to show the backend as unavailable
+    ///
+    /// Always `CircuitState::Closed` without `with_circuit_breaker`.
+    pub fn circuit_state(&self) -> CircuitState {
+        self.circuit.state()
+    }
+
+    /// Register the callback for backpressure transitions
+    ///
+    /// Runs on the thread that sent or flushed the message, so it should
+    /// only signal the UI and must not send through the bridge itself.
+    pub fn on_backpressure<F>(&self, callback: F)
+    where
+        F: Fn(BackpressureEvent) + Send + 'static,
+    {
+        self.backpressure.set_callback(Box::new(callback));
+    }
+
+    /// Set how many consecutive unparseable messages mark the stream as corrupt
+    pub fn with_corrupt_stream_threshold(mut self, threshold: usize) -> Self {
+        self.corrupt_stream_threshold = threshold;
+        self
+    }
+
+    /// Register a callback for a stdout stream that keeps failing to parse
+    ///
+    /// The stdout listener counts consecutive messages that fail to parse and
+    /// resets the count on any successful parse. When the count reaches the
+    /// threshold (default 10) the callback receives it, so the supervisor can
+    /// restart the backend; it fires again only after a fresh run of failures.
+    /// Plain-text log lines are not counted.
+    pub fn on_stream_corrupt<F>(&self, callback: F)
+    where
+        F: Fn(usize) + Send + 'static,
+    {
+        *self.stream_corrupt_callback.lock().unwrap() = Some(Box::new(callback));
+    }
+
+    /// Register a callback for inbound messages over the size limit
+    ///
+    /// The stdout listener skips the rest of an over-long line (or frame)
+    /// and resumes at the next boundary, so each one yields a single
+    /// `IPCError::ParseError` here, however many reads it spans.
+    pub fn on_stream_error<F>(&self, callback: F)
+    where
+        F: Fn(IPCError) + Send + 'static,
+    {
+        *self.stream_error_callback.lock().unwrap() = Some(Box::new(callback));
+    }
+
+    /// Register a callback for responses that match no pending request
+    ///
+    /// Late responses to requests that already timed out or were cancelled,
+    /// and responses with a confused ID, are passed here in full instead of
+    /// reaching event handlers or the general message handler. Without a
+    /// callback they are logged as warnings and dropped.
+    pub fn on_orphan_response<F>(&self, callback: F)
+    where
+        F: Fn(IPCMessage) + Send + 'static,
+    {
+        *self.orphan_response_callback.lock().unwrap() = Some(Box::new(callback));
+    }
+
+    /// Register a callback for when the stdout listener stops reading a stream
+    ///
+    /// Runs on the listener thread with the reason. Without a reconnect
+    /// source the bridge receives nothing more afterwards, so a supervisor
+    /// can restart the backend and call `start_stdout_listener` again.
+    pub fn on_listener_ended<F>(&self, callback: F)
+    where
+        F: Fn(ListenerEndReason) + Send + 'static,
+    {
+        *self.listener_ended_callback.lock().unwrap() = Some(Box::new(callback));
+    }
+
+    /// Register a callback run on every message sent or received
+    ///
+    /// Sends report before anything is written or queued, received messages
+    /// before they are dispatched. Used to track idleness, e.g. with
+    /// `ProcessManager::activity_recorder`.
+    pub fn on_activity<F>(&self, callback: F)
+    where
+        F: Fn() + Send + 'static,
+    {
+        *self.activity_callback.lock().unwrap() = Some(Box::new(callback));
+    }
+
+    /// Add a transformation run on every message sent to Node.js
+    ///
+    /// Middlewares run in registration order before the message is validated
+    /// and encoded, and may change it freely, e.g. to inject an auth token or
+    /// a timestamp into every payload. Sequence numbers and the protocol
+    /// version are stamped afterwards. Raw lines skip the middleware.
+    pub fn add_outbound_middleware<F>(&self, middleware: F)
+    where
+        F: Fn(&mut IPCMessage) + Send + Sync + 'static,
+    {
+        self.outbound_middleware.lock().unwrap().push(Arc::new(middleware));
+    }
+
+    /// Add a transformation run on every message received from Node.js
+    ///
+    /// Middlewares run in registration order on the listener thread before
+    /// the message is dispatched to pending requests and handlers, e.g. to
+    /// strip or normalize fields.
+    pub fn add_inbound_middleware<F>(&self, middleware: F)
+    where
+        F: Fn(&mut IPCMessage) + Send + Sync + 'static,
+    {
+        self.inbound_middleware.lock().unwrap().push(Arc::new(middleware));
+    }
+
+    /// Set the maximum queue size and what happens when it is exceeded
+    pub fn with_queue_limit(mut self, max_size: usize, policy: QueueOverflowPolicy) -> Self {
+        self.max_queue_size = max_size;
+        self.overflow_policy = policy;
+        self
+    }
+
+    /// Set how long a write to Node.js may block before `on_write_stalled` fires
+    pub fn with_write_stall_timeout(mut self, timeout: Duration) -> Self {
+        self.write_stall_timeout = timeout;
+        self
+    }
+
+    /// Register a callback for a write to Node.js that stopped making progress
+    ///
+    /// Fires once per stall, from a watchdog thread, with how long the write
+    /// has been blocked (see `with_write_stall_timeout`). A backend that
+    /// stops reading its stdin is wedged; a supervisor can restart it.
+    pub fn on_write_stalled<F>(&self, callback: F)
+    where
+        F: Fn(Duration) + Send + 'static,
+    {
+        *self.write_stalled_callback.lock().unwrap() = Some(Box::new(callback));
+    }
+
+    /// Set the Node.js process stdin for sending messages
+    ///
+    /// Writes go through a dedicated writer thread (see `ThreadedWriter`),
+    /// so senders never block on a full pipe. Once `max_queue_size` writes
+    /// are waiting on that thread, further messages are queued. A write error
+    /// on the thread is reported by the next send, and whatever the thread
+    /// did not write is put back at the front of the queue.
+    pub fn set_stdin(&self, stdin: ChildStdin) {
+        self.set_writer(self.threaded_writer(stdin));
+    }
+
+    /// Wrap a writer so it is written on its own thread, watched for stalls
+    fn threaded_writer<W: Write + Send + 'static>(&self, writer: W) -> ThreadedWriter {
+        let callback = Arc::clone(&self.write_stalled_callback);
+        let queue = Arc::clone(&self.message_queue);
+        ThreadedWriter::spawn(
+            writer,
+            self.max_queue_size,
+            self.write_stall_timeout,
+            Some(Arc::new(move |stalled_for| {
+                if let Some(callback) = callback.lock().unwrap().as_ref() {
+                    callback(stalled_for);
+                }
+            })),
+            Some(Box::new(move |unwritten| {
+                warn!("Re-queueing {} frame(s) the writer thread did not write", unwritten.len());
+                // These were sent before anything still queued, so they go first
+                let mut queue = queue.lock().unwrap();
+                for bytes in unwritten.into_iter().rev() {
+                    queue.push_front(Priority::High, Queued::Raw(bytes));
+                }
+            })),
+        )
+    }
+
+    /// Connect the bridge to Node.js over a transport
+    ///
+    /// Sends through the transport's writer on a writer thread (flushing the
+    /// queue, as `set_stdin` does) and starts the stdout listener on its reader. With
+    /// `UnixSocketTransport` or `NamedPipeTransport` the backend's real stdout
+    /// is left for logging.
+    pub fn connect<T, F>(&self, transport: T, on_message: F) -> Result<JoinHandle<()>, IPCError>
+    where
+        T: Transport,
+        F: Fn(IPCMessage) + Send + 'static,
+    {
+        let (reader, writer) = transport
+            .split()
+            .map_err(|e| IPCError::Other(format!("Failed to set up transport: {}", e)))?;
+        self.set_writer(self.threaded_writer(writer));
+        Ok(self.start_stdout_listener(reader, on_message))
+    }
+
+    /// Connect the bridge to an in-memory backend instead of Node.js
+    ///
+    /// Every message the bridge sends is passed to `responder` on the sending
+    /// thread; a returned message is fed back through the stdout listener, as
+    /// if Node.js had written it. Intended for tests of requests, timeouts,
+    /// retries and coalescing without spawning node. Returns the listener
+    /// thread, which ends once stdin is replaced or the bridge is dropped.
+    pub fn connect_loopback<F>(&self, responder: F) -> JoinHandle<()>
+    where
+        F: Fn(IPCMessage) -> Option<IPCMessage> + Send + 'static,
+    {
+        let (writer, reader) = loopback::pipe(self.wire_format, responder);
+        self.set_writer(writer);
+        self.start_stdout_listener(reader, |_| {})
+    }
+
+    /// Connect the bridge to an in-memory backend that echoes requests
+    ///
+    /// Each request is answered with a response carrying its own payload;
+    /// events are swallowed. See `connect_loopback`.
+    pub fn connect_echo(&self) -> JoinHandle<()> {
+        self.connect_loopback(|msg| match (&msg.msg_type, &msg.id) {
+            (IPCMessageType::Request, Some(id)) => Some(IPCMessage::response(id, &msg.event, msg.payload.clone())),
+            _ => None,
+        })
+    }
+
+    /// Set an arbitrary writer as the destination for outgoing messages
+    ///
+    /// Queued messages are flushed immediately; failures are logged and the
+    /// undelivered messages stay queued. Use `try_flush_queue` to observe them.
+    /// The writer is written on the sending thread; wrap pipes that may fill
+    /// up in a `ThreadedWriter`.
+    pub fn set_writer<W: Write + Send + 'static>(&self, writer: W) {
+        debug!("Setting Node.js stdin for IPC bridge");
+        *self.stdin.lock().unwrap() = Some(Box::new(writer));
+        if self.ready_gate {
+            // A new process has to report ready again
+            self.ready.store(false, Ordering::SeqCst);
+        }
+
+        // Flush any queued messages
+        match self.try_flush_queue() {
+            Ok(0) => {}
+            Ok(count) => debug!("Flushed {} queued message(s)", count),
+            Err(e) => error!("Failed to flush message queue: {}", e),
+        }
+    }
+
+    /// Detach the current writer, e.g. before the backend is stopped
+    ///
+    /// Later messages are queued (unless the backend is not expected, see
+    /// `set_backend_expected`) until `set_writer` attaches the next one.
+    pub fn clear_writer(&self) {
+        debug!("Clearing Node.js stdin for IPC bridge");
+        *self.stdin.lock().unwrap() = None;
+    }
+
+    /// Flush queued messages to stdin
+    ///
+    /// Returns the number of messages written. Messages that fail to encode
+    /// are dropped; on a write failure the message is put back at the front
+    /// of the queue and the error is returned. While the ready gate is closed
+    /// nothing is written and `Ok(0)` is returned.
+    pub fn try_flush_queue(&self) -> Result<usize, IPCError> {
+        self.sender().flush_queue()
+    }
+
+    /// Mark the backend ready and flush the queued messages
+    ///
+    /// Called by the stdout listener when `BACKEND_READY_EVENT` arrives; call
+    /// it manually for a backend that signals readiness some other way. Has
+    /// no effect on the gate unless `with_ready_gate` is enabled.
+    pub fn set_ready(&self) {
+        self.sender().mark_ready();
+    }
+
+    /// Whether the backend may currently receive messages
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::SeqCst)
+    }
+
+    /// Block until the backend reports ready, for up to `timeout`
+    ///
+    /// Returns immediately without the ready gate. Note that `handshake` is
+    /// itself held behind the gate, so wait for readiness first.
+    pub fn wait_until_ready(&self, timeout: Duration) -> Result<(), IPCError> {
+        let deadline = Instant::now() + timeout;
+        while !self.is_ready() {
+            if Instant::now() >= deadline {
+                return Err(IPCError::Timeout(format!("backend not ready within {:?}", timeout)));
+            }
+            thread::sleep(Duration::from_millis(WAIT_POLL_INTERVAL_MS));
+        }
+        Ok(())
+    }
+
+    /// Start listening to Node.js stdout
+    ///
+    /// This spawns a thread that reads from stdout and processes messages.
+    /// The stream is split into messages according to the bridge's `FramingMode`,
+    /// or by length prefix when the wire format is `WireFormat::MessagePack`.
+    /// The thread exits on EOF, on a read error, or after the next read once
+    /// `shutdown` has been called; `on_listener_ended` reports which.
+    pub fn start_stdout_listener<R, F>(&self, stdout: R, on_message: F) -> JoinHandle<()>
+    where
+        R: Read + Send + 'static,
+        F: Fn(IPCMessage) + Send + 'static,
+    {
+        self.start_stdout_listener_with_raw(stdout, on_message, |_| {})
+    }
+
+    /// Parse and dispatch one line of Node.js output on the calling thread
+    ///
+    /// Handles the line exactly as the newline stdout listener does: it
+    /// completes the matching pending request, answers requests with a
+    /// registered handler, runs event handlers, and counts unparseable lines
+    /// towards `on_stream_corrupt`. Returns the message as dispatched, after
+    /// the inbound middleware, or `None` for lines that yield no message.
+    /// Intended for deterministic tests of the dispatch pipeline without a
+    /// listener thread.
+    pub fn process_line(&self, line: &str) -> Option<IPCMessage> {
+        let dispatcher = self.dispatcher();
+        let dispatched = std::cell::RefCell::new(None);
+        let mut reader = std::io::Cursor::new(format!("{}\n", line));
+        let deliver = |msg: IPCMessage| {
+            let msg = dispatcher.receive(msg);
+            *dispatched.borrow_mut() = Some(msg.clone());
+            dispatcher.route(msg, &|_| {});
+        };
+        if let Err(reason) = self.inbound_stream().read_line(&mut reader, &|_| {}, &deliver) {
+            debug!("Line ended before a message was read: {:?}", reason);
+        }
+        dispatched.into_inner()
+    }
+
+    /// Start listening to Node.js stdout, also forwarding every raw line
+    ///
+    /// `on_raw_line` receives each non-empty line as text before it is parsed,
+    /// including plain-text log output and lines that are not valid messages.
+    /// Lines that do parse are still dispatched as with `start_stdout_listener`.
+    /// In `FramingMode::JsonStream` each object is forwarded as one line; the
+    /// `WireFormat::MessagePack` stream has no lines and forwards nothing.
+    pub fn start_stdout_listener_with_raw<R, F, G>(&self, stdout: R, on_message: F, on_raw_line: G) -> JoinHandle<()>
+    where
+        R: Read + Send + 'static,
+        F: Fn(IPCMessage) + Send + 'static,
+        G: Fn(String) + Send + 'static,
+    {
+        self.spawn_stdout_listener(stdout, on_message, on_raw_line, None)
+    }
+
+    /// Start listening to Node.js stdout, re-attaching after the stream ends
+    ///
+    /// When the stream hits EOF or a read error, `reconnect` is called on the
+    /// listener thread to supply the next stream (e.g. the stdout of a
+    /// restarted backend); it may block until one is available. Returning
+    /// `None` stops the listener. `on_listener_ended` still fires for every
+    /// stream that ends. Nothing is re-attached after `shutdown`.
+    pub fn start_stdout_listener_with_reconnect<R, F, S>(&self, stdout: R, on_message: F, reconnect: S) -> JoinHandle<()>
+    where
+        R: Read + Send + 'static,
+        F: Fn(IPCMessage) + Send + 'static,
+        S: FnMut() -> Option<R> + Send + 'static,
+    {
+        self.spawn_stdout_listener(stdout, on_message, |_| {}, Some(Box::new(reconnect)))
+    }
+
+    /// Spawn the listener thread, re-attaching through `reconnect` if given
+    fn spawn_stdout_listener<R, F, G>(
+        &self,
+        stdout: R,
+        on_message: F,
+        on_raw_line: G,
+        mut reconnect: Option<Box<dyn FnMut() -> Option<R> + Send>>,
+    ) -> JoinHandle<()>
+    where
+        R: Read + Send + 'static,
+        F: Fn(IPCMessage) + Send + 'static,
+        G: Fn(String) + Send + 'static,
+    {
+        info!("Starting stdout listener for IPC bridge ({:?} framing, {:?})", self.framing_mode, self.wire_format);
+        let dispatcher = self.dispatcher();
+        let stream = self.inbound_stream();
+        let framing_mode = self.framing_mode;
+        let wire_format = self.wire_format;
+        let shutdown = Arc::clone(&self.shutdown);
+        let max_message_bytes = self.max_message_bytes;
+        let read_buffer_size = self.read_buffer_size;
+        let listener_ended_callback = Arc::clone(&self.listener_ended_callback);
+        let metrics = Arc::clone(&self.metrics);
+
+        thread::spawn(move || {
+            let dispatch = |msg: IPCMessage| dispatcher.dispatch(msg, &on_message);
+
+            // Read one stream until it ends, reporting why
+            let read_stream = |stdout: R| -> ListenerEndReason {
+                let stdout = CountingReader { inner: stdout, metrics: Arc::clone(&metrics) };
+                if wire_format == WireFormat::MessagePack {
+                    let mut reader = BufReader::with_capacity(read_buffer_size, stdout);
+                    while !shutdown.load(Ordering::SeqCst) {
+                        match read_length_prefixed_frame(&mut reader, max_message_bytes) {
+                            Ok(RawFrame::Complete(frame)) => match decode_message(&frame, WireFormat::MessagePack) {
+                                Ok(mut msg) => {
+                                    debug!("Received from Node.js: {} ({} bytes)", msg.event, frame.len());
+                                    stream.parsed();
+
+                                    // A binary payload follows its header as a separate frame
+                                    if let Some(expected) = msg.binary_len() {
+                                        match read_length_prefixed_frame(&mut reader, max_message_bytes) {
+                                            Ok(RawFrame::Complete(bytes)) if bytes.len() == expected => {
+                                                msg.binary = Some(bytes);
+                                            }
+                                            Ok(RawFrame::Complete(bytes)) => {
+                                                warn!("Binary frame for {} is {} bytes, expected {}; discarded",
+                                                      msg.event, bytes.len(), expected);
+                                                continue;
+                                            }
+                                            Ok(RawFrame::TooLong(len)) => {
+                                                stream.report_oversized(len);
+                                                continue;
+                                            }
+                                            Ok(RawFrame::Eof) => return ListenerEndReason::Eof,
+                                            Err(e) => return ListenerEndReason::Error(e.to_string()),
+                                        }
+                                    }
+                                    dispatch(msg);
+                                }
+                                // A compression header is followed by the compressed message
+                                Err(e) => match CompressionHeader::from_msgpack(&frame) {
+                                    Some(header) => match read_length_prefixed_frame(&mut reader, max_message_bytes) {
+                                        Ok(RawFrame::Complete(body)) => {
+                                            match decode_compressed_message(&header, &body, WireFormat::MessagePack, max_message_bytes) {
+                                                Ok(msg) => {
+                                                    debug!("Received from Node.js: {} ({} bytes compressed)", msg.event, body.len());
+                                                    stream.parsed();
+                                                    dispatch(msg);
+                                                }
+                                                Err(e) => stream.record_parse_failure(&body, e),
+                                            }
+                                        }
+                                        Ok(RawFrame::TooLong(len)) => stream.report_oversized(len),
+                                        Ok(RawFrame::Eof) => return ListenerEndReason::Eof,
+                                        Err(e) => return ListenerEndReason::Error(e.to_string()),
+                                    },
+                                    None => stream.record_parse_failure(&frame, e),
+                                },
+                            },
+                            Ok(RawFrame::TooLong(len)) => stream.report_oversized(len),
+                            Ok(RawFrame::Eof) => return ListenerEndReason::Eof,
+                            Err(e) => return ListenerEndReason::Error(e.to_string()),
+                        }
+                    }
+                    return ListenerEndReason::Shutdown;
+                }
+
+                match framing_mode {
+                    FramingMode::Newline => {
+                        let mut reader = BufReader::with_capacity(read_buffer_size, stdout);
+
+                        while !shutdown.load(Ordering::SeqCst) {
+                            if let Err(reason) = stream.read_line(&mut reader, &on_raw_line, &dispatch) {
+                                return reason;
+                            }
+                        }
+                    }
+                    FramingMode::JsonStream => {
+                        let mut reader = stdout;
+                        let mut decoder = JsonFrameDecoder::new(max_message_bytes);
+                        let mut buf = vec![0u8; read_buffer_size];
+
+                        while !shutdown.load(Ordering::SeqCst) {
+                            match reader.read(&mut buf) {
+                                Ok(0) => {
+                                    if let Some(StreamFrame::Text(line)) = decoder.finish() {
+                                        on_raw_line(line);
+                                    }
+                                    return ListenerEndReason::Eof;
+                                }
+                                Ok(n) => {
+                                    for frame in decoder.push(&buf[..n]) {
+                                        match frame {
+                                            StreamFrame::Json(frame) => {
+                                                on_raw_line(frame.clone());
+                                                stream.handle_message(&frame, &dispatch);
+                                            }
+                                            StreamFrame::Text(line) => {
+                                                debug!("Skipping non-JSON output from Node.js: {}", line);
+                                                on_raw_line(line);
+                                            }
+                                            StreamFrame::Oversized(e) => stream.report_stream_error(e),
+                                        }
+                                    }
+                                }
+                                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                                Err(e) => return ListenerEndReason::Error(e.to_string()),
+                            }
+                        }
+                    }
+                }
+
+                ListenerEndReason::Shutdown
+            };
+
+            let mut stdout = stdout;
+            loop {
+                stream.parsed();
+                let reason = read_stream(stdout);
+                match &reason {
+                    ListenerEndReason::Error(e) => error!("Error reading from Node.js stdout: {}", e),
+                    _ => debug!("Node.js stdout stream ended: {:?}", reason),
+                }
+                if let Some(callback) = listener_ended_callback.lock().unwrap().as_ref() {
+                    callback(reason.clone());
+                }
+                if reason == ListenerEndReason::Shutdown || shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                // Keep listening if the supervisor can supply a fresh stream
+                match reconnect.as_mut().and_then(|next| next()) {
+                    Some(next) => {
+                        info!("stdout listener re-attached to a new stream");
+                        stdout = next;
+                    }
+                    None => break,
+                }
+            }
+
+            info!("stdout listener stopped");
+        })
+    }
+
+    /// Send an event to Node.js (fire and forget)
+    pub fn emit(&self, event: &str, payload: Value) -> Result<(), String> {
+        let msg = IPCMessage::event(event, payload);
+        self.send_to_node(&msg)
+    }
+
+    /// Send an event to Node.js with a queueing priority
+    ///
+    /// The priority only matters while messages are buffered: queued messages
+    /// are flushed High before Normal before Low, FIFO within a level.
+    pub fn emit_with_priority(&self, event: &str, payload: Value, priority: Priority) -> Result<(), String> {
+        let msg = IPCMessage::event(event, payload);
+        let mut sender = self.sender();
+        sender.priority = priority;
+        sender.send(&msg).map_err(String::from)
+    }
+
+    /// Send several events to Node.js as one unit
+    ///
+    /// The events are encoded up front and written with a single lock
+    /// acquisition and flush, so no other thread's message lands between them
+    /// and Node.js reads them back to back. If any event fails validation or
+    /// encoding, nothing is sent. While stdin is unavailable they are queued
+    /// like `emit`; a batch that does not fit a `QueueOverflowPolicy::Reject`
+    /// queue is rejected whole.
+    pub fn emit_batch(&self, messages: Vec<(String, Value)>) -> Result<(), String> {
+        let msgs = messages
+            .into_iter()
+            .map(|(event, payload)| IPCMessage::event(&event, payload))
+            .collect();
+        self.sender().send_batch(msgs).map_err(String::from)
+    }
+
+    /// Send an event to Node.js and get notified once it has been handled
+    ///
+    /// The event is sent as a request message, so Node.js **must** reply with a
+    /// `response` message carrying the same `id` (the payload is ignored) for
+    /// the callback to receive `Ok(())`. An error response or no reply within
+    /// `timeout` yields `Err`. Unlike `emit`, a message that is merely queued
+    /// is not reported as delivered. Returns the request ID.
+    pub fn emit_acked<F>(
+        &self,
+        event: &str,
+        payload: Value,
+        timeout: Duration,
+        callback: F,
+    ) -> Result<String, String>
+    where
+        F: FnOnce(Result<(), IPCError>) + Send + 'static,
+    {
+        self.send_request(event, payload, timeout, Box::new(move |result| {
+            callback(result.map(|_| ()));
+        }))
+    }
+
+    /// Send a request to Node.js and wait for response
+    ///
+    /// The callback receives the response payload, or an `IPCError` telling
+    /// a timeout, send failure, cancellation, or backend error apart.
+    pub fn request<F>(&self, event: &str, payload: Value, callback: F) -> Result<String, String>
+    where
+        F: FnOnce(Result<Value, IPCError>) + Send + 'static,
+    {
+        self.send_request(
+            event,
+            payload,
+            self.timeout_for(event),
+            Box::new(callback),
+        )
+    }
+
+    /// Send a request to Node.js with a queueing priority
+    ///
+    /// See `emit_with_priority` for how the priority is applied.
+    pub fn request_with_priority<F>(
+        &self,
+        event: &str,
+        payload: Value,
+        priority: Priority,
+        callback: F,
+    ) -> Result<String, String>
+    where
+        F: FnOnce(Result<Value, IPCError>) + Send + 'static,
+    {
+        let mut sender = self.sender();
+        sender.priority = priority;
+        sender
+            .send_request(
+                event,
+                payload,
+                self.timeout_for(event),
+                Box::new(callback),
+            )
+            .map_err(String::from)
+    }
+
+    /// Send a request with custom timeout
+    pub fn request_with_timeout<F>(
+        &self,
+        event: &str,
+        payload: Value,
+        timeout_secs: u64,
+        callback: F,
+    ) -> Result<String, String>
+    where
+        F: FnOnce(Result<Value, IPCError>) + Send + 'static,
+    {
+        self.send_request(event, payload, Duration::from_secs(timeout_secs), Box::new(callback))
+    }
+
+    /// Send a request that must complete by an absolute deadline
+    ///
+    /// Several sequential requests can share one deadline, each getting
+    /// whatever budget remains, without recomputing relative timeouts. A
+    /// request held back by the concurrency cap keeps its deadline. Fails
+    /// without sending if the deadline has already passed.
+    pub fn request_with_deadline<F>(&self, event: &str, payload: Value, deadline: Instant, callback: F) -> Result<String, String>
+    where
+        F: FnOnce(Result<Value, IPCError>) + Send + 'static,
+    {
+        if deadline <= Instant::now() {
+            return Err(IPCError::Timeout(format!("deadline for request {} has already passed", event)).into());
+        }
+        self.sender()
+            .send_stream_request(event, payload, Expiry::At(deadline), None, Box::new(callback))
+            .map_err(String::from)
+    }
+
+    /// Send a request whose result arrives in pieces
+    ///
+    /// Node.js answers with any number of `chunk` messages carrying the
+    /// request's ID (see `IPCMessage::chunk`), each passed to `on_chunk`, and
+    /// ends the stream with a regular response (or error response), passed
+    /// to `on_complete`. The timeout covers the whole stream, so long streams
+    /// need a matching `set_event_timeout`. Chunks arriving after the end are
+    /// dropped.
+    pub fn request_stream<C, F>(&self, event: &str, payload: Value, on_chunk: C, on_complete: F) -> Result<String, String>
+    where
+        C: Fn(Value) + Send + Sync + 'static,
+        F: FnOnce(Result<Value, IPCError>) + Send + 'static,
+    {
+        self.sender()
+            .send_stream_request(
+                event,
+                payload,
+                Expiry::After(self.timeout_for(event)),
+                Some(Arc::new(on_chunk)),
+                Box::new(on_complete),
+            )
+            .map_err(String::from)
+    }
+
+    /// Send a best-effort request to Node.js, skipping it if not connected
+    ///
+    /// `request` queues the message while stdin is unavailable and the
+    /// callback waits for the backend to come up (or for the timeout).
+    /// `try_request` never queues: without stdin it returns
+    /// `IPCError::StdinNotAvailable` immediately, registers no pending entry,
+    /// and never calls the callback. Use it for optional calls such as status
+    /// polls that are pointless once stale.
+    pub fn try_request<F>(&self, event: &str, payload: Value, callback: F) -> Result<String, IPCError>
+    where
+        F: FnOnce(Result<Value, IPCError>) + Send + 'static,
+    {
+        if self.stdin.lock().unwrap().is_none() {
+            return Err(IPCError::StdinNotAvailable);
+        }
+        let mut sender = self.sender();
+        sender.fail_fast = true;
+        sender.send_request(
+            event,
+            payload,
+            self.timeout_for(event),
+            Box::new(callback),
+        )
+    }
+
+    /// Negotiate the protocol version with Node.js
+    ///
+    /// Sends a `__hello__` request advertising `MIN_PROTOCOL_VERSION` and
+    /// `PROTOCOL_VERSION`; Node.js must answer with `{"version": N}`. A version
+    /// in that range is stored and stamped on every message sent afterwards;
+    /// anything else fails with `IPCError::UnsupportedVersion`. Call once after
+    /// connecting stdin and starting the stdout listener. Blocks up to `timeout`.
+    pub fn handshake(&self, timeout: Duration) -> Result<u32, IPCError> {
+        let payload = serde_json::json!({
+            "version": PROTOCOL_VERSION,
+            "min_version": MIN_PROTOCOL_VERSION,
+        });
+        let response = self.request_blocking(HELLO_EVENT, payload, timeout)?;
+
+        let version = response
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| IPCError::ParseError(format!("invalid {} response: {}", HELLO_EVENT, response)))?;
+        let version = u32::try_from(version).unwrap_or(u32::MAX);
+        if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version) {
+            error!("Node.js negotiated protocol version {}, which is not supported", version);
+            return Err(IPCError::UnsupportedVersion(version));
+        }
+
+        info!("Negotiated IPC protocol version {}", version);
+        self.negotiated_version.store(version, Ordering::SeqCst);
+        Ok(version)
+    }
+
+    /// Get the protocol version agreed by `handshake`, if any
+    pub fn negotiated_version(&self) -> Option<u32> {
+        match self.negotiated_version.load(Ordering::SeqCst) {
+            0 => None,
+            version => Some(version),
+        }
+    }
+
+    /// Mark whether the backend is expected to (re)start
+    ///
+    /// While `false`, sends with no stdin fail with `IPCError::StdinNotAvailable`
+    /// instead of being queued, so requests error out immediately rather than
+    /// waiting for their timeout. Messages already queued are left in place.
+    pub fn set_backend_expected(&self, expected: bool) {
+        info!("Backend expected to start: {}", expected);
+        self.backend_expected.store(expected, Ordering::SeqCst);
+    }
+
+    /// Send a request to Node.js, retrying on error or timeout
+    ///
+    /// Each attempt gets a fresh request ID and uses the event's default
+    /// timeout; `backoff` is waited between attempts. The callback fires once,
+    /// with the first success or the last error. Only use this for events that
+    /// are safe to repeat. Returns the ID of the first attempt.
+    pub fn request_with_retry<F>(
+        &self,
+        event: &str,
+        payload: Value,
+        max_attempts: u32,
+        backoff: Duration,
+        callback: F,
+    ) -> Result<String, String>
+    where
+        F: FnOnce(Result<Value, IPCError>) + Send + 'static,
+    {
+        let callback: SharedCallback = Arc::new(Mutex::new(Some(Box::new(callback))));
+        self.sender()
+            .send_attempt(
+                event.to_string(),
+                payload,
+                self.timeout_for(event),
+                max_attempts.max(1),
+                backoff,
+                callback,
+            )
+            .map_err(String::from)
+    }
+
+    /// Send a request to Node.js, sharing an identical request already in flight
+    ///
+    /// Requests are identical when the event and the serialized payload match.
+    /// A caller arriving while such a request is pending attaches to it instead
+    /// of sending a duplicate, and every caller receives the single response.
+    /// Only use this for pure reads: the backend handles the request once, no
+    /// matter how many callers asked. Returns the ID of the shared request.
+    pub fn request_coalesced<F>(&self, event: &str, payload: Value, callback: F) -> Result<String, String>
+    where
+        F: FnOnce(Result<Value, IPCError>) + Send + 'static,
+    {
+        let key = format!("{}\n{}", event, payload);
+        let mut coalesced = self.coalesced.lock().unwrap();
+        if let Some(request) = coalesced.get_mut(&key) {
+            debug!("Coalescing {} into pending request {}", event, request.id);
+            request.waiters.push(Box::new(callback));
+            return Ok(request.id.clone());
+        }
+
+        let shared = Arc::clone(&self.coalesced);
+        let shared_key = key.clone();
+        let id = self.send_request(
+            event,
+            payload,
+            self.timeout_for(event),
+            Box::new(move |result| {
+                let request = shared.lock().unwrap().remove(&shared_key);
+                for waiter in request.map(|request| request.waiters).unwrap_or_default() {
+                    waiter(result.clone());
+                }
+            }),
+        )?;
+
+        coalesced.insert(key, CoalescedRequest {
+            id: id.clone(),
+            waiters: vec![Box::new(callback)],
+        });
+        Ok(id)
+    }
+
+    /// Send a typed command to Node.js
+    ///
+    /// Like `request`, with the payload serialized from `C::Req` and the
+    /// response deserialized into `C::Res`. A response that does not match
+    /// `C::Res` yields `IPCError::ParseError`.
+    pub fn call<C: Command>(
+        &self,
+        req: C::Req,
+        callback: impl FnOnce(Result<C::Res, IPCError>) + Send + 'static,
+    ) -> Result<String, IPCError> {
+        let payload = serde_json::to_value(&req).map_err(|e| {
+            IPCError::SerializationError(format!("invalid payload for {}: {}", C::EVENT, e))
+        })?;
+        self.sender().send_request(
+            C::EVENT,
+            payload,
+            self.timeout_for(C::EVENT),
+            Box::new(move |result| {
+                callback(result.and_then(|value| {
+                    serde_json::from_value(value).map_err(|e| {
+                        IPCError::ParseError(format!("invalid response to {}: {}", C::EVENT, e))
+                    })
+                }));
+            }),
+        )
+    }
+
+    /// Send a request to Node.js and return a future that resolves with the response
+    ///
+    /// The request is tracked in the same pending map as `request`, so the
+    /// response is matched by the stdout listener and the timeout is enforced
+    /// by `start_timeout_checker`. Suitable for `#[tauri::command] async fn`.
+    pub fn request_async(&self, event: &str, payload: Value) -> ResponseFuture {
+        let slot = Arc::new(Mutex::new(ResponseSlot::default()));
+        let callback_slot = Arc::clone(&slot);
+
+        let id = self.sender().send_request(
+            event,
+            payload,
+            self.timeout_for(event),
+            Box::new(move |result| {
+                let mut slot = callback_slot.lock().unwrap();
+                slot.result = Some(result);
+                if let Some(waker) = slot.waker.take() {
+                    waker.wake();
+                }
+            }),
+        );
+
+        let id = match id {
+            Ok(id) => Some(id),
+            Err(e) => {
+                slot.lock().unwrap().result = Some(Err(e));
+                None
+            }
+        };
+
+        ResponseFuture {
+            id,
+            slot,
+            pending_requests: Arc::clone(&self.pending_requests),
+            completed: false,
+        }
+    }
+
+    /// Send a request to Node.js and block until the response arrives
+    ///
+    /// Waits at most `timeout`; on timeout the pending request is removed so
+    /// it does not leak. Intended for scripts and tests that simply want the
+    /// result and do not need concurrency.
+    pub fn request_blocking(&self, event: &str, payload: Value, timeout: Duration) -> Result<Value, IPCError> {
+        let (tx, rx) = mpsc::channel();
+        let id = self.sender().send_request(
+            event,
+            payload,
+            timeout,
+            Box::new(move |result| {
+                let _ = tx.send(result);
+            }),
+        )?;
+
+        match rx.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                self.pending_requests.lock().unwrap().remove(&id);
+                self.limiter.remove_waiting(&id);
+                warn!("Request {} [{}] timed out after {:?}", event, id, timeout);
+                let err = IPCError::Timeout(format!("request {} timed out after {:?}", id, timeout));
+                self.circuit.record(&Err(err.clone()));
+                if let Some(observer) = &self.observer {
+                    observer.on_timeout(&id, event);
+                }
+                Err(err)
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                Err(IPCError::Other(format!("request {} was dropped before completion", id)))
+            }
+        }
+    }
+
+    /// Forward a frontend invoke to Node.js and return its response
+    ///
+    /// Sends `command` as a request with `args` as the payload and blocks
+    /// until the response arrives or the event's timeout (see
+    /// `set_event_timeout`) elapses. Errors are turned into strings, ready to
+    /// return from a Tauri command:
+    ///
+    /// ```ignore
+    /// #[tauri::command]
+    /// fn invoke_backend(bridge: tauri::State<'_, Arc<IPCBridge>>, command: String, args: Value) -> Result<Value, String> {
+    ///     bridge.handle_frontend_invoke(&command, args)
+    /// }
+    /// ```
+    pub fn handle_frontend_invoke(&self, command: &str, args: Value) -> Result<Value, String> {
+        debug!("Frontend invoke: {}", command);
+        self.request_blocking(command, args, self.timeout_for(command))
+            .map_err(String::from)
+    }
+
+    /// Register a pending request and send it to Node.js
+    fn send_request(
+        &self,
+        event: &str,
+        payload: Value,
+        timeout: Duration,
+        callback: RequestCallback,
+    ) -> Result<String, String> {
+        self.sender()
+            .send_request(event, payload, timeout, callback)
+            .map_err(String::from)
+    }
+
+    /// Start a background thread to check for timed out requests
+    ///
+    /// Scans every 100ms; see `start_timeout_checker_with_interval`. The
+    /// checker survives a poisoned pending-request lock (a thread that
+    /// panicked while holding it) and callbacks that panic, so one failure
+    /// elsewhere does not leave every later request hanging forever.
+    pub fn start_timeout_checker(&self) -> JoinHandle<()> {
+        self.start_timeout_checker_with_interval(Duration::from_millis(DEFAULT_TIMEOUT_CHECK_INTERVAL_MS))
+    }
+
+    /// Start a background thread checking for timed out requests every `interval`
+    ///
+    /// A request fails at most `interval` after its deadline, so shorter
+    /// intervals give more precise timeouts at the cost of more wakeups.
+    /// Nothing is scanned while no request is pending.
+    pub fn start_timeout_checker_with_interval(&self, interval: Duration) -> JoinHandle<()> {
+        let pending_requests = Arc::clone(&self.pending_requests);
+        let shutdown = Arc::clone(&self.shutdown);
+        let metrics = Arc::clone(&self.metrics);
+        let observer = self.observer.clone();
+
+        thread::spawn(move || {
+            loop {
+                thread::sleep(interval);
+                if shutdown.load(Ordering::SeqCst) {
+                    debug!("Timeout checker stopped");
+                    break;
+                }
+
+                let timed_out: Vec<(String, PendingRequest)> = {
+                    let mut requests = pending_requests.lock().unwrap_or_else(PoisonError::into_inner);
+                    if requests.is_empty() {
+                        continue;
+                    }
+
+                    // Find timed out requests
+                    let now = Instant::now();
+                    let timed_out_ids: Vec<String> = requests
+                        .iter()
+                        .filter(|(_, request)| now > request.deadline)
+                        .map(|(id, _)| id.clone())
+                        .collect();
+
+                    timed_out_ids
+                        .into_iter()
+                        .filter_map(|id| requests.remove(&id).map(|request| (id, request)))
+                        .collect()
+                };
+
+                // Handle timed out requests outside the lock so callbacks may re-enter the bridge
+                for (id, request) in timed_out {
+                    warn!("Request {} [{}] timed out after {:?}", request.event, id, request.timeout());
+                    metrics.record_timeout();
+                    if let Some(observer) = &observer {
+                        observer.on_timeout(&id, &request.event);
+                    }
+                    let error = IPCError::Timeout(format!("request {} timed out after {:?}", id, request.timeout()));
+                    if panic::catch_unwind(AssertUnwindSafe(|| (request.callback)(Err(error)))).is_err() {
+                        error!("Timeout callback for request {} [{}] panicked", request.event, id);
+                    }
+                }
+            }
+        })
+    }
+
+    /// Start a background thread that pings Node.js to detect a hung backend
+    ///
+    /// Every `interval` a request with the heartbeat event (default `__ping__`)
+    /// is sent. Any response from Node.js, including an error response, counts
+    /// as a pong. If none arrives within `timeout`, or the ping fails locally
+    /// (timed out by the timeout checker, cancelled, not sent), `on_dead` is
+    /// invoked so the caller can restart the backend. Pings are skipped while
+    /// stdin is not set.
+    pub fn start_heartbeat<F>(&self, interval: Duration, timeout: Duration, on_dead: F) -> JoinHandle<()>
+    where
+        F: Fn() + Send + 'static,
+    {
+        info!("Starting heartbeat every {:?} (timeout {:?})", interval, timeout);
+        let sender = self.sender();
+        let shutdown = Arc::clone(&self.shutdown);
+        let event = self.heartbeat_event.clone();
+
+        thread::spawn(move || {
+            loop {
+                thread::sleep(interval);
+                if shutdown.load(Ordering::SeqCst) {
+                    debug!("Heartbeat stopped");
+                    break;
+                }
+                if sender.stdin.lock().unwrap().is_none() {
+                    debug!("Skipping heartbeat, stdin not available");
+                    continue;
+                }
+
+                let (tx, rx) = mpsc::channel();
+                let sent = sender.send_request(&event, Value::Null, timeout, Box::new(move |result| {
+                    let _ = tx.send(result);
+                }));
+                match sent {
+                    Ok(id) => match rx.recv_timeout(timeout) {
+                        Ok(Ok(_)) | Ok(Err(IPCError::BackendError { .. })) | Ok(Err(IPCError::UnsupportedVersion(_))) => {
+                            debug!("Heartbeat acknowledged")
+                        }
+                        Ok(Err(e)) => {
+                            warn!("Heartbeat failed without a response: {}", e);
+                            on_dead();
+                        }
+                        Err(_) => {
+                            // Drop the ping wherever it waits, so it is not sent late
+                            sender.pending_requests.lock().unwrap().remove(&id);
+                            sender.limiter.remove_waiting(&id);
+                            warn!("No heartbeat response within {:?}, backend appears hung", timeout);
+                            on_dead();
+                        }
+                    },
+                    Err(e) => {
+                        warn!("Failed to send heartbeat: {}", e);
+                        on_dead();
+                    }
+                }
+            }
+        })
+    }
+
+    /// Signal the stdout listener, timeout checker and heartbeat threads to exit
+    ///
+    /// Threads notice the flag on their next loop iteration; join the handles
+    /// returned by the `start_*` methods to wait for them.
+    pub fn shutdown(&self) {
+        info!("Shutting down IPC bridge background threads");
+        self.shutdown.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether `shutdown` has been called
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    /// Let in-flight requests finish before shutting down
+    ///
+    /// New requests are rejected with `IPCError::ShuttingDown` from now on;
+    /// events can still be emitted. Waits up to `timeout` for the pending
+    /// requests to complete, then resolves the remaining callbacks with
+    /// `IPCError::ShuttingDown` so the UI does not report them as timeouts.
+    /// Returns the number of requests that were cut off. Call this before
+    /// killing the backend, e.g. from `ProcessManager::on_shutdown`.
+    pub fn drain(&self, timeout: Duration) -> usize {
+        info!("Draining IPC bridge, {} request(s) in flight", self.pending_request_count());
+        self.draining.store(true, Ordering::SeqCst);
+
+        // Held-back requests were never sent, so there is nothing to wait for
+        let waiting: Vec<WaitingRequest> = self.limiter.waiting.lock().unwrap().drain(..).collect();
+        for request in waiting {
+            debug!("Held-back request {} [{}] cut off by shutdown", request.event, request.id);
+            (request.callback)(Err(IPCError::ShuttingDown));
+        }
+
+        let deadline = Instant::now() + timeout;
+        while self.pending_request_count() > 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(WAIT_POLL_INTERVAL_MS));
+        }
+
+        let remaining: Vec<(String, PendingRequest)> = self.pending_requests.lock().unwrap().drain().collect();
+        if !remaining.is_empty() {
+            warn!("{} request(s) still pending after {:?}, cancelling", remaining.len(), timeout);
+        }
+        let cancelled = remaining.len();
+        for (id, request) in remaining {
+            debug!("Request {} [{}] cut off by shutdown", request.event, id);
+            (request.callback)(Err(IPCError::ShuttingDown));
+        }
+        cancelled
+    }
+
+    /// Flush queued messages before the backend is terminated
+    ///
+    /// Blocks until the queue is empty, retrying failed writes, for up to
+    /// `timeout`. Fails immediately with `IPCError::StdinNotAvailable` if
+    /// stdin is not set, and with `IPCError::Timeout` if messages are still
+    /// queued at the deadline. Call it before killing the backend (e.g. from
+    /// `ProcessManager::on_shutdown`) so a final "save state" event arrives.
+    pub fn final_flush(&self, timeout: Duration) -> Result<(), IPCError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.try_flush_queue() {
+                Err(IPCError::StdinNotAvailable) => return Err(IPCError::StdinNotAvailable),
+                Err(e) => debug!("Final flush attempt failed: {}", e),
+                Ok(_) => {}
+            }
+            let remaining = self.queue_size();
+            if remaining == 0 {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                warn!("{} message(s) still queued after final flush", remaining);
+                return Err(IPCError::Timeout(format!(
+                    "{} queued message(s) not flushed within {:?}",
+                    remaining, timeout
+                )));
+            }
+            thread::sleep(Duration::from_millis(WAIT_POLL_INTERVAL_MS));
+        }
+    }
+
+    /// Register an event handler
+    ///
+    /// Returns a handler ID that can be passed to `off` to deregister it.
+    pub fn on<F>(&self, event: &str, handler: F) -> usize
+    where
+        F: Fn(Value) + Send + 'static,
+    {
+        self.add_handler(event, Box::new(handler), false)
+    }
+
+    /// Register an event handler that fires only for the next occurrence of `event`
+    ///
+    /// The handler is removed after it runs. Returns a handler ID so it can
+    /// still be cancelled with `off` before the event arrives.
+    pub fn once<F>(&self, event: &str, handler: F) -> usize
+    where
+        F: FnOnce(Value) + Send + 'static,
+    {
+        let handler = Mutex::new(Some(handler));
+        self.add_handler(
+            event,
+            Box::new(move |payload| {
+                if let Some(handler) = handler.lock().unwrap().take() {
+                    handler(payload);
+                }
+            }),
+            true,
+        )
+    }
+
+    /// Block until the next occurrence of `event` and return its payload
+    ///
+    /// The blocking counterpart of `once`, for tests and sequential flows that
+    /// need to synchronize on a backend event. The temporary handler is
+    /// removed again if nothing arrives within `timeout`. Only occurrences
+    /// after the call are seen.
+    pub fn wait_for_event(&self, event: &str, timeout: Duration) -> Result<Value, String> {
+        let (tx, rx) = mpsc::channel();
+        let id = self.once(event, move |payload| {
+            let _ = tx.send(payload);
+        });
+
+        rx.recv_timeout(timeout).map_err(|_| {
+            self.off(event, id);
+            format!("Timed out after {:?} waiting for event {}", timeout, event)
+        })
+    }
+
+    fn add_handler(&self, event: &str, handler: EventHandler, once: bool) -> usize {
+        let id = self.next_handler_id.fetch_add(1, Ordering::Relaxed);
+        let mut handlers = self.event_handlers.lock().unwrap();
+        handlers
+            .entry(event.to_string())
+            .or_default()
+            .push(HandlerEntry { id, handler, once });
+
+        debug!("Registered {}handler {} for event: {}", if once { "one-shot " } else { "" }, id, event);
+        id
+    }
+
+    /// Answer requests Node.js sends with the given event name
+    ///
+    /// When a `Request` for `event` arrives, the stdout listener runs the
+    /// handler and sends its result back under the same ID: `Ok` as a
+    /// response, `Err` as an error response. Handled requests are not passed
+    /// to `on` handlers or the listener's `on_message`. There is one handler
+    /// per event; registering again replaces it.
+    pub fn on_request<F>(&self, event: &str, handler: F)
+    where
+        F: Fn(Value) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        self.request_handlers.lock().unwrap().insert(event.to_string(), Arc::new(handler));
+        debug!("Registered request handler for event: {}", event);
+    }
+
+    /// Remove the request handler for `event`, if any
+    pub fn off_request(&self, event: &str) -> bool {
+        self.request_handlers.lock().unwrap().remove(event).is_some()
+    }
+
+    /// Register a handler for raw bytes sent with an event
+    ///
+    /// Fires for messages that carry a binary frame (see `emit_binary`), which
+    /// requires `WireFormat::MessagePack`. Handlers registered with `on` still
+    /// receive the `__binary_len__` payload. Returns an ID for `off`.
+    pub fn on_binary<F>(&self, event: &str, handler: F) -> usize
+    where
+        F: Fn(Vec<u8>) + Send + 'static,
+    {
+        let id = self.next_handler_id.fetch_add(1, Ordering::Relaxed);
+        self.binary_handlers
+            .lock()
+            .unwrap()
+            .entry(event.to_string())
+            .or_default()
+            .push((id, Box::new(handler)));
+
+        debug!("Registered binary handler {} for event: {}", id, event);
+        id
+    }
+
+    /// Send raw bytes to Node.js without base64-encoding them
+    ///
+    /// The bytes follow a header message as their own length-prefixed frame.
+    /// Fails with `IPCError::SerializationError` unless the bridge uses
+    /// `WireFormat::MessagePack`.
+    pub fn emit_binary(&self, event: &str, bytes: Vec<u8>) -> Result<(), String> {
+        if self.wire_format != WireFormat::MessagePack {
+            return Err(IPCError::SerializationError(
+                "binary payloads require WireFormat::MessagePack".to_string(),
+            )
+            .into());
+        }
+        self.send_to_node(&IPCMessage::binary_event(event, bytes))
+    }
+
+    /// Write an already-encoded line to Node.js stdin as is
+    ///
+    /// For forwarding or replaying captured traffic; a newline is appended if
+    /// missing. The line is queued like any other message while stdin is not
+    /// available, but it is not validated, compressed or sequence-stamped.
+    /// Fails unless the bridge uses `WireFormat::Json`.
+    pub fn send_raw(&self, line: &str) -> Result<(), String> {
+        if line.trim().is_empty() {
+            return Err(IPCError::SerializationError("raw line is empty".to_string()).into());
+        }
+        if self.wire_format != WireFormat::Json {
+            return Err(IPCError::SerializationError("raw lines require WireFormat::Json".to_string()).into());
+        }
+        let mut line = line.to_string();
+        if !line.ends_with('\n') {
+            line.push('\n');
+        }
+        self.sender().send_raw(line.into_bytes()).map_err(String::from)
+    }
+
+    /// Remove a specific event handler by the ID returned from `on` or `on_binary`
+    pub fn off(&self, event: &str, id: usize) -> bool {
+        {
+            let mut binary_handlers = self.binary_handlers.lock().unwrap();
+            if let Some(list) = binary_handlers.get_mut(event) {
+                let before = list.len();
+                list.retain(|(handler_id, _)| *handler_id != id);
+                if list.len() != before {
+                    if list.is_empty() {
+                        binary_handlers.remove(event);
+                    }
+                    debug!("Removed binary handler {} for event: {}", id, event);
+                    return true;
+                }
+            }
+        }
+
+        let mut handlers = self.event_handlers.lock().unwrap();
+        let Some(list) = handlers.get_mut(event) else {
+            return false;
+        };
+
+        let before = list.len();
+        list.retain(|entry| entry.id != id);
+        let removed = list.len() != before;
+        if list.is_empty() {
+            handlers.remove(event);
+        }
+
+        if removed {
+            debug!("Removed handler {} for event: {}", id, event);
+        }
+        removed
+    }
+
+    /// Remove all handlers registered for an event, returning how many were removed
+    pub fn remove_all_handlers(&self, event: &str) -> usize {
+        let binary = self.binary_handlers.lock().unwrap().remove(event).map(|list| list.len()).unwrap_or(0);
+        let mut handlers = self.event_handlers.lock().unwrap();
+        let removed = binary + handlers.remove(event).map(|list| list.len()).unwrap_or(0);
+        debug!("Removed {} handler(s) for event: {}", removed, event);
+        removed
+    }
+
+    /// Send a message to Node.js via stdin
+    fn send_to_node(&self, msg: &IPCMessage) -> Result<(), String> {
+        self.sender().send(msg).map_err(String::from)
+    }
+
+    /// Queue a message for later sending
+    ///
+    /// Enforces the queue limit according to the bridge's `QueueOverflowPolicy`.
+    pub fn queue_message(&self, mut msg: IPCMessage) -> Result<(), IPCError> {
+        let sender = self.sender();
+        sender.transform(&mut msg);
+        sender.queue(msg)
+    }
+
+    /// Get a copy of the queued messages, in the order they will be sent
+    ///
+    /// Lines queued by `send_raw` are not included.
+    pub fn queue_peek(&self) -> Vec<IPCMessage> {
+        self.message_queue.lock().unwrap().iter().filter_map(Queued::message).cloned().collect()
+    }
+
+    /// Discard every queued message, returning how many were dropped
+    ///
+    /// Useful when queued events became irrelevant (e.g. after a config
+    /// change) and should not reach a freshly restarted backend. Discarded
+    /// requests are cancelled: their callbacks receive `IPCError::Cancelled`.
+    pub fn queue_clear(&self) -> usize {
+        let cleared = {
+            // Lock order is stdin then queue, matching the send path
+            let _stdin_guard = self.stdin.lock().unwrap();
+            self.message_queue.lock().unwrap().clear()
+        };
+        self.backpressure.update(0);
+        info!("Cleared {} queued message(s)", cleared.len());
+
+        // These never reached Node.js, so there is nothing to cancel there
+        for msg in cleared.iter().filter_map(Queued::message) {
+            if let (IPCMessageType::Request, Some(id)) = (&msg.msg_type, &msg.id) {
+                self.cancel(id, false);
+            }
+        }
+        cleared.len()
+    }
+
+    /// Save the queued events to `path` as JSON lines
+    ///
+    /// Only `Event` messages are written: a queued request's callback lives in
+    /// this process and cannot survive a restart, and binary frames and raw
+    /// lines have no JSON message form. Priorities are not kept. The queue itself is left as is.
+    /// Returns the number of messages saved.
+    pub fn save_queue<P: AsRef<std::path::Path>>(&self, path: P) -> Result<usize, IPCError> {
+        let mut lines = String::new();
+        let mut saved = 0;
+        {
+            let queue = self.message_queue.lock().unwrap();
+            for entry in queue.iter() {
+                let msg = match entry.message() {
+                    Some(msg) if matches!(msg.msg_type, IPCMessageType::Event) && msg.binary.is_none() => msg,
+                    _ => {
+                        debug!("Not persisting queued {}", entry.label());
+                        continue;
+                    }
+                };
+                lines.push_str(&encode_message_for_stdin(msg).map_err(IPCError::SerializationError)?);
+                saved += 1;
+            }
+        }
+        std::fs::write(path.as_ref(), lines)
+            .map_err(|e| IPCError::Other(format!("Failed to save queue to {}: {}", path.as_ref().display(), e)))?;
+        info!("Saved {} queued message(s) to {}", saved, path.as_ref().display());
+        Ok(saved)
+    }
+
+    /// Queue the events saved by `save_queue`
+    ///
+    /// They are delivered like any queued message once stdin is set. Lines
+    /// that do not parse (e.g. a file cut short by a crash) and non-event
+    /// messages are skipped. The loaded messages get fresh sequence numbers
+    /// and are subject to the queue limit, but do not go through the outbound
+    /// middleware again. Returns the number queued.
+    pub fn load_queue<P: AsRef<std::path::Path>>(&self, path: P) -> Result<usize, IPCError> {
+        let contents = std::fs::read(path.as_ref())
+            .map_err(|e| IPCError::Other(format!("Failed to load queue from {}: {}", path.as_ref().display(), e)))?;
+        let sender = self.sender();
+        let mut loaded = 0;
+        for line in String::from_utf8_lossy(&contents).lines().filter(|line| !line.trim().is_empty()) {
+            match parse_stdin_message(line) {
+                Ok(msg) if matches!(msg.msg_type, IPCMessageType::Event) => {
+                    sender.queue(msg)?;
+                    loaded += 1;
+                }
+                Ok(msg) => warn!("Skipping persisted {}: only events can be restored", log_label(&msg)),
+                Err(e) => warn!("Skipping corrupt queue entry: {}", e),
+            }
+        }
+        info!("Loaded {} queued message(s) from {}", loaded, path.as_ref().display());
+
+        if self.stdin.lock().unwrap().is_some() {
+            if let Err(e) = self.try_flush_queue() {
+                error!("Failed to flush message queue: {}", e);
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Reader state shared by the stdout listener and `process_line`
+    fn inbound_stream(&self) -> InboundStream {
+        InboundStream {
+            max_message_bytes: self.max_message_bytes,
+            strict_utf8: self.strict_utf8,
+            corrupt_stream_threshold: self.corrupt_stream_threshold,
+            observer: self.observer.clone(),
+            stream_corrupt_callback: Arc::clone(&self.stream_corrupt_callback),
+            stream_error_callback: Arc::clone(&self.stream_error_callback),
+            parse_failures: Arc::clone(&self.parse_failures),
+        }
+    }
+
+    /// Handle to the incoming path that can be moved onto the listener thread
+    fn dispatcher(&self) -> Dispatcher {
+        Dispatcher {
+            pending_requests: Arc::clone(&self.pending_requests),
+            event_handlers: Arc::clone(&self.event_handlers),
+            binary_handlers: Arc::clone(&self.binary_handlers),
+            request_handlers: Arc::clone(&self.request_handlers),
+            metrics: Arc::clone(&self.metrics),
+            observer: self.observer.clone(),
+            orphan_response_callback: Arc::clone(&self.orphan_response_callback),
+            inbound_middleware: Arc::clone(&self.inbound_middleware),
+            sender: self.sender(),
+        }
+    }
+
+    /// Handle to the outgoing path that can be moved into callbacks
+    fn sender(&self) -> RequestSender {
+        RequestSender {
+            stdin: Arc::clone(&self.stdin),
+            message_queue: Arc::clone(&self.message_queue),
+            pending_requests: Arc::clone(&self.pending_requests),
+            next_seq: Arc::clone(&self.next_seq),
+            max_queue_size: self.max_queue_size,
+            overflow_policy: self.overflow_policy,
+            wire_format: self.wire_format,
+            compression: self.compression,
+            schemas: self.schema_validation.then(|| Arc::clone(&self.schemas)),
+            metrics: Arc::clone(&self.metrics),
+            backend_expected: Arc::clone(&self.backend_expected),
+            fail_fast: false,
+            max_message_bytes: self.max_message_bytes,
+            backpressure: Arc::clone(&self.backpressure),
+            circuit: Arc::clone(&self.circuit),
+            draining: Arc::clone(&self.draining),
+            limiter: Arc::clone(&self.limiter),
+            negotiated_version: Arc::clone(&self.negotiated_version),
+            ready: Arc::clone(&self.ready),
+            priority: Priority::default(),
+            activity: Arc::clone(&self.activity_callback),
+            observer: self.observer.clone(),
+            outbound_middleware: Arc::clone(&self.outbound_middleware),
+        }
+    }
+
+    /// Get a snapshot of the bridge's request metrics
+    pub fn metrics(&self) -> IPCMetricsSnapshot {
+        self.metrics.snapshot(self.queue_size(), self.pending_request_count())
+    }
+
+    /// Total encoded bytes written to Node.js stdin
+    ///
+    /// Counts every message, request and queue flush actually written, after
+    /// compression; queued and failed writes are not included.
+    pub fn bytes_sent(&self) -> u64 {
+        self.metrics.bytes_sent()
+    }
+
+    /// Total bytes the stdout listener read from Node.js, log output included
+    pub fn bytes_received(&self) -> u64 {
+        self.metrics.bytes_received()
+    }
+
+    /// Get the current message queue size
+    pub fn queue_size(&self) -> usize {
+        let queue = self.message_queue.lock().unwrap();
+        queue.len()
+    }
+
+    /// Cancel a pending request
+    ///
+    /// The request's callback is invoked with `IPCError::Cancelled`, so futures
+    /// and blocking callers waiting on it resolve instead of hanging. A request
+    /// still in the queue is taken out of it and never reaches Node.js. If it
+    /// was already written, Node.js is sent a `CANCEL_EVENT` so it can stop
+    /// working on it; that notice is not queued while stdin is unavailable.
+    pub fn cancel_request(&self, id: &str) -> bool {
+        self.cancel(id, true)
+    }
+
+    /// Cancel a request locally, optionally telling Node.js to abort it
+    fn cancel(&self, id: &str, notify_backend: bool) -> bool {
+        let request = self.pending_requests.lock().unwrap().remove(id);
+        let (event, callback, mut sent) = match request {
+            Some(request) => (request.event, request.callback, true),
+            None => match self.limiter.remove_waiting(id) {
+                Some(request) => (request.event, request.callback, false),
+                None => return false,
+            },
+        };
+        if sent {
+            let mut queue = self.message_queue.lock().unwrap();
+            let queued = queue.remove_first(|entry| entry.message().is_some_and(|msg| msg.id.as_deref() == Some(id)));
+            if queued.is_some() {
+                sent = false;
+                let depth = queue.len();
+                drop(queue);
+                self.backpressure.update(depth);
+            }
+        }
+        debug!("Cancelled request {} [{}]", event, id);
+
+        if sent && notify_backend {
+            let mut sender = self.sender();
+            sender.fail_fast = true;
+            sender.priority = Priority::High;
+            if let Err(e) = sender.send(&IPCMessage::event(CANCEL_EVENT, serde_json::json!({ "id": id }))) {
+                debug!("Could not notify Node.js of cancelled request {}: {}", id, e);
+            }
+        }
+        callback(Err(IPCError::Cancelled(format!("request {} was cancelled", id))));
+        true
+    }
+
+    /// Get the number of pending requests
+    pub fn pending_request_count(&self) -> usize {
+        let requests = self.pending_requests.lock().unwrap();
+        requests.len()
+    }
+
+    /// List the pending requests, oldest first
+    pub fn pending_requests_info(&self) -> Vec<PendingRequestInfo> {
+        let requests = self.pending_requests.lock().unwrap();
+        let mut info: Vec<PendingRequestInfo> = requests
+            .iter()
+            .map(|(id, request)| PendingRequestInfo {
+                id: id.clone(),
+                event: request.event.clone(),
+                age: request.created_at.elapsed(),
+                timeout: request.timeout(),
+            })
+            .collect();
+        info.sort_by_key(|request| std::cmp::Reverse(request.age));
+        info
+    }
+}
+
+impl Default for IPCBridge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Monotonic counter backing request IDs
+static REQUEST_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Per-process random prefix so IDs from different runs don't collide
+fn request_id_prefix() -> &'static str {
+    static PREFIX: OnceLock<String> = OnceLock::new();
+    PREFIX.get_or_init(|| {
+        use std::collections::hash_map::RandomState;
+        use std::hash::{BuildHasher, Hasher};
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(std::process::id());
+        hasher.write_u128(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos())
+                .unwrap_or_default(),
+        );
+        format!("{:016x}", hasher.finish())
+    })
+}
+
+/// Generate a unique request ID
+///
+/// Combines a per-process random prefix with an atomic counter, so IDs stay
+/// unique no matter how many requests are issued concurrently.
+fn generate_request_id() -> String {
+    let seq = REQUEST_COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("req_{}_{}", request_id_prefix(), seq)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ipc_message_event_creation() {
+        let msg = IPCMessage::event("test_event", serde_json::json!({"key": "value"}));
+        assert_eq!(msg.event, "test_event");
+        assert!(matches!(msg.msg_type, IPCMessageType::Event));
+        assert!(msg.id.is_none());
+    }
+
+    #[test]
+    fn test_ipc_message_request_creation() {
+        let msg = IPCMessage::request("req-001", "get_data", serde_json::json!({}));
+        assert_eq!(msg.id, Some("req-001".to_string()));
+        assert!(matches!(msg.msg_type, IPCMessageType::Request));
+    }
+
+    #[test]
+    fn test_ipc_message_response_creation() {
+        let msg = IPCMessage::response("req-001", "get_data", serde_json::json!({"result": 42}));
+        assert_eq!(msg.id, Some("req-001".to_string()));
+        assert!(matches!(msg.msg_type, IPCMessageType::Response));
+    }
+
+    #[test]
+    fn test_ipc_message_error_response() {
+        let msg = IPCMessage::error_response("req-001", "get_data", "Something went wrong");
+        assert_eq!(msg.error, Some("Something went wrong".to_string()));
+    }
+
+    #[test]
+    fn test_parse_valid_message() {
+        let json = r#"{"id":"msg-001","msg_type":"event","event":"test","payload":{},"error":null}"#;
+        let result = parse_stdin_message(json);
+        assert!(result.is_ok());
+        let msg = result.unwrap();
+        assert_eq!(msg.event, "test");
+    }
+
+    #[test]
+    fn test_parse_invalid_message() {
+        let result = parse_stdin_message("not json");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_encode_message() {
+        let msg = IPCMessage::event("test", serde_json::json!({}));
+        let encoded = encode_message_for_stdin(&msg);
+        assert!(encoded.is_ok());
+        assert!(encoded.unwrap().ends_with('\n'));
+    }
+
+    #[test]
+    fn test_message_pack_round_trip() {
+        let msg = IPCMessage::event("files", serde_json::json!({"diff": "a\nb\n"}));
+        let frame = encode_message(&msg, WireFormat::MessagePack).unwrap();
+
+        let len = u32::from_be_bytes([frame[0], frame[1], frame[2], frame[3]]) as usize;
+        assert_eq!(len, frame.len() - 4);
+
+        let decoded = decode_message(&frame[4..], WireFormat::MessagePack).unwrap();
+        assert_eq!(decoded.event, "files");
+        assert_eq!(decoded.payload["diff"], "a\nb\n");
+    }
+
+    #[test]
+    fn test_json_wire_format_matches_stdin_encoding() {
+        let msg = IPCMessage::event("test", serde_json::json!({"key": "value"}));
+        let frame = encode_message(&msg, WireFormat::Json).unwrap();
+        assert_eq!(frame, encode_message_for_stdin(&msg).unwrap().into_bytes());
+
+        let decoded = decode_message(&frame, WireFormat::Json).unwrap();
+        assert_eq!(decoded.event, "test");
+    }
+
+    #[test]
+    fn test_forward_to_frontend() {
+        let msg = IPCMessage::event("display_message", serde_json::json!({"text": "Hello"}));
+        let (event_name, payload) = forward_to_frontend(&msg);
+        assert_eq!(event_name, "display_message");
+        assert_eq!(payload["text"], "Hello");
+    }
+
+    #[test]
+    fn test_forward_to_frontend_full() {
+        let msg = IPCMessage::error_response("req_9", "load_project", "not found");
+        let (event_name, envelope) = forward_to_frontend_full(&msg);
+        assert_eq!(event_name, "load_project");
+        assert_eq!(envelope["id"], "req_9");
+        assert_eq!(envelope["msg_type"], "response");
+        assert_eq!(envelope["error"], "not found");
+        assert_eq!(envelope["payload"], msg.payload);
+
+        let (_, envelope) = forward_to_frontend_full(&IPCMessage::event("tick", serde_json::json!(3)));
+        assert!(envelope["id"].is_null());
+        assert_eq!(envelope["msg_type"], "event");
+        assert_eq!(envelope["payload"], 3);
+        assert!(envelope.get("meta").is_none());
+    }
+
+    #[test]
+    fn test_meta_round_trips_and_is_optional() {
+        let msg = IPCMessage::event("file_changed", serde_json::json!({"path": "a.rs"}))
+            .with_meta("session", serde_json::json!("s-1"))
+            .with_meta("trace_id", serde_json::json!("t-9"));
+        let encoded = encode_message_for_stdin(&msg).unwrap();
+        let decoded = parse_stdin_message(encoded.trim()).unwrap();
+        assert_eq!(decoded.meta, msg.meta);
+        assert_eq!(decoded.payload, serde_json::json!({"path": "a.rs"}));
+
+        let (_, envelope) = forward_to_frontend_full(&decoded);
+        assert_eq!(envelope["meta"], serde_json::json!({"session": "s-1", "trace_id": "t-9"}));
+
+        // Messages from backends that predate `meta` still parse, and none is written
+        let old = parse_stdin_message(r#"{"id":null,"msg_type":"event","event":"tick","payload":1,"error":null}"#).unwrap();
+        assert!(old.meta.is_none());
+        assert!(!encode_message_for_stdin(&old).unwrap().contains("meta"));
+    }
+
+    #[test]
+    fn test_ipc_bridge_creation() {
+        let bridge = IPCBridge::new();
+        assert_eq!(bridge.pending_request_count(), 0);
+        assert_eq!(bridge.queue_size(), 0);
+    }
+
+    #[test]
+    fn test_ipc_bridge_with_timeout() {
+        let bridge = IPCBridge::with_timeout(60);
+        assert_eq!(bridge.default_timeout(), 60);
+    }
+
+    #[test]
+    fn test_generate_request_id() {
+        let id1 = generate_request_id();
+        let id2 = generate_request_id();
+        assert!(id1.starts_with("req_"));
+        assert_ne!(id1, id2);
+    }
+
+    #[test]
+    fn test_generate_request_id_unique_across_threads() {
+        let handles: Vec<_> = (0..8)
+            .map(|_| thread::spawn(|| (0..1000).map(|_| generate_request_id()).collect::<Vec<_>>()))
+            .collect();
+
+        let mut ids = std::collections::HashSet::new();
+        for handle in handles {
+            for id in handle.join().unwrap() {
+                assert!(id.starts_with("req_"));
+                assert!(ids.insert(id), "duplicate request id generated");
+            }
+        }
+        assert_eq!(ids.len(), 8000);
+    }
+
+    #[test]
+    fn test_ipc_error_display() {
+        let err = IPCError::StdinNotAvailable;
+        assert_eq!(err.to_string(), "Node.js stdin not available");
+
+        let err = IPCError::Timeout("30s".to_string());
+        assert!(err.to_string().contains("timeout"));
+
+        let err = IPCError::ParseError("invalid json".to_string());
+        assert!(err.to_string().contains("Parse error"));
+    }
+
+    #[test]
+    fn test_ipc_error_to_string() {
+        let err: String = IPCError::SendError("connection refused".to_string()).into();
+        assert!(err.contains("Send error"));
+    }
+
+    #[test]
+    fn test_message_queue() {
+        let bridge = IPCBridge::new();
+
+        // Queue a message when stdin is not available
+        let msg = IPCMessage::event("test", serde_json::json!({}));
+        bridge.queue_message(msg).unwrap();
+
+        assert_eq!(bridge.queue_size(), 1);
+    }
+
+    /// Writer that accepts a fixed number of writes, then fails
+    struct FailingWriter {
+        remaining: usize,
+        written: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.remaining == 0 {
+                return Err(std::io::Error::new(ErrorKind::BrokenPipe, "broken pipe"));
+            }
+            self.remaining -= 1;
+            self.written.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Writer whose writes succeed but whose flush fails like a closed pipe
+    struct FlushBrokenWriter;
+
+    impl Write for FlushBrokenWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Err(std::io::Error::new(ErrorKind::BrokenPipe, "broken pipe"))
+        }
+    }
+
+    #[test]
+    fn test_broken_pipe_on_flush_requeues_events() {
+        let single = IPCBridge::new();
+        single.set_writer(FlushBrokenWriter);
+        let err = single.emit("save_state", serde_json::json!({})).unwrap_err();
+        assert_eq!(err, IPCError::StdinNotAvailable.to_string());
+        assert_eq!(queued_events(&single), vec!["save_state"]);
+
+        let batch = IPCBridge::new();
+        batch.set_writer(FlushBrokenWriter);
+        let err = batch.emit_batch(vec![("edit".to_string(), serde_json::json!({}))]).unwrap_err();
+        assert_eq!(err, IPCError::StdinNotAvailable.to_string());
+        assert_eq!(queued_events(&batch), vec!["edit"]);
+    }
+
+    #[test]
+    fn test_broken_pipe_behind_queued_messages_clears_stdin() {
+        let bridge = IPCBridge::new();
+        bridge.emit("autosave", serde_json::json!({})).unwrap();
+        // The flush on attach fails, leaving "autosave" queued in front of the request
+        bridge.set_writer(FailingWriter { remaining: 0, written: Arc::new(Mutex::new(Vec::new())) });
+        assert_eq!(queued_events(&bridge), vec!["autosave"]);
+
+        let err = bridge.try_request("status", serde_json::json!({}), |_| {}).unwrap_err();
+        assert!(matches!(err, IPCError::StdinNotAvailable));
+        assert!(bridge.stdin.lock().unwrap().is_none());
+        assert_eq!(queued_events(&bridge), vec!["autosave"]);
+        assert!(bridge.pending_requests_info().is_empty());
+
+        let batch = IPCBridge::new();
+        batch.emit("autosave", serde_json::json!({})).unwrap();
+        batch.set_writer(FailingWriter { remaining: 0, written: Arc::new(Mutex::new(Vec::new())) });
+        let err = batch.emit_batch(vec![("edit".to_string(), serde_json::json!({}))]).unwrap_err();
+        assert_eq!(err, IPCError::StdinNotAvailable.to_string());
+        assert_eq!(queued_events(&batch), vec!["autosave", "edit"]);
+    }
+
+    #[test]
+    fn test_emit_batch_writes_once() {
+        let bridge = IPCBridge::new();
+        let written = Arc::new(Mutex::new(Vec::new()));
+        // Only one write succeeds, so the whole batch must go out in it
+        bridge.set_writer(FailingWriter { remaining: 1, written: written.clone() });
+
+        bridge
+            .emit_batch(vec![
+                ("edit".to_string(), serde_json::json!({"file": "a.ts"})),
+                ("edit".to_string(), serde_json::json!({"file": "b.ts"})),
+                ("commit".to_string(), serde_json::json!({})),
+            ])
+            .unwrap();
+
+        let output = String::from_utf8(written.lock().unwrap().clone()).unwrap();
+        let msgs: Vec<IPCMessage> = output.lines().map(|line| parse_stdin_message(line).unwrap()).collect();
+        assert_eq!(msgs.iter().map(|m| m.event.as_str()).collect::<Vec<_>>(), vec!["edit", "edit", "commit"]);
+        assert_eq!(msgs.iter().map(|m| m.seq.unwrap()).collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_emit_batch_rejects_whole_batch() {
+        let bridge = IPCBridge::new().with_max_message_bytes(200);
+        bridge
+            .emit_batch(vec![
+                ("edit".to_string(), serde_json::json!({"file": "a.ts"})),
+                ("edit".to_string(), serde_json::json!({"file": "x".repeat(500)})),
+            ])
+            .unwrap_err();
+        assert_eq!(bridge.queue_size(), 0);
+
+        bridge.emit_batch(vec![("edit".to_string(), serde_json::json!({}))]).unwrap();
+        assert_eq!(bridge.queue_size(), 1);
+    }
+
+    #[test]
+    fn test_emit_batch_queues_all_or_nothing() {
+        let bridge = IPCBridge::new().with_queue_limit(3, QueueOverflowPolicy::Reject);
+        bridge.emit("a", serde_json::json!({})).unwrap();
+        bridge.emit("b", serde_json::json!({})).unwrap();
+
+        let batch = vec![("c".to_string(), serde_json::json!({})), ("d".to_string(), serde_json::json!({}))];
+        assert!(bridge.emit_batch(batch).is_err());
+        assert_eq!(queued_events(&bridge), vec!["a", "b"]);
+
+        bridge.emit_batch(vec![("c".to_string(), serde_json::json!({}))]).unwrap();
+        let seqs: Vec<u64> = bridge.queue_peek().iter().map(|msg| msg.seq.unwrap()).collect();
+        assert_eq!(seqs, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_emit_batch_held_by_ready_gate_all_or_nothing() {
+        let bridge = IPCBridge::new()
+            .with_ready_gate()
+            .with_queue_limit(2, QueueOverflowPolicy::Reject);
+        let written = Arc::new(Mutex::new(Vec::new()));
+        bridge.set_writer(FailingWriter { remaining: usize::MAX, written: written.clone() });
+        bridge.emit("a", serde_json::json!({})).unwrap();
+
+        let batch = vec![("b".to_string(), serde_json::json!({})), ("c".to_string(), serde_json::json!({}))];
+        assert!(bridge.emit_batch(batch).is_err());
+        assert_eq!(queued_events(&bridge), vec!["a"]);
+        assert!(written.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_try_flush_queue_without_stdin() {
+        let bridge = IPCBridge::new();
+        bridge.emit("a", serde_json::json!({})).unwrap();
+
+        assert!(matches!(bridge.try_flush_queue(), Err(IPCError::StdinNotAvailable)));
+        assert_eq!(bridge.queue_size(), 1);
+    }
+
+    #[test]
+    fn test_set_writer_flushes_queue() {
+        let bridge = IPCBridge::new();
+        for event in ["a", "b"] {
+            bridge.emit(event, serde_json::json!({})).unwrap();
+        }
+
+        let written = Arc::new(Mutex::new(Vec::new()));
+        bridge.set_writer(FailingWriter { remaining: usize::MAX, written: written.clone() });
+
+        assert_eq!(bridge.queue_size(), 0);
+        let output = String::from_utf8(written.lock().unwrap().clone()).unwrap();
+        assert_eq!(output.lines().count(), 2);
+        assert_eq!(bridge.try_flush_queue().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_broken_pipe_requeues_events() {
+        let bridge = IPCBridge::new();
+        let dead = Arc::new(Mutex::new(Vec::new()));
+        bridge.set_writer(FailingWriter { remaining: 0, written: dead.clone() });
+
+        let err = bridge.emit("save_state", serde_json::json!({})).unwrap_err();
+        assert_eq!(err, IPCError::StdinNotAvailable.to_string());
+        assert_eq!(bridge.queue_size(), 1);
+
+        // Requests are reported as failed instead of re-queued
+        let result = bridge.try_request("status", serde_json::json!({}), |_| {});
+        assert!(matches!(result, Err(IPCError::StdinNotAvailable)));
+        assert_eq!(bridge.pending_request_count(), 0);
+        assert_eq!(bridge.queue_size(), 1);
+
+        let written = Arc::new(Mutex::new(Vec::new()));
+        bridge.set_writer(FailingWriter { remaining: usize::MAX, written: written.clone() });
+        assert_eq!(bridge.queue_size(), 0);
+        let output = String::from_utf8(written.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("save_state"));
+        assert!(dead.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_final_flush() {
+        let bridge = IPCBridge::new();
+        bridge.emit("save_state", serde_json::json!({})).unwrap();
+        assert!(matches!(bridge.final_flush(Duration::from_secs(5)), Err(IPCError::StdinNotAvailable)));
+
+        // A writer that keeps failing leaves the message queued
+        bridge.set_writer(FailingWriter { remaining: 0, written: Arc::new(Mutex::new(Vec::new())) });
+        assert!(matches!(bridge.final_flush(Duration::from_millis(50)), Err(IPCError::Timeout(_))));
+        assert_eq!(bridge.queue_size(), 1);
+
+        let written = Arc::new(Mutex::new(Vec::new()));
+        *bridge.stdin.lock().unwrap() = Some(Box::new(FailingWriter { remaining: usize::MAX, written: written.clone() }));
+        bridge.final_flush(Duration::from_secs(1)).unwrap();
+        assert_eq!(bridge.queue_size(), 0);
+        assert!(String::from_utf8(written.lock().unwrap().clone()).unwrap().contains("save_state"));
+    }
+
+    #[test]
+    fn test_try_flush_queue_keeps_failed_message() {
+        let bridge = IPCBridge::new();
+        for event in ["a", "b", "c"] {
+            bridge.emit(event, serde_json::json!({})).unwrap();
+        }
+
+        // set_writer flushes "a", then fails on "b"
+        let written = Arc::new(Mutex::new(Vec::new()));
+        bridge.set_writer(FailingWriter { remaining: 1, written: written.clone() });
+
+        assert_eq!(queued_events(&bridge), vec!["b", "c"]);
+        assert!(matches!(bridge.try_flush_queue(), Err(IPCError::SendError(_))));
+        assert_eq!(queued_events(&bridge), vec!["b", "c"]);
+    }
+
+    /// Event names and sequence numbers of the messages in a written buffer
+    fn written_messages(written: &Arc<Mutex<Vec<u8>>>) -> Vec<(String, Option<u64>)> {
+        let output = String::from_utf8(written.lock().unwrap().clone()).unwrap();
+        output
+            .lines()
+            .map(|line| {
+                let msg = parse_stdin_message(line).unwrap();
+                (msg.event, msg.seq)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_messages_keep_fifo_order_across_queue_and_stdin() {
+        let bridge = IPCBridge::new();
+        bridge.emit("a", serde_json::json!({})).unwrap();
+        bridge.emit("b", serde_json::json!({})).unwrap();
+
+        // Stdin appears without the queue being flushed yet
+        let written = Arc::new(Mutex::new(Vec::new()));
+        *bridge.stdin.lock().unwrap() = Some(Box::new(FailingWriter { remaining: usize::MAX, written: written.clone() }));
+        bridge.emit("c", serde_json::json!({})).unwrap();
+        bridge.emit("d", serde_json::json!({})).unwrap();
+
+        assert_eq!(bridge.queue_size(), 0);
+        assert_eq!(
+            written_messages(&written),
+            vec![
+                ("a".to_string(), Some(1)),
+                ("b".to_string(), Some(2)),
+                ("c".to_string(), Some(3)),
+                ("d".to_string(), Some(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_process_line_dispatches_synchronously() {
+        let bridge = IPCBridge::new();
+        assert!(bridge.process_line("   ").is_none());
+        assert!(bridge.process_line("not a message").is_none());
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let handler_seen = Arc::clone(&seen);
+        bridge.on("progress", move |payload| handler_seen.lock().unwrap().push(payload));
+        let msg = bridge
+            .process_line(r#"{"id":null,"msg_type":"event","event":"progress","payload":{"done":1},"error":null}"#)
+            .unwrap();
+        assert_eq!(msg.event, "progress");
+        assert_eq!(*seen.lock().unwrap(), vec![serde_json::json!({"done": 1})]);
+
+        let result = Arc::new(Mutex::new(None));
+        let callback_result = Arc::clone(&result);
+        let id = bridge
+            .request("status", serde_json::json!({}), move |r| {
+                *callback_result.lock().unwrap() = Some(r);
+            })
+            .unwrap();
+        let line = format!(r#"{{"id":"{}","msg_type":"response","event":"status","payload":"ok","error":null}}"#, id);
+        assert!(bridge.process_line(&line).is_some());
+        assert!(matches!(result.lock().unwrap().take(), Some(Ok(v)) if v == "ok"));
+        assert_eq!(bridge.pending_request_count(), 0);
+    }
+
+    #[test]
+    fn test_process_line_reports_like_the_listener() {
+        let bridge = IPCBridge::new().with_max_message_bytes(64).with_corrupt_stream_threshold(2);
+        let corrupt = Arc::new(Mutex::new(Vec::new()));
+        let corrupt_seen = Arc::clone(&corrupt);
+        bridge.on_stream_corrupt(move |failures| corrupt_seen.lock().unwrap().push(failures));
+        let errors = Arc::new(Mutex::new(0));
+        let errors_seen = Arc::clone(&errors);
+        bridge.on_stream_error(move |_| *errors_seen.lock().unwrap() += 1);
+
+        let oversized = format!(r#"{{"id":null,"msg_type":"event","event":"{}","payload":{{}}}}"#, "x".repeat(64));
+        assert!(bridge.process_line(&oversized).is_none());
+        assert_eq!(*errors.lock().unwrap(), 1);
+
+        // Plain-text output is skipped; only malformed messages count as failures
+        assert!(bridge.process_line("server listening").is_none());
+        assert!(bridge.process_line("{broken").is_none());
+        assert!(bridge.process_line("{broken").is_none());
+        assert_eq!(*corrupt.lock().unwrap(), vec![2]);
+    }
+
+    #[test]
+    fn test_middleware_transforms_outbound_and_inbound_messages() {
+        let bridge = IPCBridge::new();
+        bridge.add_outbound_middleware(|msg| msg.payload["token"] = serde_json::json!("secret"));
+        bridge.add_outbound_middleware(|msg| {
+            let token = msg.payload["token"].clone();
+            msg.payload["echo"] = token;
+        });
+        bridge.add_inbound_middleware(|msg| {
+            if let Some(payload) = msg.payload.as_object_mut() {
+                payload.remove("internal");
+            }
+        });
+
+        let written = Arc::new(Mutex::new(Vec::new()));
+        bridge.set_writer(FailingWriter { remaining: usize::MAX, written: written.clone() });
+        bridge.emit("save", serde_json::json!({"path": "a.txt"})).unwrap();
+
+        let output = String::from_utf8(written.lock().unwrap().clone()).unwrap();
+        let sent = parse_stdin_message(output.trim()).unwrap();
+        assert_eq!(sent.payload, serde_json::json!({"path": "a.txt", "token": "secret", "echo": "secret"}));
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let handler_seen = Arc::clone(&seen);
+        bridge.on("progress", move |payload| handler_seen.lock().unwrap().push(payload));
+        let dispatched = bridge
+            .process_line(r#"{"id":null,"msg_type":"event","event":"progress","payload":{"done":1,"internal":true},"error":null}"#)
+            .unwrap();
+        assert_eq!(dispatched.payload, serde_json::json!({"done": 1}));
+        assert_eq!(*seen.lock().unwrap(), vec![serde_json::json!({"done": 1})]);
+    }
+
+    #[test]
+    fn test_bytes_sent_and_received() {
+        let bridge = IPCBridge::new();
+        bridge.emit("queued", serde_json::json!({"n": 1})).unwrap();
+        assert_eq!(bridge.bytes_sent(), 0, "queued messages are not written yet");
+
+        let written = Arc::new(Mutex::new(Vec::new()));
+        bridge.set_writer(FailingWriter { remaining: usize::MAX, written: written.clone() });
+        bridge.emit("direct", serde_json::json!({"n": 2})).unwrap();
+        let output = String::from_utf8(written.lock().unwrap().clone()).unwrap();
+        let encoded: usize = output
+            .lines()
+            .map(|line| encode_message_for_stdin(&parse_stdin_message(line).unwrap()).unwrap().len())
+            .sum();
+        assert_eq!(bridge.bytes_sent(), encoded as u64);
+        assert_eq!(bridge.bytes_sent(), output.len() as u64);
+
+        let input = "{\"id\":null,\"msg_type\":\"event\",\"event\":\"progress\",\"payload\":{},\"error\":null}\nlog line\n";
+        bridge.start_stdout_listener(std::io::Cursor::new(input.as_bytes().to_vec()), |_| {}).join().unwrap();
+        assert_eq!(bridge.bytes_received(), input.len() as u64);
+        assert_eq!(bridge.metrics().bytes_received, input.len() as u64);
+    }
+
+    #[test]
+    fn test_send_raw_writes_line_verbatim_and_queues_without_stdin() {
+        let bridge = IPCBridge::new();
+        assert!(bridge.send_raw("  \n").is_err());
+
+        bridge.send_raw(r#"{"msg_type":"event","event":"replayed","payload":{}}"#).unwrap();
+        assert_eq!(bridge.queue_size(), 1);
+
+        let written = Arc::new(Mutex::new(Vec::new()));
+        bridge.set_writer(FailingWriter { remaining: usize::MAX, written: written.clone() });
+        bridge.send_raw("{\"proxied\":true}\n").unwrap();
+
+        let output = String::from_utf8(written.lock().unwrap().clone()).unwrap();
+        assert_eq!(output, "{\"msg_type\":\"event\",\"event\":\"replayed\",\"payload\":{}}\n{\"proxied\":true}\n");
+    }
+
+    #[test]
+    fn test_send_raw_takes_no_sequence_number() {
+        let bridge = IPCBridge::new();
+        bridge.emit("a", serde_json::json!({})).unwrap();
+        bridge.send_raw("{\"proxied\":true}").unwrap();
+        bridge.emit("b", serde_json::json!({})).unwrap();
+
+        assert_eq!(bridge.queue_size(), 3);
+        let seqs: Vec<Option<u64>> = bridge.queue_peek().iter().map(|m| m.seq).collect();
+        assert_eq!(seqs, vec![Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn test_frames_unwritten_by_writer_thread_are_requeued() {
+        let bridge = IPCBridge::new();
+        let failed = Arc::new(Mutex::new(Vec::new()));
+        bridge.set_writer(bridge.threaded_writer(FailingWriter { remaining: 0, written: failed }));
+        bridge.emit("a", serde_json::json!({})).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while bridge.queue_size() == 0 {
+            assert!(Instant::now() < deadline, "unwritten frame should be re-queued");
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let written = Arc::new(Mutex::new(Vec::new()));
+        bridge.set_writer(FailingWriter { remaining: usize::MAX, written: written.clone() });
+        assert_eq!(written_messages(&written), vec![("a".to_string(), Some(1))]);
+    }
+
+    #[test]
+    fn test_send_stays_behind_messages_left_by_failed_flush() {
+        let bridge = IPCBridge::new();
+        for event in ["a", "b"] {
+            bridge.emit(event, serde_json::json!({})).unwrap();
+        }
+
+        // set_writer flushes "a", then fails on "b"; "c" must not overtake it
+        let failed = Arc::new(Mutex::new(Vec::new()));
+        bridge.set_writer(FailingWriter { remaining: 1, written: failed.clone() });
+        assert!(bridge.emit("c", serde_json::json!({})).is_err());
+        assert_eq!(queued_events(&bridge), vec!["b", "c"]);
+
+        let written = Arc::new(Mutex::new(Vec::new()));
+        bridge.set_writer(FailingWriter { remaining: usize::MAX, written: written.clone() });
+        let events: Vec<String> = written_messages(&written).into_iter().map(|(event, _)| event).collect();
+        assert_eq!(events, vec!["b", "c"]);
+        assert_eq!(written_messages(&failed), vec![("a".to_string(), Some(1))]);
+    }
+
+    #[test]
+    fn test_queued_messages_flush_by_priority() {
+        let bridge = IPCBridge::new();
+        bridge.emit_with_priority("reindex", serde_json::json!({}), Priority::Low).unwrap();
+        bridge.emit("save", serde_json::json!({})).unwrap();
+        bridge
+            .request_with_priority("autocomplete", serde_json::json!({}), Priority::High, |_| {})
+            .unwrap();
+        bridge.emit_with_priority("reindex_more", serde_json::json!({}), Priority::Low).unwrap();
+        bridge.emit_with_priority("hover", serde_json::json!({}), Priority::High).unwrap();
+
+        let written = Arc::new(Mutex::new(Vec::new()));
+        bridge.set_writer(FailingWriter { remaining: usize::MAX, written: written.clone() });
+
+        let events: Vec<String> = written_messages(&written).into_iter().map(|(event, _)| event).collect();
+        assert_eq!(events, vec!["autocomplete", "hover", "save", "reindex", "reindex_more"]);
+    }
+
+    #[test]
+    fn test_backpressure_signals_queue_depth() {
+        let bridge = IPCBridge::new().with_backpressure(3, 1);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&events);
+        bridge.on_backpressure(move |event| seen.lock().unwrap().push(event));
+
+        for event in ["a", "b", "c", "d"] {
+            bridge.emit(event, serde_json::json!({})).unwrap();
+        }
+        assert_eq!(*events.lock().unwrap(), vec![BackpressureEvent::HighWater(3)]);
+
+        let written = Arc::new(Mutex::new(Vec::new()));
+        bridge.set_writer(FailingWriter { remaining: usize::MAX, written });
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![BackpressureEvent::HighWater(3), BackpressureEvent::LowWater(0)]
+        );
+    }
+
+    #[test]
+    fn test_schema_rejects_invalid_payload() {
+        let bridge = IPCBridge::new();
+        bridge.register_schema("open_file", serde_json::json!({
+            "type": "object",
+            "required": ["path"],
+            "properties": {"path": {"type": "string"}}
+        }));
+
+        let err = bridge.emit("open_file", serde_json::json!({"path": 42})).unwrap_err();
+        assert!(err.contains("Serialization error"));
+        assert!(err.contains("$.path"));
+        assert!(bridge.request("open_file", serde_json::json!({}), |_| {}).is_err());
+        assert_eq!(bridge.pending_request_count(), 0);
+
+        bridge.emit("open_file", serde_json::json!({"path": "/tmp/a"})).unwrap();
+        bridge.emit("other", serde_json::json!({"path": 42})).unwrap();
+        assert_eq!(bridge.queue_size(), 2);
+
+        assert!(bridge.unregister_schema("open_file"));
+        bridge.emit("open_file", serde_json::json!({"path": 42})).unwrap();
+    }
+
+    #[test]
+    fn test_schema_validation_can_be_disabled() {
+        let bridge = IPCBridge::new().with_schema_validation(false);
+        bridge.register_schema("open_file", serde_json::json!({"type": "object"}));
+
+        bridge.emit("open_file", serde_json::json!("not an object")).unwrap();
+        assert_eq!(bridge.queue_size(), 1);
+    }
+
+    fn queued_events(bridge: &IPCBridge) -> Vec<String> {
+        bridge.message_queue.lock().unwrap().iter().filter_map(Queued::message).map(|m| m.event.clone()).collect()
+    }
+
+    #[test]
+    fn test_queue_overflow_drop_oldest() {
+        let bridge = IPCBridge::new().with_queue_limit(2, QueueOverflowPolicy::DropOldest);
+
+        for event in ["a", "b", "c"] {
+            bridge.emit(event, serde_json::json!({})).unwrap();
+        }
+
+        assert_eq!(bridge.queue_size(), 2);
+        assert_eq!(queued_events(&bridge), vec!["b", "c"]);
+    }
+
+    #[test]
+    fn test_queue_overflow_drop_newest() {
+        let bridge = IPCBridge::new().with_queue_limit(2, QueueOverflowPolicy::DropNewest);
+
+        for event in ["a", "b", "c"] {
+            bridge.emit(event, serde_json::json!({})).unwrap();
+        }
+
+        assert_eq!(bridge.queue_size(), 2);
+        assert_eq!(queued_events(&bridge), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_queue_overflow_reject() {
+        let bridge = IPCBridge::new().with_queue_limit(2, QueueOverflowPolicy::Reject);
+
+        bridge.queue_message(IPCMessage::event("a", serde_json::json!({}))).unwrap();
+        bridge.queue_message(IPCMessage::event("b", serde_json::json!({}))).unwrap();
+
+        let result = bridge.queue_message(IPCMessage::event("c", serde_json::json!({})));
+        assert!(matches!(result, Err(IPCError::SendError(_))));
+        assert!(bridge.emit("d", serde_json::json!({})).is_err());
+        assert_eq!(queued_events(&bridge), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_cancel_request() {
+        let bridge = IPCBridge::new();
+
+        // Add a pending request manually (simulating request without stdin)
+        {
+            let mut requests = bridge.pending_requests.lock().unwrap();
+            requests.insert("test-req-001".to_string(), PendingRequest {
+                event: "test".to_string(),
+                callback: Box::new(|_| {}),
+                on_chunk: None,
+                created_at: Instant::now(),
+                deadline: Instant::now() + Duration::from_secs(30),
+            });
+        }
+
+        assert_eq!(bridge.pending_request_count(), 1);
+        assert!(bridge.cancel_request("test-req-001"));
+        assert_eq!(bridge.pending_request_count(), 0);
+        assert!(!bridge.cancel_request("nonexistent"));
+    }
+
+    #[derive(Default)]
+    struct RecordingObserver(Mutex<Vec<String>>);
+
+    impl IpcObserver for RecordingObserver {
+        fn on_send(&self, msg: &IPCMessage) {
+            self.0.lock().unwrap().push(format!("send {}", msg.event));
+        }
+
+        fn on_receive(&self, msg: &IPCMessage) {
+            self.0.lock().unwrap().push(format!("receive {}", msg.event));
+        }
+
+        fn on_timeout(&self, _id: &str, event: &str) {
+            self.0.lock().unwrap().push(format!("timeout {}", event));
+        }
+
+        fn on_parse_error(&self, raw: &[u8], _error: &str) {
+            self.0.lock().unwrap().push(format!("parse_error {}", String::from_utf8_lossy(raw)));
+        }
+    }
+
+    #[test]
+    fn test_observer_sees_message_lifecycle() {
+        let observer = Arc::new(RecordingObserver::default());
+        let bridge = IPCBridge::new().with_observer(observer.clone());
+
+        // Queued while disconnected, reported once actually written
+        bridge.emit("early", serde_json::json!({})).unwrap();
+        assert!(observer.0.lock().unwrap().is_empty());
+        bridge.connect_echo();
+        bridge.request_blocking("ping", serde_json::json!({}), Duration::from_secs(2)).unwrap();
+
+        bridge
+            .start_stdout_listener(std::io::Cursor::new(b"{not json}\n".to_vec()), |_| {})
+            .join()
+            .unwrap();
+
+        let silent = IPCBridge::new().with_observer(observer.clone());
+        silent.connect_loopback(|_| None);
+        assert!(silent.request_blocking("stuck", serde_json::json!({}), Duration::from_millis(50)).is_err());
+
+        assert_eq!(*observer.0.lock().unwrap(), vec![
+            "send early",
+            "send ping",
+            "receive ping",
+            "parse_error {not json}",
+            "send stuck",
+            "timeout stuck",
+        ]);
+    }
+
+    #[test]
+    fn test_invalid_utf8_line_does_not_stop_listener() {
+        let mut stream = encode_message_for_stdin(&IPCMessage::event("before", serde_json::json!(1))).unwrap().into_bytes();
+        stream.extend_from_slice(b"binary log \xff\xfe\n");
+        stream.extend_from_slice(encode_message_for_stdin(&IPCMessage::event("after", serde_json::json!(2))).unwrap().as_bytes());
+
+        let bridge = IPCBridge::new();
+        let (tx, rx) = mpsc::channel();
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let raw_lines = Arc::clone(&lines);
+        bridge
+            .start_stdout_listener_with_raw(
+                std::io::Cursor::new(stream.clone()),
+                move |msg| {
+                    let _ = tx.send(msg.event);
+                },
+                move |line| raw_lines.lock().unwrap().push(line),
+            )
+            .join()
+            .unwrap();
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["before", "after"]);
+        assert!(lines.lock().unwrap().contains(&"binary log \u{fffd}\u{fffd}".to_string()));
+
+        // Strict mode treats the line as corruption and stops reading
+        let bridge = IPCBridge::builder().strict_utf8(true).build();
+        let (tx, rx) = mpsc::channel();
+        let (end_tx, end_rx) = mpsc::channel();
+        bridge.on_listener_ended(move |reason| {
+            let _ = end_tx.send(reason);
+        });
+        bridge
+            .start_stdout_listener(std::io::Cursor::new(stream), move |msg| {
+                let _ = tx.send(msg.event);
+            })
+            .join()
+            .unwrap();
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["before"]);
+        assert!(matches!(end_rx.try_recv().unwrap(), ListenerEndReason::Error(_)));
+    }
+
+    #[test]
+    fn test_wait_for_event() {
+        let bridge = Arc::new(IPCBridge::new());
+        let waiter = Arc::clone(&bridge);
+        let handle = thread::spawn(move || waiter.wait_for_event("indexed", Duration::from_secs(2)));
+
+        // Feed the event once the waiter's handler is registered
+        while !bridge.event_handlers.lock().unwrap().contains_key("indexed") {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let line = encode_message_for_stdin(&IPCMessage::event("indexed", serde_json::json!({"files": 3}))).unwrap();
+        bridge.start_stdout_listener(std::io::Cursor::new(line.into_bytes()), |_| {}).join().unwrap();
+        assert_eq!(handle.join().unwrap().unwrap(), serde_json::json!({"files": 3}));
+
+        let err = bridge.wait_for_event("never", Duration::from_millis(50)).unwrap_err();
+        assert!(err.contains("never"));
+        assert!(!bridge.event_handlers.lock().unwrap().contains_key("never"), "handler removed on timeout");
+    }
+
+    #[test]
+    fn test_activity_reported_for_sent_and_received_messages() {
+        let bridge = IPCBridge::new();
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&count);
+        bridge.on_activity(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        bridge.connect_echo();
+
+        bridge.request_blocking("ping", serde_json::json!({}), Duration::from_secs(2)).unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 2, "one send and one receive");
+
+        // Detached, messages are queued and still count as activity
+        bridge.clear_writer();
+        bridge.emit("later", serde_json::json!({})).unwrap();
+        assert_eq!(bridge.queue_size(), 1);
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_duplicate_request_id_is_rejected() {
+        let bridge = IPCBridge::new();
+        let (tx, rx) = mpsc::channel();
+        bridge.pending_requests.lock().unwrap().insert("dup-1".to_string(), PendingRequest {
+            event: "first".to_string(),
+            callback: Box::new(move |result| {
+                let _ = tx.send(result);
+            }),
+            on_chunk: None,
+            created_at: Instant::now(),
+            deadline: Instant::now() + Duration::from_secs(30),
+        });
+
+        let duplicate = WaitingRequest {
+            sender: bridge.sender(),
+            id: "dup-1".to_string(),
+            event: "second".to_string(),
+            payload: serde_json::json!({}),
+            expiry: Expiry::After(Duration::from_secs(30)),
+            callback: Box::new(|_| {}),
+            on_chunk: None,
+        };
+        match duplicate.dispatch() {
+            Err((IPCError::Other(msg), Some(_))) => assert_eq!(msg, "duplicate request id"),
+            other => panic!("expected a duplicate id error, got {:?}", other.map_err(|(e, _)| e)),
+        }
+
+        // The original request is still pending and still reachable
+        assert_eq!(bridge.pending_requests.lock().unwrap()["dup-1"].event, "first");
+        assert!(bridge.cancel_request("dup-1"));
+        assert!(matches!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), Err(IPCError::Cancelled(_))));
+    }
+
+    /// Minimal executor for driving a future to completion in tests
+    fn block_on<F: Future>(future: F) -> F::Output {
+        use std::task::Wake;
+
+        struct ThreadWaker(thread::Thread);
+
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::park_timeout(Duration::from_millis(50));
+        }
+    }
+
+    #[test]
+    fn test_request_async_resolves_on_response() {
+        let bridge = IPCBridge::new();
+        let future = bridge.request_async("get_data", serde_json::json!({}));
+        let id = future.id().unwrap().to_string();
+
+        // Simulate the stdout listener matching the response
+        let pending = bridge.pending_requests.lock().unwrap().remove(&id).unwrap();
+        (pending.callback)(Ok(serde_json::json!({"result": 42})));
+
+        let result = block_on(future);
+        assert_eq!(result.unwrap()["result"], 42);
+    }
+
+    #[test]
+    fn test_request_async_drop_removes_pending() {
+        let bridge = IPCBridge::new();
+        let future = bridge.request_async("get_data", serde_json::json!({}));
+        assert_eq!(bridge.pending_request_count(), 1);
+
+        drop(future);
+        assert_eq!(bridge.pending_request_count(), 0);
+    }
+
+    #[test]
+    fn test_request_async_times_out() {
+        let bridge = IPCBridge::with_timeout(0);
+        bridge.start_timeout_checker();
+
+        let result = block_on(bridge.request_async("slow", serde_json::json!({})));
+        assert!(matches!(result, Err(IPCError::Timeout(_))));
+        assert_eq!(bridge.pending_request_count(), 0);
+    }
+
+    #[test]
+    fn test_request_blocking_returns_response() {
+        let bridge = IPCBridge::new();
+        let pending_requests = Arc::clone(&bridge.pending_requests);
+
+        // Simulate Node.js answering from another thread
+        let responder = thread::spawn(move || loop {
+            let pending = {
+                let mut requests = pending_requests.lock().unwrap();
+                let id = requests.keys().next().cloned();
+                id.and_then(|id| requests.remove(&id))
+            };
+            if let Some(pending) = pending {
+                (pending.callback)(Ok(serde_json::json!({"ok": true})));
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        });
+
+        let result = bridge.request_blocking("get_data", serde_json::json!({}), Duration::from_secs(5));
+        responder.join().unwrap();
+        assert_eq!(result.unwrap()["ok"], true);
+    }
+
+    #[test]
+    fn test_request_blocking_timeout_removes_pending() {
+        let bridge = IPCBridge::new();
+        let result = bridge.request_blocking("get_data", serde_json::json!({}), Duration::from_millis(50));
+
+        assert!(matches!(result, Err(IPCError::Timeout(_))));
+        assert_eq!(bridge.pending_request_count(), 0);
+    }
+
+    #[test]
+    fn test_timeout_checker_fires_close_to_deadline() {
+        let bridge = IPCBridge::new();
+        let (tx, rx) = mpsc::channel();
+        let sent_at = Instant::now();
+        bridge
+            .request_with_deadline("slow", serde_json::json!({}), sent_at + Duration::from_millis(200), move |result| {
+                let _ = tx.send((Instant::now(), result));
+            })
+            .unwrap();
+        bridge.start_timeout_checker_with_interval(Duration::from_millis(20));
+
+        let (fired_at, result) = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert!(matches!(result, Err(IPCError::Timeout(_))));
+        let elapsed = fired_at - sent_at;
+        assert!(elapsed >= Duration::from_millis(200), "fired early: {:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(350), "fired late: {:?}", elapsed);
+    }
+
+    #[test]
+    fn test_circuit_breaker_fails_fast_then_recovers() {
+        let bridge = IPCBridge::new().with_circuit_breaker(CircuitBreakerConfig {
+            failure_threshold: 2,
+            window: Duration::from_secs(10),
+            cool_down: Duration::from_millis(200),
+        });
+        let backend = mock::MockBackend::new().with_dropped_requests(2);
+        backend.connect(&bridge);
+
+        for _ in 0..2 {
+            let result = bridge.request_blocking("status", serde_json::json!({}), Duration::from_millis(50));
+            assert!(matches!(result, Err(IPCError::Timeout(_))));
+        }
+        assert_eq!(bridge.circuit_state(), CircuitState::Open);
+
+        let started = Instant::now();
+        let result = bridge.request_blocking("status", serde_json::json!({}), Duration::from_secs(5));
+        assert!(matches!(result, Err(IPCError::Other(ref e)) if e == "circuit open"));
+        assert!(started.elapsed() < Duration::from_millis(100));
+        assert_eq!(backend.request_count("status"), 2);
+
+        thread::sleep(Duration::from_millis(250));
+        assert_eq!(bridge.circuit_state(), CircuitState::HalfOpen);
+        assert!(bridge.request_blocking("status", serde_json::json!({}), Duration::from_secs(1)).is_ok());
+        assert_eq!(bridge.circuit_state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_shutdown_stops_timeout_checker() {
+        let bridge = IPCBridge::new();
+        let handle = bridge.start_timeout_checker();
+
+        bridge.shutdown();
+        assert!(bridge.is_shutdown());
+
+        let deadline = Instant::now() + Duration::from_secs(3);
+        while !handle.is_finished() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(50));
+        }
+        assert!(handle.is_finished());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_shutdown_stops_stdout_listener() {
+        let input = "{\"id\":null,\"msg_type\":\"event\",\"event\":\"a\",\"payload\":{},\"error\":null}\n";
+        let listen = |bridge: &IPCBridge| {
+            let (tx, rx) = mpsc::channel();
+            let handle = bridge.start_stdout_listener(std::io::Cursor::new(input), move |msg| {
+                let _ = tx.send(msg);
+            });
+            handle.join().unwrap();
+            rx.try_recv().ok()
+        };
+
+        let running = IPCBridge::new();
+        assert_eq!(listen(&running).map(|msg| msg.event), Some("a".to_string()));
+
+        let stopped = IPCBridge::new();
+        stopped.shutdown();
+        assert!(listen(&stopped).is_none());
+    }
+
+    /// Reader that yields its data, then fails like a broken pipe
+    struct BrokenReader {
+        data: std::io::Cursor<Vec<u8>>,
+    }
+
+    impl Read for BrokenReader {
+        fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+            match self.data.read(out)? {
+                0 => Err(std::io::Error::new(ErrorKind::BrokenPipe, "broken pipe")),
+                n => Ok(n),
+            }
+        }
+    }
+
+    #[test]
+    fn test_request_coalesced_shares_one_request() {
+        let bridge = IPCBridge::new();
+        let (tx, rx) = mpsc::channel();
+
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            let tx = tx.clone();
+            ids.push(
+                bridge
+                    .request_coalesced("refresh_file_tree", serde_json::json!({"root": "/src"}), move |result| {
+                        let _ = tx.send(result);
+                    })
+                    .unwrap(),
+            );
+        }
+        let other = bridge
+            .request_coalesced("refresh_file_tree", serde_json::json!({"root": "/docs"}), |_| {})
+            .unwrap();
+
+        assert!(ids.iter().all(|id| *id == ids[0]));
+        assert_ne!(other, ids[0]);
+        assert_eq!(bridge.queue_size(), 2);
+        assert_eq!(bridge.pending_request_count(), 2);
+
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&requests);
+        bridge.connect_loopback(move |msg| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Some(IPCMessage::response(msg.id.as_ref().unwrap(), &msg.event, msg.payload.clone()))
+        });
+
+        for _ in 0..3 {
+            let result = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+            assert_eq!(result.unwrap()["root"], "/src");
+        }
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        assert!(bridge.coalesced.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_pending_requests_info() {
+        let bridge = IPCBridge::new();
+        let first = bridge.request_with_timeout("load", serde_json::json!({}), 5, |_| {}).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        let second = bridge.request_with_timeout("save", serde_json::json!({}), 9, |_| {}).unwrap();
+
+        let info = bridge.pending_requests_info();
+        assert_eq!(info.len(), 2);
+        assert_eq!(info[0].id, first);
+        assert_eq!(info[0].event, "load");
+        assert_eq!(info[0].timeout, Duration::from_secs(5));
+        assert!(info[0].age >= Duration::from_millis(20));
+        assert_eq!(info[1].id, second);
+        assert_eq!(info[1].event, "save");
+        assert!(info[1].age < info[0].age);
+    }
+
+    #[test]
+    fn test_handle_frontend_invoke_round_trip() {
+        let bridge = IPCBridge::new();
+        bridge.connect_loopback(|msg| {
+            let id = msg.id.as_ref().unwrap();
+            Some(match msg.event.as_str() {
+                "read_file" => IPCMessage::response(id, &msg.event, serde_json::json!({"content": msg.payload["path"]})),
+                _ => IPCMessage::error_response(id, &msg.event, "unknown command"),
+            })
+        });
+
+        let result = bridge.handle_frontend_invoke("read_file", serde_json::json!({"path": "a.ts"}));
+        assert_eq!(result.unwrap(), serde_json::json!({"content": "a.ts"}));
+
+        let err = bridge.handle_frontend_invoke("delete_all", serde_json::json!({})).unwrap_err();
+        assert!(err.contains("unknown command"));
+    }
+
+    #[test]
+    fn test_requests_share_absolute_deadline() {
+        let bridge = IPCBridge::new();
+        let deadline = Instant::now() + Duration::from_millis(300);
+        let (tx, rx) = mpsc::channel();
+        for event in ["step_one", "step_two"] {
+            let tx = tx.clone();
+            bridge
+                .request_with_deadline(event, serde_json::json!({}), deadline, move |result| {
+                    let _ = tx.send((event, result));
+                })
+                .unwrap();
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        // The later request only gets what is left of the budget
+        let info = bridge.pending_requests_info();
+        assert!(info[1].timeout < info[0].timeout);
+        assert!(info[0].timeout <= Duration::from_millis(300));
+
+        bridge.start_timeout_checker();
+        for _ in 0..2 {
+            let (_, result) = rx.recv_timeout(Duration::from_secs(3)).unwrap();
+            assert!(matches!(result, Err(IPCError::Timeout(_))));
+        }
+        assert!(Instant::now() >= deadline);
+
+        let err = bridge.request_with_deadline("late", serde_json::json!({}), deadline, |_| {}).unwrap_err();
+        assert!(err.contains("already passed"));
+        assert_eq!(bridge.pending_request_count(), 0);
+    }
+
+    #[test]
+    fn test_cancel_request_invokes_callback() {
+        let bridge = IPCBridge::new();
+        let (tx, rx) = mpsc::channel();
+        let id = bridge
+            .request("slow", serde_json::json!({}), move |result| {
+                let _ = tx.send(result);
+            })
+            .unwrap();
+
+        assert!(bridge.cancel_request(&id));
+        let result = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert!(matches!(result, Err(IPCError::Cancelled(_))));
+        assert_eq!(bridge.pending_request_count(), 0);
+        assert!(!bridge.cancel_request(&id));
+    }
+
+    #[test]
+    fn test_cancel_request_notifies_backend() {
+        let bridge = IPCBridge::new();
+        let backend = mock::MockBackend::new().with_dropped_requests(1);
+        backend.connect(&bridge);
+
+        let id = bridge.request("scan", serde_json::json!({}), |_| {}).unwrap();
+        assert!(bridge.cancel_request(&id));
+
+        let received = backend.received();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[1].event, CANCEL_EVENT);
+        assert_eq!(received[1].payload, serde_json::json!({ "id": id }));
+    }
+
+    #[test]
+    fn test_cancel_queued_request_is_not_sent() {
+        let bridge = IPCBridge::new().with_ready_gate();
+        let written = Arc::new(Mutex::new(Vec::new()));
+        bridge.set_writer(FailingWriter { remaining: usize::MAX, written: written.clone() });
+
+        let id = bridge.request("scan", serde_json::json!({}), |_| {}).unwrap();
+        assert_eq!(bridge.queue_size(), 1);
+        assert!(bridge.cancel_request(&id));
+        assert_eq!(bridge.queue_size(), 0);
+
+        bridge.set_ready();
+        assert!(written.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cancel_request_resolves_future() {
+        let bridge = IPCBridge::new();
+        let future = bridge.request_async("slow", serde_json::json!({}));
+        let id = future.id().unwrap().to_string();
+
+        assert!(bridge.cancel_request(&id));
+        assert!(matches!(block_on(future), Err(IPCError::Cancelled(_))));
+    }
+
+    #[test]
+    fn test_drain_waits_for_responses_then_cancels() {
+        let bridge = IPCBridge::new();
+        let (tx, rx) = mpsc::channel();
+        bridge.connect_loopback(|msg| match msg.event.as_str() {
+            "fast" => Some(IPCMessage::response(msg.id.as_ref().unwrap(), &msg.event, serde_json::json!(1))),
+            _ => None,
+        });
+
+        for event in ["fast", "stuck"] {
+            let tx = tx.clone();
+            bridge
+                .request(event, serde_json::json!({}), move |result| {
+                    let _ = tx.send((event, result));
+                })
+                .unwrap();
+        }
+
+        assert_eq!(bridge.drain(Duration::from_millis(200)), 1);
+        let mut results: Vec<_> = rx.try_iter().collect();
+        results.sort_by_key(|(event, _)| *event);
+        assert!(matches!(results[0], ("fast", Ok(_))));
+        assert!(matches!(results[1], ("stuck", Err(IPCError::ShuttingDown))));
+        assert_eq!(bridge.pending_request_count(), 0);
+
+        assert!(bridge.request("late", serde_json::json!({}), |_| {}).is_err());
+        assert!(bridge.emit("goodbye", serde_json::json!({})).is_ok());
+    }
+
+    #[test]
+    fn test_orphan_response_bypasses_event_handlers() {
+        let bridge = IPCBridge::new();
+        let (orphan_tx, orphan_rx) = mpsc::channel();
+        bridge.on_orphan_response(move |msg| {
+            let _ = orphan_tx.send(msg);
+        });
+        let handled = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&handled);
+        bridge.on("status", move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let input = concat!(
+            r#"{"id":"req-late","msg_type":"response","event":"status","payload":{"ok":true},"error":null}"#, "\n",
+            r#"{"id":null,"msg_type":"event","event":"status","payload":{},"error":null}"#, "\n",
+        );
+        let (tx, rx) = mpsc::channel();
+        bridge
+            .start_stdout_listener(std::io::Cursor::new(input), move |msg| {
+                let _ = tx.send(msg.msg_type);
+            })
+            .join()
+            .unwrap();
+
+        let orphan = orphan_rx.try_recv().unwrap();
+        assert_eq!(orphan.id.as_deref(), Some("req-late"));
+        assert_eq!(orphan.payload["ok"], true);
+        assert_eq!(handled.load(Ordering::SeqCst), 1);
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![IPCMessageType::Event]);
+    }
+
+    #[test]
+    fn test_small_read_buffer_reassembles_messages() {
+        let input = concat!(
+            r#"{"id":null,"msg_type":"event","event":"chunk","payload":{"text":"streamed output"},"error":null}"#, "\n",
+            "plain log line\n",
+            r#"{"id":null,"msg_type":"event","event":"done","payload":{},"error":null}"#, "\n",
+        );
+
+        for mode in [FramingMode::Newline, FramingMode::JsonStream] {
+            let bridge = IPCBridge::new().with_framing_mode(mode).with_read_buffer_size(7);
+            let (tx, rx) = mpsc::channel();
+            bridge
+                .start_stdout_listener(std::io::Cursor::new(input), move |msg| {
+                    let _ = tx.send(msg.event);
+                })
+                .join()
+                .unwrap();
+            assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["chunk", "done"], "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn test_listener_reports_end_reason() {
+        let bridge = IPCBridge::new();
+        let (tx, rx) = mpsc::channel();
+        bridge.on_listener_ended(move |reason| {
+            let _ = tx.send(reason);
+        });
+
+        bridge.start_stdout_listener(std::io::Cursor::new(Vec::new()), |_| {}).join().unwrap();
+        let broken = BrokenReader { data: std::io::Cursor::new(Vec::new()) };
+        bridge.start_stdout_listener(broken, |_| {}).join().unwrap();
+
+        assert_eq!(rx.try_recv().unwrap(), ListenerEndReason::Eof);
+        assert!(matches!(rx.try_recv().unwrap(), ListenerEndReason::Error(e) if e.contains("broken pipe")));
+    }
+
+    #[test]
+    fn test_listener_reconnects_after_error() {
+        let bridge = IPCBridge::new();
+        let (ended_tx, ended_rx) = mpsc::channel();
+        bridge.on_listener_ended(move |reason| {
+            let _ = ended_tx.send(reason);
+        });
+
+        let line = |event: &str| format!("{{\"id\":null,\"msg_type\":\"event\",\"event\":\"{}\",\"payload\":{{}},\"error\":null}}\n", event);
+        let first = BrokenReader { data: std::io::Cursor::new(line("before").into_bytes()) };
+        let mut next = vec![BrokenReader { data: std::io::Cursor::new(line("after").into_bytes()) }];
+
+        let (tx, rx) = mpsc::channel();
+        bridge
+            .start_stdout_listener_with_reconnect(first, move |msg| {
+                let _ = tx.send(msg.event);
+            }, move || next.pop())
+            .join()
+            .unwrap();
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["before", "after"]);
+        assert_eq!(ended_rx.try_iter().count(), 2);
+    }
+
+    #[test]
+    fn test_handshake_negotiates_version() {
+        let bridge = IPCBridge::new();
+        let (tx, rx) = mpsc::channel();
+        bridge.connect_loopback(move |msg| {
+            let _ = tx.send(msg.clone());
+            let id = msg.id.clone()?;
+            match msg.event.as_str() {
+                HELLO_EVENT => Some(IPCMessage::response(&id, &msg.event, serde_json::json!({"version": 1}))),
+                _ => None,
+            }
+        });
+
+        assert_eq!(bridge.negotiated_version(), None);
+        assert_eq!(bridge.handshake(Duration::from_secs(2)).unwrap(), 1);
+        assert_eq!(bridge.negotiated_version(), Some(1));
+
+        let hello = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(hello.payload["version"], PROTOCOL_VERSION);
+        assert_eq!(hello.version, None);
+
+        bridge.emit("ready", serde_json::json!({})).unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap().version, Some(1));
+    }
+
+    #[test]
+    fn test_handshake_rejects_unsupported_version() {
+        let bridge = IPCBridge::new();
+        bridge.connect_loopback(|msg| {
+            Some(IPCMessage::response(msg.id.as_ref().unwrap(), &msg.event, serde_json::json!({"version": 99})))
+        });
+
+        assert!(matches!(bridge.handshake(Duration::from_secs(2)), Err(IPCError::UnsupportedVersion(99))));
+        assert_eq!(bridge.negotiated_version(), None);
+    }
+
+    #[test]
+    fn test_listener_rejects_unsupported_message_version() {
+        let bridge = IPCBridge::new();
+        let (tx, rx) = mpsc::channel();
+        bridge.connect_loopback(|msg| {
+            let mut response = IPCMessage::response(msg.id.as_ref().unwrap(), &msg.event, serde_json::json!({}));
+            response.version = Some(PROTOCOL_VERSION + 1);
+            Some(response)
+        });
+
+        bridge
+            .request("status", serde_json::json!({}), move |result| {
+                let _ = tx.send(result);
+            })
+            .unwrap();
+        let result = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert!(matches!(result, Err(IPCError::UnsupportedVersion(v)) if v == PROTOCOL_VERSION + 1));
+    }
+
+    #[test]
+    fn test_ready_gate_holds_queue_until_set_ready() {
+        let bridge = IPCBridge::new().with_ready_gate();
+        let (tx, rx) = mpsc::channel();
+        bridge.connect_loopback(move |msg| {
+            let _ = tx.send(msg.event);
+            None
+        });
+
+        bridge.emit("first", serde_json::json!({})).unwrap();
+        bridge.emit("second", serde_json::json!({})).unwrap();
+        assert!(!bridge.is_ready());
+        assert_eq!(bridge.queue_size(), 2);
+        assert!(rx.try_recv().is_err());
+        assert!(matches!(bridge.wait_until_ready(Duration::from_millis(20)), Err(IPCError::Timeout(_))));
+
+        bridge.set_ready();
+        assert_eq!(bridge.queue_size(), 0);
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["first", "second"]);
+        assert!(bridge.wait_until_ready(Duration::from_millis(20)).is_ok());
+    }
+
+    #[test]
+    fn test_ready_event_opens_gate() {
+        let bridge = IPCBridge::new().with_ready_gate();
+        let (tx, rx) = mpsc::channel();
+        bridge.connect_loopback(move |msg| {
+            let _ = tx.send(msg.event);
+            None
+        });
+        bridge.emit("early", serde_json::json!({})).unwrap();
+
+        let ready = encode_message_for_stdin(&IPCMessage::event(BACKEND_READY_EVENT, serde_json::json!({}))).unwrap();
+        bridge.start_stdout_listener(std::io::Cursor::new(ready.into_bytes()), |_| {});
+
+        bridge.wait_until_ready(Duration::from_secs(2)).unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), "early");
+    }
+
+    #[test]
+    fn test_on_request_answers_node_requests() {
+        let bridge = IPCBridge::new();
+        bridge.on_request("add", |payload| {
+            let a = payload["a"].as_i64().ok_or("a is required")?;
+            Ok(serde_json::json!(a + payload["b"].as_i64().unwrap_or(0)))
+        });
+
+        let (tx, rx) = mpsc::channel();
+        let handled_as_event = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&handled_as_event);
+        bridge.on("add", move |_| flag.store(true, Ordering::SeqCst));
+        bridge.connect_loopback(move |msg| match msg.msg_type {
+            // Node.js turns each "ask" event into a request to Rust
+            IPCMessageType::Event => Some(IPCMessage::request("node_1", "add", msg.payload)),
+            IPCMessageType::Response => {
+                let _ = tx.send(msg);
+                None
+            }
+            _ => None,
+        });
+
+        bridge.emit("ask", serde_json::json!({"a": 2, "b": 3})).unwrap();
+        let reply = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(reply.id.as_deref(), Some("node_1"));
+        assert_eq!(reply.payload, serde_json::json!(5));
+        assert!(reply.error.is_none());
+
+        bridge.emit("ask", serde_json::json!({"b": 3})).unwrap();
+        let reply = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(reply.error.as_deref(), Some("a is required"));
+        assert!(!handled_as_event.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_max_concurrent_requests_holds_back_overflow() {
+        let bridge = IPCBridge::new();
+        bridge.set_max_concurrent_requests(2);
+        let (tx, rx) = mpsc::channel();
+        bridge.connect_loopback(move |msg| {
+            // Cancelling also sends Node.js an id-less `CANCEL_EVENT`
+            if let Some(id) = msg.id {
+                let _ = tx.send(id);
+            }
+            None
+        });
+
+        let ids: Vec<String> = (0..5)
+            .map(|i| bridge.request("thumbnail", serde_json::json!(i), |_| {}).unwrap())
+            .collect();
+        assert_eq!(bridge.pending_request_count(), 2);
+        assert_eq!(bridge.waiting_request_count(), 3);
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), ids[..2]);
+
+        // Completing one request lets exactly the next one through
+        assert!(bridge.cancel_request(&ids[0]));
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), ids[2]);
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+        assert_eq!(bridge.waiting_request_count(), 2);
+
+        // Held-back requests can be cancelled before they are sent
+        assert!(bridge.cancel_request(&ids[4]));
+        assert_eq!(bridge.waiting_request_count(), 1);
+
+        bridge.set_max_concurrent_requests(0);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), ids[3]);
+        assert_eq!(bridge.waiting_request_count(), 0);
+    }
+
+    #[test]
+    fn test_save_and_load_queue() {
+        let path = std::env::temp_dir().join(format!("ipc_queue_{}.jsonl", generate_request_id()));
+        let bridge = IPCBridge::new();
+        bridge.emit("autosave", serde_json::json!({"doc": 1})).unwrap();
+        bridge.request("status", serde_json::json!({}), |_| {}).unwrap();
+        bridge.emit("autosave", serde_json::json!({"doc": 2})).unwrap();
+        assert_eq!(bridge.save_queue(&path).unwrap(), 2);
+        assert_eq!(bridge.queue_size(), 3);
+
+        // A crash mid-write leaves a partial last line
+        let mut contents = std::fs::read_to_string(&path).unwrap();
+        contents.push_str("{\"id\":null,\"msg_type\":\"ev");
+        std::fs::write(&path, contents).unwrap();
+
+        let restarted = IPCBridge::new();
+        assert_eq!(restarted.load_queue(&path).unwrap(), 2);
+        assert_eq!(restarted.queue_size(), 2);
+
+        let (tx, rx) = mpsc::channel();
+        restarted.connect_loopback(move |msg| {
+            let _ = tx.send((msg.payload["doc"].clone(), msg.seq));
+            None
+        });
+        let delivered: Vec<_> = rx.try_iter().collect();
+        assert_eq!(delivered, vec![(serde_json::json!(1), Some(1)), (serde_json::json!(2), Some(2))]);
+
+        std::fs::remove_file(&path).ok();
+        assert!(restarted.load_queue(&path).is_err());
+    }
+
+    #[test]
+    fn test_loaded_queue_skips_outbound_middleware() {
+        let stamp = |msg: &mut IPCMessage| {
+            let hops = msg.payload["hops"].as_u64().unwrap_or(0);
+            msg.payload["hops"] = serde_json::json!(hops + 1);
+        };
+        let path = std::env::temp_dir().join(format!("ipc_queue_{}.jsonl", generate_request_id()));
+        let bridge = IPCBridge::new();
+        bridge.add_outbound_middleware(stamp);
+        bridge.emit("autosave", serde_json::json!({})).unwrap();
+        bridge.save_queue(&path).unwrap();
+
+        let restarted = IPCBridge::new();
+        restarted.add_outbound_middleware(stamp);
+        restarted.load_queue(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(restarted.queue_peek()[0].payload["hops"], serde_json::json!(1));
+    }
+
+    #[test]
+    fn test_middleware_may_use_the_bridge() {
+        let bridge = Arc::new(IPCBridge::new());
+        let inner = Arc::clone(&bridge);
+        bridge.add_outbound_middleware(move |msg| {
+            if msg.event == "outer" {
+                inner.add_outbound_middleware(|_| {});
+            }
+        });
+        bridge.emit("outer", serde_json::json!({})).unwrap();
+        assert_eq!(bridge.outbound_middleware.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_queue_peek_and_clear() {
+        let bridge = IPCBridge::new();
+        bridge.emit("config_changed", serde_json::json!({"v": 1})).unwrap();
+        bridge.emit_with_priority("urgent", serde_json::json!({}), Priority::High).unwrap();
+        let (tx, rx) = mpsc::channel();
+        bridge
+            .request("status", serde_json::json!({}), move |result| {
+                let _ = tx.send(result);
+            })
+            .unwrap();
+
+        let events: Vec<String> = bridge.queue_peek().into_iter().map(|msg| msg.event).collect();
+        assert_eq!(events, vec!["urgent", "config_changed", "status"]);
+        assert_eq!(bridge.queue_size(), 3);
+
+        assert_eq!(bridge.queue_clear(), 3);
+        assert_eq!(bridge.queue_size(), 0);
+        assert!(bridge.queue_peek().is_empty());
+        assert!(matches!(rx.try_recv().unwrap(), Err(IPCError::Cancelled(_))));
+        assert_eq!(bridge.pending_request_count(), 0);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_connect_over_unix_socket() {
+        use std::io::BufRead;
+        use std::os::unix::net::{UnixListener, UnixStream};
+
+        let path = std::env::temp_dir().join(format!("ipc_{}.sock", generate_request_id()));
+        let listener = UnixListener::bind(&path).unwrap();
+
+        // Stand-in for the backend: answers one request over the socket
+        let socket_path = path.clone();
+        let backend = thread::spawn(move || {
+            let mut stream = UnixStream::connect(socket_path).unwrap();
+            let mut line = String::new();
+            BufReader::new(stream.try_clone().unwrap()).read_line(&mut line).unwrap();
+            let request = parse_stdin_message(&line).unwrap();
+            let response = IPCMessage::response(request.id.as_deref().unwrap(), &request.event, serde_json::json!("pong"));
+            stream.write_all(encode_message_for_stdin(&response).unwrap().as_bytes()).unwrap();
+        });
+
+        let bridge = IPCBridge::new();
+        bridge.connect(UnixSocketTransport::accept(&listener).unwrap(), |_| {}).unwrap();
+        let result = bridge.request_blocking("ping", serde_json::json!({}), Duration::from_secs(2));
+        assert_eq!(result.unwrap(), serde_json::json!("pong"));
+
+        backend.join().unwrap();
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_backend_not_reading_does_not_block_senders() {
+        use std::os::unix::net::UnixStream;
+
+        // The peer never reads, so the socket buffer fills up
+        let (ours, _theirs) = UnixStream::pair().unwrap();
+        let bridge = IPCBridge::new().with_write_stall_timeout(Duration::from_millis(100));
+        let (tx, rx) = mpsc::channel();
+        bridge.on_write_stalled(move |stalled_for| {
+            let _ = tx.send(stalled_for);
+        });
+        bridge.connect(UnixSocketTransport::new(ours.try_clone().unwrap()), |_| {}).unwrap();
+
+        let started = Instant::now();
+        let chunk = "x".repeat(64 * 1024);
+        for _ in 0..64 {
+            bridge.emit("bulk", serde_json::json!(chunk)).unwrap();
+        }
+        assert!(started.elapsed() < Duration::from_secs(2), "emit must not wait for the backend");
+
+        let stalled_for = rx.recv_timeout(Duration::from_secs(2)).expect("stall should be reported");
+        assert!(stalled_for >= Duration::from_millis(100));
+        ours.shutdown(std::net::Shutdown::Both).ok();
+    }
+
+    #[test]
+    fn test_connect_over_stdio_transport() {
+        let bridge = IPCBridge::new();
+        let input = encode_message_for_stdin(&IPCMessage::event("hello", serde_json::json!({}))).unwrap();
+        let (tx, rx) = mpsc::channel();
+        let handle = bridge
+            .connect(StdioTransport::new(std::io::Cursor::new(input.into_bytes()), Vec::new()), move |msg| {
+                let _ = tx.send(msg.event);
+            })
+            .unwrap();
+        handle.join().unwrap();
+        assert_eq!(rx.try_recv().unwrap(), "hello");
+    }
+
+    #[test]
+    fn test_timeout_checker_survives_poisoned_lock_and_panicking_callback() {
+        let bridge = IPCBridge::new();
+        bridge.request_with_timeout("boom", serde_json::json!({}), 0, |_| panic!("callback failed")).unwrap();
+        let (tx, rx) = mpsc::channel();
+        bridge
+            .request_with_timeout("status", serde_json::json!({}), 0, move |result| {
+                let _ = tx.send(result);
+            })
+            .unwrap();
+
+        // Poison the pending map: a thread panics while holding its lock
+        let pending = Arc::clone(&bridge.pending_requests);
+        let _ = thread::spawn(move || {
+            let _guard = pending.lock().unwrap();
+            panic!("poisoning pending_requests");
+        })
+        .join();
+        assert!(bridge.pending_requests.is_poisoned());
+
+        let checker = bridge.start_timeout_checker();
+        let result = rx.recv_timeout(Duration::from_secs(3)).unwrap();
+        assert!(matches!(result, Err(IPCError::Timeout(_))));
+        assert!(!checker.is_finished(), "checker should keep running");
+        bridge.shutdown();
+    }
+
+    #[test]
+    fn test_request_stream_delivers_chunks_then_completes() {
+        let bridge = IPCBridge::new();
+        bridge.connect_loopback(|msg| {
+            let id = msg.id.unwrap();
+            // The loopback answers a write with at most one message: the first chunk
+            Some(match msg.event.as_str() {
+                "scan" => IPCMessage::chunk(&id, "scan", serde_json::json!("a.rs")),
+                _ => return None,
+            })
+        });
+
+        let (chunk_tx, chunk_rx) = mpsc::channel();
+        let (done_tx, done_rx) = mpsc::channel();
+        let id = bridge
+            .request_stream(
+                "scan",
+                serde_json::json!({}),
+                move |chunk| {
+                    let _ = chunk_tx.send(chunk);
+                },
+                move |result| {
+                    let _ = done_tx.send(result);
+                },
+            )
+            .unwrap();
+        assert_eq!(chunk_rx.recv_timeout(Duration::from_secs(2)).unwrap(), "a.rs");
+        assert_eq!(bridge.pending_request_count(), 1, "a chunk must not complete the request");
+
+        // The rest of the stream arrives on a second listener: another chunk,
+        // the final response, and a late chunk that must be dropped
+        let lines: String = [
+            IPCMessage::chunk(&id, "scan", serde_json::json!("b.rs")),
+            IPCMessage::response(&id, "scan", serde_json::json!({"files": 2})),
+            IPCMessage::chunk(&id, "scan", serde_json::json!("late.rs")),
+        ]
+        .iter()
+        .map(|msg| encode_message_for_stdin(msg).unwrap())
+        .collect();
+        bridge.start_stdout_listener(std::io::Cursor::new(lines.into_bytes()), |_| {}).join().unwrap();
+
+        assert_eq!(chunk_rx.try_iter().collect::<Vec<_>>(), vec![serde_json::json!("b.rs")]);
+        assert_eq!(done_rx.try_recv().unwrap().unwrap(), serde_json::json!({"files": 2}));
+        assert_eq!(bridge.pending_request_count(), 0);
+    }
+
+    struct GetFileTree;
+
+    impl Command for GetFileTree {
+        type Req = String;
+        type Res = Vec<String>;
+        const EVENT: &'static str = "get_file_tree";
+    }
+
+    #[test]
+    fn test_call_typed_command() {
+        let bridge = IPCBridge::new();
+        let (seen_tx, seen_rx) = mpsc::channel();
+        bridge.connect_loopback(move |msg| {
+            let _ = seen_tx.send((msg.event.clone(), msg.payload.clone()));
+            let files = match msg.payload.as_str() {
+                Some("/src") => serde_json::json!(["main.ts", "app.ts"]),
+                _ => serde_json::json!({"unexpected": true}),
+            };
+            Some(IPCMessage::response(msg.id.as_ref().unwrap(), &msg.event, files))
+        });
+
+        let (tx, rx) = mpsc::channel();
+        let ok_tx = tx.clone();
+        bridge
+            .call::<GetFileTree>("/src".to_string(), move |result| {
+                let _ = ok_tx.send(result);
+            })
+            .unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap().unwrap(), vec!["main.ts", "app.ts"]);
+        assert_eq!(seen_rx.recv().unwrap(), ("get_file_tree".to_string(), serde_json::json!("/src")));
+
+        bridge
+            .call::<GetFileTree>("/other".to_string(), move |result| {
+                let _ = tx.send(result);
+            })
+            .unwrap();
+        assert!(matches!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), Err(IPCError::ParseError(_))));
+    }
+
+    #[test]
+    fn test_request_reports_backend_error_code() {
+        let bridge = IPCBridge::new();
+        bridge.connect_loopback(|msg| {
+            let id = msg.id.clone().unwrap();
+            let mut response = IPCMessage::error_response(&id, &msg.event, "file not found");
+            response.payload = serde_json::json!({"code": "ENOENT"});
+            Some(response)
+        });
+
+        let result = bridge.request_blocking("read_file", serde_json::json!({}), Duration::from_secs(2));
+        match result {
+            Err(IPCError::BackendError { code, message }) => {
+                assert_eq!(code.as_deref(), Some("ENOENT"));
+                assert_eq!(message, "file not found");
+            }
+            other => panic!("expected a backend error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_error_retryable_and_string_conversion() {
+        assert!(IPCError::Timeout("1s".to_string()).is_retryable());
+        assert!(IPCError::StdinNotAvailable.is_retryable());
+        assert!(!IPCError::Cancelled("req_1".to_string()).is_retryable());
+
+        let err = IPCError::BackendError { code: Some("E42".to_string()), message: "bad input".to_string() };
+        assert!(!err.is_retryable());
+        assert_eq!(String::from(err), "Backend error [E42]: bad input");
+    }
+
+    #[test]
+    fn test_request_with_retry_succeeds_after_errors() {
+        let bridge = IPCBridge::new();
+        let ids = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&ids);
+        bridge.connect_loopback(move |msg| {
+            let id = msg.id.clone().unwrap();
+            let mut seen = seen.lock().unwrap();
+            seen.push(id.clone());
+            if seen.len() < 3 {
+                Some(IPCMessage::error_response(&id, &msg.event, "busy"))
+            } else {
+                Some(IPCMessage::response(&id, &msg.event, serde_json::json!({"ok": true})))
+            }
+        });
+
+        let (tx, rx) = mpsc::channel();
+        bridge
+            .request_with_retry("save", serde_json::json!({}), 3, Duration::from_millis(10), move |result| {
+                let _ = tx.send(result);
+            })
+            .unwrap();
+
+        let result = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(result.unwrap()["ok"], true);
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+        let ids = ids.lock().unwrap();
+        assert_eq!(ids.len(), 3);
+        assert_ne!(ids[0], ids[1]);
+        assert_ne!(ids[1], ids[2]);
+    }
+
+    #[test]
+    fn test_request_with_retry_reports_last_error() {
+        let bridge = IPCBridge::new();
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&attempts);
+        bridge.connect_loopback(move |msg| {
+            let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            Some(IPCMessage::error_response(msg.id.as_deref().unwrap(), &msg.event, &format!("busy {}", n)))
+        });
+
+        let (tx, rx) = mpsc::channel();
+        bridge
+            .request_with_retry("save", serde_json::json!({}), 2, Duration::from_millis(10), move |result| {
+                let _ = tx.send(result);
+            })
+            .unwrap();
+
+        let result = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert!(matches!(result, Err(IPCError::BackendError { message, .. }) if message == "busy 2"));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(bridge.pending_request_count(), 0);
+    }
+
+    #[test]
+    fn test_metrics_track_requests_and_responses() {
+        let bridge = IPCBridge::new();
+        bridge.connect_loopback(|msg| {
+            Some(IPCMessage::response(msg.id.as_deref().unwrap(), &msg.event, Value::Null))
+        });
+
+        for _ in 0..3 {
+            bridge
+                .request_blocking("ping", serde_json::json!({}), Duration::from_secs(1))
+                .unwrap();
+        }
+
+        let snapshot = bridge.metrics();
+        assert_eq!(snapshot.requests_sent, 3);
+        assert_eq!(snapshot.responses_received, 3);
+        assert_eq!(snapshot.timeouts, 0);
+        assert_eq!(snapshot.pending_requests, 0);
+    }
+
+    #[test]
+    fn test_metrics_count_timeouts_and_queue_depth() {
+        let bridge = IPCBridge::new();
+        bridge.emit("queued", serde_json::json!({})).unwrap();
+        bridge.request_with_timeout("slow", serde_json::json!({}), 0, |_| {}).unwrap();
+        bridge.start_timeout_checker();
+
+        let deadline = Instant::now() + Duration::from_secs(3);
+        while bridge.metrics().timeouts == 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        let snapshot = bridge.metrics();
+        assert_eq!(snapshot.requests_sent, 1);
+        assert_eq!(snapshot.timeouts, 1);
+        assert_eq!(snapshot.queue_depth, 2);
+        bridge.shutdown();
+    }
+
+    #[test]
+    fn test_heartbeat_detects_silent_backend() {
+        let bridge = IPCBridge::new().with_heartbeat_event("__hb__");
+        let pings = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&pings);
+        bridge.connect_loopback(move |msg| {
+            seen.lock().unwrap().push(msg.event);
+            None
+        });
+
+        let (tx, rx) = mpsc::channel();
+        bridge.start_heartbeat(Duration::from_millis(20), Duration::from_millis(50), move || {
+            let _ = tx.send(());
+        });
+
+        assert!(rx.recv_timeout(Duration::from_secs(1)).is_ok());
+        bridge.shutdown();
+        assert_eq!(pings.lock().unwrap()[0], "__hb__");
+        assert_eq!(bridge.pending_request_count(), 0);
     }
 
-    /// Queue a message for later sending
-    pub fn queue_message(&self, msg: IPCMessage) {
-        let mut queue = self.message_queue.lock().unwrap();
-        queue.push_back(msg);
-        debug!("Message queued, queue size: {}", queue.len());
+    #[test]
+    fn test_heartbeat_detects_hung_backend_with_timeout_checker() {
+        let bridge = IPCBridge::new();
+        bridge.connect_loopback(|_| None);
+        // The checker fails the ping locally, which must not count as a pong
+        bridge.start_timeout_checker_with_interval(Duration::from_millis(1));
+
+        let (tx, rx) = mpsc::channel();
+        bridge.start_heartbeat(Duration::from_millis(20), Duration::from_millis(50), move || {
+            let _ = tx.send(());
+        });
+
+        assert!(rx.recv_timeout(Duration::from_secs(1)).is_ok());
+        bridge.shutdown();
     }
 
-    /// Get the current message queue size
-    pub fn queue_size(&self) -> usize {
-        let queue = self.message_queue.lock().unwrap();
-        queue.len()
+    #[test]
+    fn test_heartbeat_timeout_drops_held_back_ping() {
+        let bridge = IPCBridgeBuilder::new().max_concurrent_requests(1).build();
+        bridge.connect_loopback(|_| None);
+        // Occupies the only slot, so the ping is held back by the limiter
+        bridge.request_with_timeout("slow", serde_json::json!({}), 60, |_| {}).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        bridge.start_heartbeat(Duration::from_millis(200), Duration::from_millis(50), move || {
+            let _ = tx.send(());
+        });
+
+        assert!(rx.recv_timeout(Duration::from_secs(1)).is_ok());
+        bridge.shutdown();
+        // Checked well before the next ping is due
+        assert!(bridge.limiter.waiting.lock().unwrap().is_empty());
     }
 
-    /// Cancel a pending request
-    pub fn cancel_request(&self, id: &str) -> bool {
-        let mut requests = self.pending_requests.lock().unwrap();
-        requests.remove(id).is_some()
+    #[test]
+    fn test_heartbeat_quiet_while_backend_responds() {
+        let bridge = IPCBridge::new();
+        bridge.connect_loopback(|msg| {
+            Some(IPCMessage::response(msg.id.as_deref().unwrap(), &msg.event, Value::Null))
+        });
+
+        let (tx, rx) = mpsc::channel();
+        bridge.start_heartbeat(Duration::from_millis(20), Duration::from_millis(200), move || {
+            let _ = tx.send(());
+        });
+
+        assert!(rx.recv_timeout(Duration::from_millis(300)).is_err());
+        bridge.shutdown();
+        assert!(bridge.metrics().responses_received > 0);
     }
 
-    /// Get the number of pending requests
-    pub fn pending_request_count(&self) -> usize {
-        let requests = self.pending_requests.lock().unwrap();
-        requests.len()
+    #[test]
+    fn test_try_request_fails_fast_without_stdin() {
+        let bridge = IPCBridge::new();
+
+        let result = bridge.try_request("load", serde_json::json!({}), |_| {});
+        assert!(matches!(result, Err(IPCError::StdinNotAvailable)));
+        assert_eq!(bridge.pending_request_count(), 0);
+        assert_eq!(bridge.queue_size(), 0);
     }
-}
 
-impl Default for IPCBridge {
-    fn default() -> Self {
-        Self::new()
+    #[test]
+    fn test_try_request_sends_with_stdin() {
+        let bridge = IPCBridge::new();
+        bridge.connect_loopback(|msg| {
+            Some(IPCMessage::response(msg.id.as_deref().unwrap(), &msg.event, serde_json::json!(7)))
+        });
+
+        let (tx, rx) = mpsc::channel();
+        bridge
+            .try_request("load", serde_json::json!({}), move |result| {
+                let _ = tx.send(result);
+            })
+            .unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap().unwrap(), serde_json::json!(7));
     }
-}
 
-/// Generate a unique request ID
-fn generate_request_id() -> String {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_nanos();
-    format!("req_{}", timestamp)
-}
+    #[test]
+    fn test_try_request_leaves_queue_alone() {
+        let bridge = IPCBridge::new();
+        bridge.emit("saved", serde_json::json!({})).unwrap();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let called = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&called);
+        let result = bridge.try_request("status", serde_json::json!({}), move |_| {
+            flag.store(true, Ordering::SeqCst);
+        });
+        assert!(matches!(result, Err(IPCError::StdinNotAvailable)));
+        assert_eq!(bridge.queue_size(), 1);
+        assert_eq!(bridge.pending_request_count(), 0);
+        assert!(!called.load(Ordering::SeqCst));
+    }
 
     #[test]
-    fn test_ipc_message_event_creation() {
-        let msg = IPCMessage::event("test_event", serde_json::json!({"key": "value"}));
-        assert_eq!(msg.event, "test_event");
-        assert!(matches!(msg.msg_type, IPCMessageType::Event));
-        assert!(msg.id.is_none());
+    fn test_backend_not_expected_fails_fast() {
+        let bridge = IPCBridge::new();
+        bridge.set_backend_expected(false);
+
+        let err = bridge.request("load", serde_json::json!({}), |_| {}).unwrap_err();
+        assert_eq!(err, IPCError::StdinNotAvailable.to_string());
+        assert!(bridge.emit("event", serde_json::json!({})).is_err());
+        assert_eq!(bridge.pending_request_count(), 0);
+        assert_eq!(bridge.queue_size(), 0);
+
+        bridge.set_backend_expected(true);
+        bridge.request("load", serde_json::json!({}), |_| {}).unwrap();
+        assert_eq!(bridge.queue_size(), 1);
     }
 
     #[test]
-    fn test_ipc_message_request_creation() {
-        let msg = IPCMessage::request("req-001", "get_data", serde_json::json!({}));
-        assert_eq!(msg.id, Some("req-001".to_string()));
-        assert!(matches!(msg.msg_type, IPCMessageType::Request));
+    fn test_emit_acked_resolves_on_ack() {
+        let bridge = IPCBridge::new();
+        bridge.connect_loopback(|msg| {
+            Some(IPCMessage::response(msg.id.as_deref().unwrap(), &msg.event, Value::Null))
+        });
+
+        let (tx, rx) = mpsc::channel();
+        bridge
+            .emit_acked("save", serde_json::json!({"doc": 1}), Duration::from_secs(5), move |result| {
+                let _ = tx.send(result);
+            })
+            .unwrap();
+
+        assert!(rx.recv_timeout(Duration::from_secs(1)).unwrap().is_ok());
+        assert_eq!(bridge.pending_request_count(), 0);
     }
 
     #[test]
-    fn test_ipc_message_response_creation() {
-        let msg = IPCMessage::response("req-001", "get_data", serde_json::json!({"result": 42}));
-        assert_eq!(msg.id, Some("req-001".to_string()));
-        assert!(matches!(msg.msg_type, IPCMessageType::Response));
+    fn test_emit_acked_stays_pending_while_queued() {
+        let bridge = IPCBridge::new();
+
+        bridge
+            .emit_acked("save", serde_json::json!({}), Duration::from_secs(5), |_| {})
+            .unwrap();
+
+        assert_eq!(bridge.queue_size(), 1);
+        assert_eq!(bridge.pending_request_count(), 1);
     }
 
     #[test]
-    fn test_ipc_message_error_response() {
-        let msg = IPCMessage::error_response("req-001", "get_data", "Something went wrong");
-        assert_eq!(msg.error, Some("Something went wrong".to_string()));
+    fn test_builder_configures_bridge() {
+        let bridge = IPCBridge::builder()
+            .timeout(7)
+            .wire_format(WireFormat::MessagePack)
+            .max_queue(2)
+            .overflow_policy(QueueOverflowPolicy::Reject)
+            .max_message_bytes(1024)
+            .read_buffer_size(0)
+            .build();
+
+        assert_eq!(bridge.default_timeout(), 7);
+        assert_eq!(bridge.wire_format, WireFormat::MessagePack);
+        assert_eq!(bridge.max_message_bytes, 1024);
+        assert_eq!(bridge.read_buffer_size, 1);
+
+        bridge.emit("a", serde_json::json!(1)).unwrap();
+        bridge.emit("b", serde_json::json!(2)).unwrap();
+        assert!(bridge.emit("c", serde_json::json!(3)).is_err());
+        assert_eq!(bridge.queue_size(), 2);
     }
 
     #[test]
-    fn test_parse_valid_message() {
-        let json = r#"{"id":"msg-001","msg_type":"event","event":"test","payload":{},"error":null}"#;
-        let result = parse_stdin_message(json);
-        assert!(result.is_ok());
-        let msg = result.unwrap();
-        assert_eq!(msg.event, "test");
+    fn test_set_default_timeout_applies_to_new_requests() {
+        let bridge = IPCBridge::with_timeout(5);
+        let slow = bridge.request("index", serde_json::json!({}), |_| {}).unwrap();
+
+        bridge.set_default_timeout(120);
+        assert_eq!(bridge.default_timeout(), 120);
+        let remote = bridge.request("index", serde_json::json!({}), |_| {}).unwrap();
+
+        let timeouts: HashMap<String, Duration> = bridge
+            .pending_requests_info()
+            .into_iter()
+            .map(|info| (info.id, info.timeout))
+            .collect();
+        assert_eq!(timeouts[&slow], Duration::from_secs(5));
+        assert_eq!(timeouts[&remote], Duration::from_secs(120));
     }
 
     #[test]
-    fn test_parse_invalid_message() {
-        let result = parse_stdin_message("not json");
-        assert!(result.is_err());
+    fn test_event_timeouts_override_default() {
+        let bridge = IPCBridge::with_timeout(30);
+        bridge.set_event_timeout("status", 2);
+        bridge.set_event_timeout("build", 300);
+
+        let status = bridge.request("status", serde_json::json!({}), |_| {}).unwrap();
+        let build = bridge.request_coalesced("build", serde_json::json!({}), |_| {}).unwrap();
+        let other = bridge.request("index", serde_json::json!({}), |_| {}).unwrap();
+        let explicit = bridge.request_with_timeout("status", serde_json::json!({}), 9, |_| {}).unwrap();
+
+        let timeouts: HashMap<String, Duration> = bridge
+            .pending_requests_info()
+            .into_iter()
+            .map(|info| (info.id, info.timeout))
+            .collect();
+        assert_eq!(timeouts[&status], Duration::from_secs(2));
+        assert_eq!(timeouts[&build], Duration::from_secs(300));
+        assert_eq!(timeouts[&other], Duration::from_secs(30));
+        assert_eq!(timeouts[&explicit], Duration::from_secs(9));
     }
 
     #[test]
-    fn test_encode_message() {
-        let msg = IPCMessage::event("test", serde_json::json!({}));
-        let encoded = encode_message_for_stdin(&msg);
-        assert!(encoded.is_ok());
-        assert!(encoded.unwrap().ends_with('\n'));
+    fn test_constructors_match_builder_defaults() {
+        let bridge = IPCBridge::with_timeout(3);
+        assert_eq!(bridge.default_timeout(), 3);
+        assert_eq!(bridge.max_queue_size, DEFAULT_MAX_QUEUE_SIZE);
+        assert_eq!(IPCBridge::new().default_timeout(), DEFAULT_REQUEST_TIMEOUT_SECS);
     }
 
     #[test]
-    fn test_forward_to_frontend() {
-        let msg = IPCMessage::event("display_message", serde_json::json!({"text": "Hello"}));
-        let (event_name, payload) = forward_to_frontend(&msg);
-        assert_eq!(event_name, "display_message");
-        assert_eq!(payload["text"], "Hello");
+    fn test_log_label_includes_request_id() {
+        assert_eq!(log_label(&IPCMessage::request("req_7", "load", serde_json::json!({}))), "load [req_7]");
+        assert_eq!(log_label(&IPCMessage::event("ready", serde_json::json!({}))), "ready");
     }
 
     #[test]
-    fn test_ipc_bridge_creation() {
-        let bridge = IPCBridge::new();
-        assert_eq!(bridge.pending_request_count(), 0);
-        assert_eq!(bridge.queue_size(), 0);
+    fn test_json_frame_decoder_split_object() {
+        let mut decoder = JsonFrameDecoder::new(DEFAULT_MAX_MESSAGE_BYTES);
+        assert!(decoder.push(br#"{"msg_type":"event","event":"a","#).is_empty());
+        let frames = decoder.push(br#""payload":{"text":"}{"},"error":null}"#);
+        assert_eq!(frames.len(), 1);
+
+        let StreamFrame::Json(frame) = &frames[0] else { panic!("expected a JSON frame") };
+        let msg = parse_stdin_message(frame).unwrap();
+        assert_eq!(msg.event, "a");
+        assert_eq!(msg.payload["text"], "}{");
     }
 
     #[test]
-    fn test_ipc_bridge_with_timeout() {
-        let bridge = IPCBridge::with_timeout(60);
-        assert_eq!(bridge.request_timeout_secs, 60);
+    fn test_json_frame_decoder_skips_log_lines() {
+        let mut decoder = JsonFrameDecoder::new(DEFAULT_MAX_MESSAGE_BYTES);
+        let input = b"Server listening {port: 3000}\n{\"msg_type\":\"event\",\n\"event\":\"b\",\"payload\":null,\"error\":null}\nplain log\n";
+        let frames = decoder.push(input);
+        assert_eq!(frames.len(), 3);
+        assert!(matches!(&frames[0], StreamFrame::Text(line) if line == "Server listening {port: 3000}"));
+        let StreamFrame::Json(frame) = &frames[1] else { panic!("expected a JSON frame") };
+        assert_eq!(parse_stdin_message(frame).unwrap().event, "b");
+        assert!(matches!(&frames[2], StreamFrame::Text(line) if line == "plain log"));
     }
 
     #[test]
-    fn test_generate_request_id() {
-        let id1 = generate_request_id();
-        let id2 = generate_request_id();
-        assert!(id1.starts_with("req_"));
-        assert_ne!(id1, id2);
+    fn test_json_frame_decoder_discards_oversized_object() {
+        let mut decoder = JsonFrameDecoder::new(64);
+        let big = format!("{{\"payload\":\"{}\"}}\n", "x".repeat(200));
+        let small = "{\"msg_type\":\"event\",\"event\":\"c\",\"payload\":null}\n";
+
+        let frames = decoder.push(format!("{}{}", big, small).as_bytes());
+        assert_eq!(frames.len(), 2);
+        assert!(matches!(frames[0], StreamFrame::Oversized(IPCError::ParseError(_))));
+        let StreamFrame::Json(frame) = &frames[1] else { panic!("expected a JSON frame") };
+        assert_eq!(parse_stdin_message(frame).unwrap().event, "c");
     }
 
     #[test]
-    fn test_ipc_error_display() {
-        let err = IPCError::StdinNotAvailable;
-        assert_eq!(err.to_string(), "Node.js stdin not available");
+    fn test_read_line_limited() {
+        let mut reader = BufReader::with_capacity(4, std::io::Cursor::new(b"short\nthis line is too long\nok".to_vec()));
 
-        let err = IPCError::Timeout("30s".to_string());
-        assert!(err.to_string().contains("timeout"));
+        assert_eq!(read_line_limited(&mut reader, 8).unwrap(), RawFrame::Complete(b"short".to_vec()));
+        assert_eq!(read_line_limited(&mut reader, 8).unwrap(), RawFrame::TooLong(21));
+        assert_eq!(read_line_limited(&mut reader, 8).unwrap(), RawFrame::Complete(b"ok".to_vec()));
+        assert_eq!(read_line_limited(&mut reader, 8).unwrap(), RawFrame::Eof);
+    }
 
-        let err = IPCError::ParseError("invalid json".to_string());
-        assert!(err.to_string().contains("Parse error"));
+    #[test]
+    fn test_emit_rejects_oversized_message() {
+        let bridge = IPCBridge::new().with_max_message_bytes(128);
+
+        let err = bridge.emit("dump", serde_json::json!({"data": "x".repeat(256)})).unwrap_err();
+        assert!(err.contains("Serialization error"));
+        assert_eq!(bridge.queue_size(), 0);
+
+        bridge.emit("small", serde_json::json!({})).unwrap();
+        assert_eq!(bridge.queue_size(), 1);
     }
 
     #[test]
-    fn test_ipc_error_to_string() {
-        let err: String = IPCError::SendError("connection refused".to_string()).into();
-        assert!(err.contains("Send error"));
+    fn test_stdout_listener_discards_oversized_line() {
+        let bridge = IPCBridge::new().with_max_message_bytes(128);
+        let big = IPCMessage::event("big", serde_json::json!({"data": "x".repeat(256)}));
+        let small = IPCMessage::event("small", serde_json::json!({}));
+        let input = format!(
+            "{}{}",
+            encode_message_for_stdin(&big).unwrap(),
+            encode_message_for_stdin(&small).unwrap()
+        );
+
+        let (tx, rx) = mpsc::channel();
+        let handle = bridge.start_stdout_listener(std::io::Cursor::new(input.into_bytes()), move |msg| {
+            let _ = tx.send(msg.event);
+        });
+
+        handle.join().unwrap();
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["small"]);
     }
 
     #[test]
-    fn test_message_queue() {
+    fn test_oversized_line_resyncs_with_single_error() {
+        // A small read buffer makes the giant line span many reads
+        let bridge = IPCBridge::builder().max_message_bytes(128).read_buffer_size(16).build();
+        let (err_tx, err_rx) = mpsc::channel();
+        bridge.on_stream_error(move |err| {
+            let _ = err_tx.send(err);
+        });
+
+        let mut input = format!("{{\"garbage\": \"{}\"}}\n", "y".repeat(10_000));
+        input.push_str(&encode_message_for_stdin(&IPCMessage::event("valid", serde_json::json!({}))).unwrap());
+        let (tx, rx) = mpsc::channel();
+        bridge
+            .start_stdout_listener(std::io::Cursor::new(input.into_bytes()), move |msg| {
+                let _ = tx.send(msg.event);
+            })
+            .join()
+            .unwrap();
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["valid"]);
+        let errors: Vec<_> = err_rx.try_iter().collect();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0], IPCError::ParseError(msg) if msg.contains("10015 bytes")));
+    }
+
+    #[test]
+    fn test_message_pack_listener_discards_oversized_frame() {
+        let bridge = IPCBridge::new()
+            .with_wire_format(WireFormat::MessagePack)
+            .with_max_message_bytes(128);
+        let big = IPCMessage::event("big", serde_json::json!({"data": "x".repeat(256)}));
+        let small = IPCMessage::event("small", serde_json::json!({}));
+        let mut input = encode_message(&big, WireFormat::MessagePack).unwrap();
+        input.extend(encode_message(&small, WireFormat::MessagePack).unwrap());
+
+        let (tx, rx) = mpsc::channel();
+        let handle = bridge.start_stdout_listener(std::io::Cursor::new(input), move |msg| {
+            let _ = tx.send(msg.event);
+        });
+
+        handle.join().unwrap();
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["small"]);
+    }
+
+    #[test]
+    fn test_stdout_listener_skips_plain_text_lines() {
         let bridge = IPCBridge::new();
+        let (tx, rx) = mpsc::channel();
+        let input = "booting backend...\n{\"id\":null,\"msg_type\":\"event\",\"event\":\"ready\",\"payload\":{},\"error\":null}\n";
 
-        // Queue a message when stdin is not available
-        let msg = IPCMessage::event("test", serde_json::json!({}));
-        bridge.queue_message(msg);
+        bridge.start_stdout_listener(std::io::Cursor::new(input.as_bytes().to_vec()), move |msg| {
+            let _ = tx.send(msg);
+        });
 
-        assert_eq!(bridge.queue_size(), 1);
+        let msg = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(msg.event, "ready");
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
     }
 
     #[test]
-    fn test_cancel_request() {
+    fn test_stdout_listener_json_stream_mode() {
+        let bridge = IPCBridge::new().with_framing_mode(FramingMode::JsonStream);
+        let (tx, rx) = mpsc::channel();
+        let input = "{\"msg_type\":\"event\",\n  \"event\":\"tree\",\n  \"payload\":{\"files\":[]},\"error\":null}";
+
+        bridge.start_stdout_listener(std::io::Cursor::new(input.as_bytes().to_vec()), move |msg| {
+            let _ = tx.send(msg);
+        });
+
+        let msg = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(msg.event, "tree");
+    }
+
+    #[test]
+    fn test_stdout_listener_reports_corrupt_stream() {
+        let bridge = IPCBridge::builder().corrupt_stream_threshold(3).build();
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&reports);
+        bridge.on_stream_corrupt(move |failures| seen.lock().unwrap().push(failures));
+
+        let good = "{\"id\":null,\"msg_type\":\"event\",\"event\":\"ok\",\"payload\":{},\"error\":null}\n";
+        // Two failures, a reset, then a run of five: fires once, at the third
+        let input = format!("{{bad\n{{bad\n{}plain log\n{}", good, "{bad\n".repeat(5));
+        let handle = bridge.start_stdout_listener(std::io::Cursor::new(input.into_bytes()), |_| {});
+        handle.join().unwrap();
+
+        assert_eq!(*reports.lock().unwrap(), vec![3]);
+    }
+
+    #[test]
+    fn test_stdout_listener_forwards_raw_lines() {
         let bridge = IPCBridge::new();
+        let (tx, rx) = mpsc::channel();
+        let (raw_tx, raw_rx) = mpsc::channel();
+        let input = "booting backend...\n{not json\n\n{\"id\":null,\"msg_type\":\"event\",\"event\":\"ready\",\"payload\":{},\"error\":null}\n";
 
-        // Add a pending request manually (simulating request without stdin)
-        {
-            let mut requests = bridge.pending_requests.lock().unwrap();
-            requests.insert("test-req-001".to_string(), PendingRequest {
-                event: "test".to_string(),
-                callback: Box::new(|_| {}),
-                created_at: Instant::now(),
-                timeout: Duration::from_secs(30),
+        let handle = bridge.start_stdout_listener_with_raw(
+            std::io::Cursor::new(input.as_bytes().to_vec()),
+            move |msg| {
+                let _ = tx.send(msg);
+            },
+            move |line| {
+                let _ = raw_tx.send(line);
+            },
+        );
+        handle.join().unwrap();
+
+        let lines: Vec<String> = raw_rx.try_iter().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "booting backend...");
+        assert_eq!(lines[1], "{not json");
+        assert!(lines[2].contains("\"ready\""));
+        assert_eq!(rx.try_recv().unwrap().event, "ready");
+    }
+
+    #[test]
+    fn test_json_stream_listener_forwards_log_text() {
+        let bridge = IPCBridge::new().with_framing_mode(FramingMode::JsonStream);
+        let (tx, rx) = mpsc::channel();
+        let (raw_tx, raw_rx) = mpsc::channel();
+        let input = "listening on 3000\n{\"msg_type\":\"event\",\"event\":\"tree\",\"payload\":null,\"error\":null}\ndone";
+
+        let handle = bridge.start_stdout_listener_with_raw(
+            std::io::Cursor::new(input.as_bytes().to_vec()),
+            move |msg| {
+                let _ = tx.send(msg);
+            },
+            move |line| {
+                let _ = raw_tx.send(line);
+            },
+        );
+        handle.join().unwrap();
+
+        let lines: Vec<String> = raw_rx.try_iter().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "listening on 3000");
+        assert_eq!(lines[2], "done");
+        assert_eq!(rx.try_recv().unwrap().event, "tree");
+    }
+
+    #[test]
+    fn test_stdout_listener_message_pack() {
+        let bridge = IPCBridge::new().with_wire_format(WireFormat::MessagePack);
+        let mut input = Vec::new();
+        for event in ["first", "second"] {
+            let msg = IPCMessage::event(event, serde_json::json!({"text": "line\nbreak"}));
+            input.extend(encode_message(&msg, WireFormat::MessagePack).unwrap());
+        }
+
+        let (tx, rx) = mpsc::channel();
+        bridge.start_stdout_listener(std::io::Cursor::new(input), move |msg| {
+            let _ = tx.send(msg.event);
+        });
+
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), "first");
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), "second");
+    }
+
+    #[test]
+    fn test_binary_payload_round_trip() {
+        let bridge = IPCBridge::new().with_wire_format(WireFormat::MessagePack);
+        let image: Vec<u8> = (0..=255).collect();
+        let mut input = encode_message(&IPCMessage::binary_event("thumbnail", image.clone()), WireFormat::MessagePack).unwrap();
+        input.extend(encode_message(&IPCMessage::event("after", serde_json::json!({})), WireFormat::MessagePack).unwrap());
+
+        let (bytes_tx, bytes_rx) = mpsc::channel();
+        bridge.on_binary("thumbnail", move |bytes| {
+            let _ = bytes_tx.send(bytes);
+        });
+        let (payload_tx, payload_rx) = mpsc::channel();
+        bridge.on("thumbnail", move |payload| {
+            let _ = payload_tx.send(payload);
+        });
+        let (tx, rx) = mpsc::channel();
+        let handle = bridge.start_stdout_listener(std::io::Cursor::new(input), move |msg| {
+            let _ = tx.send(msg.event);
+        });
+        handle.join().unwrap();
+
+        assert_eq!(bytes_rx.try_recv().unwrap(), image);
+        assert_eq!(payload_rx.try_recv().unwrap()[BINARY_LEN_KEY], 256);
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec!["thumbnail", "after"]);
+    }
+
+    #[test]
+    fn test_compressed_messages_round_trip() {
+        for format in [WireFormat::Json, WireFormat::MessagePack] {
+            let sender = IPCBridge::new().with_wire_format(format).with_compression(Compression::Zstd, 1024);
+            let written = Arc::new(Mutex::new(Vec::new()));
+            sender.set_writer(FailingWriter { remaining: usize::MAX, written: written.clone() });
+
+            let tree = " ".repeat(20_000);
+            sender.emit("file_tree", serde_json::json!({"tree": tree})).unwrap();
+            sender.emit("small", serde_json::json!({"ok": true})).unwrap();
+
+            let output = written.lock().unwrap().clone();
+            assert!(output.len() < tree.len() / 2, "{:?} output not compressed", format);
+
+            let receiver = IPCBridge::new().with_wire_format(format);
+            let (tx, rx) = mpsc::channel();
+            receiver.start_stdout_listener(std::io::Cursor::new(output), move |msg| {
+                let _ = tx.send(msg);
             });
+
+            let msg = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+            assert_eq!(msg.event, "file_tree");
+            assert_eq!(msg.payload["tree"], tree);
+            assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap().event, "small");
         }
+    }
 
-        assert_eq!(bridge.pending_request_count(), 1);
-        assert!(bridge.cancel_request("test-req-001"));
-        assert_eq!(bridge.pending_request_count(), 0);
-        assert!(!bridge.cancel_request("nonexistent"));
+    #[test]
+    fn test_small_messages_stay_uncompressed() {
+        let msg = IPCMessage::event("small", serde_json::json!({"text": "a".repeat(100)}));
+        let config = Some(CompressionConfig { codec: Compression::Zstd, threshold: 1024 });
+
+        for format in [WireFormat::Json, WireFormat::MessagePack] {
+            assert_eq!(
+                encode_message_compressed(&msg, format, config).unwrap(),
+                encode_message(&msg, format).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_emit_binary_requires_message_pack() {
+        let bridge = IPCBridge::new();
+        assert!(bridge.emit_binary("upload", vec![1, 2, 3]).is_err());
+        assert_eq!(bridge.queue_size(), 0);
+
+        let bridge = IPCBridge::new().with_wire_format(WireFormat::MessagePack);
+        let written = Arc::new(Mutex::new(Vec::new()));
+        bridge.set_writer(FailingWriter { remaining: usize::MAX, written: written.clone() });
+        bridge.emit_binary("upload", vec![1, 2, 3]).unwrap();
+
+        let output = written.lock().unwrap().clone();
+        assert_eq!(&output[output.len() - 7..], &[0, 0, 0, 3, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_off_removes_handler() {
+        let bridge = IPCBridge::new();
+        let removed_calls = Arc::new(AtomicUsize::new(0));
+        let kept_calls = Arc::new(AtomicUsize::new(0));
+
+        let removed_counter = Arc::clone(&removed_calls);
+        let removed_id = bridge.on("tick", move |_| {
+            removed_counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let kept_counter = Arc::clone(&kept_calls);
+        let kept_id = bridge.on("tick", move |_| {
+            kept_counter.fetch_add(1, Ordering::SeqCst);
+        });
+        assert_ne!(removed_id, kept_id);
+
+        assert!(bridge.off("tick", removed_id));
+        assert!(!bridge.off("tick", removed_id));
+        assert!(!bridge.off("unknown", kept_id));
+
+        let (tx, rx) = mpsc::channel();
+        let input = "{\"id\":null,\"msg_type\":\"event\",\"event\":\"tick\",\"payload\":{},\"error\":null}\n";
+        bridge.start_stdout_listener(std::io::Cursor::new(input.as_bytes().to_vec()), move |msg| {
+            let _ = tx.send(msg);
+        });
+        rx.recv_timeout(Duration::from_secs(2)).unwrap();
+
+        assert_eq!(removed_calls.load(Ordering::SeqCst), 0);
+        assert_eq!(kept_calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_remove_all_handlers() {
+        let bridge = IPCBridge::new();
+        bridge.on("tick", |_| {});
+        bridge.on("tick", |_| {});
+        bridge.on("other", |_| {});
+
+        assert_eq!(bridge.remove_all_handlers("tick"), 2);
+        assert_eq!(bridge.remove_all_handlers("tick"), 0);
+        assert_eq!(bridge.event_handlers.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_once_handler_fires_once() {
+        let bridge = IPCBridge::new();
+        let once_calls = Arc::new(AtomicUsize::new(0));
+        let on_calls = Arc::new(AtomicUsize::new(0));
+
+        let once_counter = Arc::clone(&once_calls);
+        bridge.once("backend_ready", move |_| {
+            once_counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let on_counter = Arc::clone(&on_calls);
+        bridge.on("backend_ready", move |_| {
+            on_counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let (tx, rx) = mpsc::channel();
+        let line = "{\"id\":null,\"msg_type\":\"event\",\"event\":\"backend_ready\",\"payload\":{},\"error\":null}\n";
+        let input = format!("{}{}", line, line);
+        bridge.start_stdout_listener(std::io::Cursor::new(input.into_bytes()), move |msg| {
+            let _ = tx.send(msg);
+        });
+        rx.recv_timeout(Duration::from_secs(2)).unwrap();
+        rx.recv_timeout(Duration::from_secs(2)).unwrap();
+
+        assert_eq!(once_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(on_calls.load(Ordering::SeqCst), 2);
+        assert_eq!(bridge.event_handlers.lock().unwrap()["backend_ready"].len(), 1);
+    }
+
+    #[test]
+    fn test_once_handler_can_be_removed() {
+        let bridge = IPCBridge::new();
+        let id = bridge.once("backend_ready", |_| {});
+        assert!(bridge.off("backend_ready", id));
     }
 }
@@ -0,0 +1,120 @@
+/**
+ * IPC Backpressure
+ *
+ * Watches the depth of the outbound queue and reports when it crosses a
+ * high-water mark, then again once it drains to a low-water mark. The gap
+ * between the two thresholds keeps the signal from flapping.
+ */
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Queue depth transition reported to `IPCBridge::on_backpressure`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackpressureEvent {
+    /// The queue reached the high-water mark; carries the current depth
+    HighWater(usize),
+    /// The queue drained to the low-water mark; carries the current depth
+    LowWater(usize),
+}
+
+type BackpressureCallback = Box<dyn Fn(BackpressureEvent) + Send + 'static>;
+
+/// Hysteresis state shared by the bridge and its senders
+pub struct Backpressure {
+    high_water: usize,
+    low_water: usize,
+    engaged: AtomicBool,
+    callback: Mutex<Option<BackpressureCallback>>,
+}
+
+impl Backpressure {
+    /// Create a tracker; `low_water` is capped at `high_water`
+    pub fn new(high_water: usize, low_water: usize) -> Self {
+        Backpressure {
+            high_water,
+            low_water: low_water.min(high_water),
+            engaged: AtomicBool::new(false),
+            callback: Mutex::new(None),
+        }
+    }
+
+    /// Create a tracker that never fires
+    pub fn disabled() -> Self {
+        Self::new(usize::MAX, usize::MAX)
+    }
+
+    /// Set the callback invoked on each transition
+    pub fn set_callback(&self, callback: BackpressureCallback) {
+        *self.callback.lock().unwrap() = Some(callback);
+    }
+
+    /// Whether the queue is currently above the high-water mark
+    pub fn is_engaged(&self) -> bool {
+        self.engaged.load(Ordering::SeqCst)
+    }
+
+    /// Report the current queue depth, firing the callback on a transition
+    ///
+    /// Must be called without the queue lock held, so the callback may
+    /// inspect the bridge.
+    pub fn update(&self, depth: usize) {
+        let event = if depth >= self.high_water {
+            self.engaged
+                .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                .ok()
+                .map(|_| BackpressureEvent::HighWater(depth))
+        } else if depth <= self.low_water {
+            self.engaged
+                .compare_exchange(true, false, Ordering::SeqCst, Ordering::SeqCst)
+                .ok()
+                .map(|_| BackpressureEvent::LowWater(depth))
+        } else {
+            None
+        };
+
+        if let Some(event) = event {
+            if let Some(callback) = self.callback.lock().unwrap().as_ref() {
+                callback(event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn recording(backpressure: &Backpressure) -> Arc<Mutex<Vec<BackpressureEvent>>> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&events);
+        backpressure.set_callback(Box::new(move |event| seen.lock().unwrap().push(event)));
+        events
+    }
+
+    #[test]
+    fn test_fires_once_per_transition() {
+        let backpressure = Backpressure::new(5, 2);
+        let events = recording(&backpressure);
+
+        for depth in [1, 3, 5, 6, 4, 3, 5, 2, 1, 4] {
+            backpressure.update(depth);
+        }
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![BackpressureEvent::HighWater(5), BackpressureEvent::LowWater(2)]
+        );
+        assert!(!backpressure.is_engaged());
+    }
+
+    #[test]
+    fn test_disabled_never_fires() {
+        let backpressure = Backpressure::disabled();
+        let events = recording(&backpressure);
+
+        backpressure.update(10_000);
+        assert!(events.lock().unwrap().is_empty());
+    }
+}
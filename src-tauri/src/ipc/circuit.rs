@@ -0,0 +1,232 @@
+/**
+ * IPC Circuit Breaker
+ *
+ * When the backend stops answering, every request would otherwise wait for
+ * its full timeout. After `failure_threshold` consecutive failures within
+ * `window` the circuit opens and requests fail fast for `cool_down`. Then a
+ * single trial request is let through (half-open): its success closes the
+ * circuit, its failure opens it again.
+ *
+ * Only timeouts and transport errors count as failures; an error response
+ * from the backend shows it is alive.
+ */
+
+use super::IPCError;
+use log::warn;
+use serde_json::Value;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Thresholds for `IPCBridge::with_circuit_breaker`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failures that open the circuit
+    pub failure_threshold: u32,
+    /// Failures further apart than this start a new count
+    pub window: Duration,
+    /// How long the circuit stays open before a trial request
+    pub cool_down: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        CircuitBreakerConfig {
+            failure_threshold: 5,
+            window: Duration::from_secs(60),
+            cool_down: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Circuit state reported by `IPCBridge::circuit_state`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are sent normally
+    Closed,
+    /// Requests fail fast until the cool-down ends
+    Open,
+    /// A trial request is in flight; others fail fast
+    HalfOpen,
+}
+
+#[derive(Debug)]
+enum State {
+    Closed {
+        failures: u32,
+        first_failure: Option<Instant>,
+    },
+    Open {
+        until: Instant,
+    },
+    /// The trial may be abandoned without an outcome (e.g. a dropped
+    /// future), so another is allowed once `until` passes
+    HalfOpen {
+        until: Instant,
+    },
+}
+
+/// Failure tracking shared by the bridge and its senders
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: Option<CircuitBreakerConfig>,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    /// Create a breaker with the given thresholds
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        CircuitBreaker {
+            config: Some(config),
+            state: Mutex::new(State::Closed { failures: 0, first_failure: None }),
+        }
+    }
+
+    /// Create a breaker that never opens
+    pub fn disabled() -> Self {
+        CircuitBreaker {
+            config: None,
+            state: Mutex::new(State::Closed { failures: 0, first_failure: None }),
+        }
+    }
+
+    /// Current state, as seen by the next request
+    pub fn state(&self) -> CircuitState {
+        match *self.state.lock().unwrap() {
+            State::Closed { .. } => CircuitState::Closed,
+            State::Open { until } if Instant::now() >= until => CircuitState::HalfOpen,
+            State::Open { .. } => CircuitState::Open,
+            State::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Whether a request may be sent; admits the trial once the cool-down ends
+    pub fn allow(&self) -> bool {
+        let Some(config) = self.config else {
+            return true;
+        };
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        match *state {
+            State::Closed { .. } => true,
+            State::Open { until } | State::HalfOpen { until } if now >= until => {
+                *state = State::HalfOpen { until: now + config.cool_down };
+                true
+            }
+            State::Open { .. } | State::HalfOpen { .. } => false,
+        }
+    }
+
+    /// Record the outcome of a request
+    ///
+    /// Any response, including an error response, counts as a success.
+    pub fn record(&self, result: &Result<Value, IPCError>) {
+        match result {
+            Err(e) if e.is_retryable() => {
+                if self.record_failure() {
+                    warn!("Circuit opened after repeated request failures, last: {}", e);
+                }
+            }
+            Ok(_) | Err(IPCError::BackendError { .. }) => self.record_success(),
+            Err(_) => {}
+        }
+    }
+
+    /// Record a request that got a response
+    pub fn record_success(&self) {
+        if self.config.is_some() {
+            *self.state.lock().unwrap() = State::Closed { failures: 0, first_failure: None };
+        }
+    }
+
+    /// Record a request that timed out or could not be delivered
+    ///
+    /// Returns `true` if this failure opened the circuit.
+    pub fn record_failure(&self) -> bool {
+        let Some(config) = self.config else {
+            return false;
+        };
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        match *state {
+            State::Closed { failures, first_failure } => {
+                let (failures, first_failure) = match first_failure {
+                    Some(first) if now.duration_since(first) <= config.window => (failures + 1, first),
+                    _ => (1, now),
+                };
+                if failures >= config.failure_threshold {
+                    *state = State::Open { until: now + config.cool_down };
+                    true
+                } else {
+                    *state = State::Closed { failures, first_failure: Some(first_failure) };
+                    false
+                }
+            }
+            State::HalfOpen { .. } => {
+                *state = State::Open { until: now + config.cool_down };
+                true
+            }
+            State::Open { .. } => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn breaker(cool_down: Duration) -> CircuitBreaker {
+        CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 3,
+            window: Duration::from_secs(60),
+            cool_down,
+        })
+    }
+
+    #[test]
+    fn test_opens_after_consecutive_failures() {
+        let breaker = breaker(Duration::from_secs(60));
+        assert!(!breaker.record_failure());
+        assert!(!breaker.record_failure());
+        breaker.record_success();
+        assert!(!breaker.record_failure());
+        assert!(!breaker.record_failure());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        assert!(breaker.record_failure());
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.allow());
+    }
+
+    #[test]
+    fn test_half_open_trial_closes_or_reopens() {
+        let breaker = breaker(Duration::from_millis(50));
+        for _ in 0..3 {
+            breaker.record_failure();
+        }
+        thread::sleep(Duration::from_millis(60));
+
+        // One trial only
+        assert!(breaker.allow());
+        assert!(!breaker.allow());
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(breaker.record_failure());
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        thread::sleep(Duration::from_millis(60));
+        assert!(breaker.allow());
+        breaker.record_success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.allow());
+    }
+
+    #[test]
+    fn test_disabled_never_opens() {
+        let breaker = CircuitBreaker::disabled();
+        for _ in 0..100 {
+            assert!(!breaker.record_failure());
+        }
+        assert!(breaker.allow());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
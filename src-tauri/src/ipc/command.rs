@@ -0,0 +1,33 @@
+/**
+ * Typed IPC Commands
+ *
+ * Ties an event name to its request and response types, so `IPCBridge::call`
+ * checks the event-name/payload contract at compile time. Messages still
+ * travel as JSON payloads underneath.
+ *
+ * # Example
+ * ```ignore
+ * struct GetFileTree;
+ *
+ * impl Command for GetFileTree {
+ *     type Req = PathBuf;
+ *     type Res = FileTree;
+ *     const EVENT: &'static str = "get_file_tree";
+ * }
+ *
+ * bridge.call::<GetFileTree>(PathBuf::from("/src"), |tree| { ... })?;
+ * ```
+ */
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// A request/response pair Node.js handles under a fixed event name
+pub trait Command {
+    /// Payload sent to Node.js
+    type Req: Serialize;
+    /// Payload Node.js responds with
+    type Res: DeserializeOwned;
+    /// Event name the command is sent under
+    const EVENT: &'static str;
+}
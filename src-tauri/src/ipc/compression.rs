@@ -0,0 +1,114 @@
+/**
+ * IPC Message Compression
+ *
+ * Large payloads (file trees, search results) are mostly text and compress
+ * well. A compressed message is sent as a small header announcing the codec
+ * and original length, followed by the compressed bytes as a length-prefixed
+ * frame. Messages below the configured threshold are sent as is.
+ */
+
+use serde::{Deserialize, Serialize};
+
+/// Header key naming the codec of the frame that follows
+pub const COMPRESSED_KEY: &str = "__compressed__";
+
+/// Header key carrying the length of the message before compression
+pub const ORIGINAL_LEN_KEY: &str = "__original_len__";
+
+/// Codec used for compressed messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// Zstandard at its default level (default)
+    #[default]
+    Zstd,
+}
+
+/// When and how outbound messages are compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionConfig {
+    pub codec: Compression,
+    /// Encoded messages of at least this many bytes are compressed
+    pub threshold: usize,
+}
+
+/// Header sent in place of a compressed message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompressionHeader {
+    #[serde(rename = "__compressed__")]
+    pub codec: Compression,
+    #[serde(rename = "__original_len__")]
+    pub original_len: usize,
+}
+
+impl CompressionHeader {
+    /// Parse a JSON line as a header, if it is one
+    pub fn from_json(line: &str) -> Option<Self> {
+        if !line.contains(COMPRESSED_KEY) {
+            return None;
+        }
+        serde_json::from_str(line).ok()
+    }
+
+    /// Parse a MessagePack frame as a header, if it is one
+    pub fn from_msgpack(frame: &[u8]) -> Option<Self> {
+        rmp_serde::from_slice(frame).ok()
+    }
+}
+
+/// Compress an encoded message body
+pub fn compress(codec: Compression, body: &[u8]) -> Result<Vec<u8>, String> {
+    match codec {
+        Compression::Zstd => zstd::bulk::compress(body, zstd::DEFAULT_COMPRESSION_LEVEL)
+            .map_err(|e| format!("Failed to compress message: {}", e)),
+    }
+}
+
+/// Decompress the frame announced by `header`
+///
+/// Fails if the announced length exceeds `max_bytes` or does not match the
+/// decompressed data.
+pub fn decompress(header: &CompressionHeader, body: &[u8], max_bytes: usize) -> Result<Vec<u8>, String> {
+    if header.original_len > max_bytes {
+        return Err(format!(
+            "compressed message of {} bytes exceeds the {} byte limit",
+            header.original_len, max_bytes
+        ));
+    }
+    let data = match header.codec {
+        Compression::Zstd => zstd::bulk::decompress(body, header.original_len)
+            .map_err(|e| format!("Failed to decompress message: {}", e))?,
+    };
+    if data.len() != header.original_len {
+        return Err(format!(
+            "decompressed message is {} bytes, expected {}",
+            data.len(),
+            header.original_len
+        ));
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let body = "{\"path\":\"src/components/\"}".repeat(200).into_bytes();
+        let compressed = compress(Compression::Zstd, &body).unwrap();
+        let header = CompressionHeader { codec: Compression::Zstd, original_len: body.len() };
+
+        assert_eq!(decompress(&header, &compressed, body.len()).unwrap(), body);
+        assert!(decompress(&header, &compressed, body.len() - 1).is_err());
+    }
+
+    #[test]
+    fn test_header_detection() {
+        let line = serde_json::to_string(&CompressionHeader { codec: Compression::Zstd, original_len: 42 }).unwrap();
+        assert_eq!(line, r#"{"__compressed__":"zstd","__original_len__":42}"#);
+        assert_eq!(CompressionHeader::from_json(&line).unwrap().original_len, 42);
+
+        assert!(CompressionHeader::from_json(r#"{"id":null,"msg_type":"event","event":"x","payload":{},"error":null}"#).is_none());
+    }
+}
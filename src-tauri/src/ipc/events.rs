@@ -0,0 +1,95 @@
+/**
+ * Typed Event Registry
+ *
+ * Event names are plain strings on the wire, so a typo in `on("file_chagned")`
+ * compiles fine and the handler silently never fires. `define_events!` declares
+ * each event once as a unit struct, and both the emitting side and the handler
+ * registration refer to that symbol instead of repeating the string.
+ *
+ * An event may name its payload type; events without one carry a raw JSON value.
+ *
+ * # Example
+ * ```ignore
+ * define_events! {
+ *     /// A watched file was modified
+ *     FileChanged => "file_changed": FileChangedPayload,
+ *     SessionEnded => "session_ended",
+ * }
+ *
+ * bridge.on(FileChanged::NAME, |msg| { ... });
+ * bridge.emit(SessionEnded::NAME, json!({}))?;
+ * ```
+ */
+
+/// An event with a fixed name on the wire, declared via `define_events!`
+pub trait Event {
+    /// Payload carried by the event
+    type Payload;
+    /// Event name used by `on`/`emit`
+    const NAME: &'static str;
+}
+
+/// Declare events as unit structs exposing their wire name as `NAME`
+#[macro_export]
+macro_rules! define_events {
+    (@payload) => { ::serde_json::Value };
+    (@payload $payload:ty) => { $payload };
+    ($( $(#[$meta:meta])* $name:ident => $event:literal $(: $payload:ty)? ),* $(,)?) => {
+        $(
+            $(#[$meta])*
+            #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+            pub struct $name;
+
+            #[allow(dead_code)]
+            impl $name {
+                /// Event name used by `on`/`emit`
+                pub const NAME: &'static str = $event;
+            }
+
+            impl $crate::ipc::events::Event for $name {
+                type Payload = $crate::define_events!(@payload $($payload)?);
+                const NAME: &'static str = $event;
+            }
+        )*
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::{json, Value};
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    pub struct FileChangedPayload {
+        path: String,
+    }
+
+    define_events! {
+        /// A watched file was modified
+        FileChanged => "file_changed": FileChangedPayload,
+        SessionEnded => "session_ended",
+    }
+
+    fn decode<E: Event>(payload: Value) -> E::Payload
+    where
+        E::Payload: serde::de::DeserializeOwned,
+    {
+        serde_json::from_value(payload).unwrap()
+    }
+
+    #[test]
+    fn test_events_expose_wire_names() {
+        assert_eq!(FileChanged::NAME, "file_changed");
+        assert_eq!(<SessionEnded as Event>::NAME, "session_ended");
+    }
+
+    #[test]
+    fn test_events_carry_payload_types() {
+        let changed = decode::<FileChanged>(json!({"path": "/src/main.rs"}));
+        assert_eq!(changed, FileChangedPayload { path: "/src/main.rs".to_string() });
+
+        let ended: Value = decode::<SessionEnded>(json!({"code": 0}));
+        assert_eq!(ended, json!({"code": 0}));
+    }
+}
@@ -0,0 +1,250 @@
+/**
+ * JSON-RPC 2.0 Adapter
+ *
+ * Maps `IPCMessage` onto JSON-RPC 2.0 envelopes so the Node.js side can use
+ * standard RPC libraries:
+ * - Request  <-> `{"jsonrpc":"2.0","method","params","id"}`
+ * - Event    <-> notification (a request without `id`)
+ * - Response <-> `{"jsonrpc":"2.0","result","id"}`
+ * - Error    <-> `{"jsonrpc":"2.0","error":{"code","message","data"},"id"}`
+ *
+ * JSON-RPC responses do not repeat the method, so parsed responses have an
+ * empty `event`; they are matched to pending requests by `id` alone. The
+ * `seq` and `version` fields have no JSON-RPC equivalent and are not sent.
+ * Batches are JSON arrays of envelopes on a single line.
+ *
+ * Message IDs are strings. A numeric JSON-RPC id is kept in the message's
+ * `meta` under `ID_META_KEY` and echoed back unchanged by responses that
+ * carry the request's `meta` (as the bridge's request handlers' replies do).
+ */
+
+use super::{IPCMessage, IPCMessageType};
+use serde_json::{Map, Value};
+
+/// Error code used when an error response carries no numeric code
+/// (the start of the JSON-RPC "server error" range)
+pub const DEFAULT_ERROR_CODE: i64 = -32000;
+
+/// `meta` key holding the original id of a message whose JSON-RPC id is not a string
+pub const ID_META_KEY: &str = "jsonrpc_id";
+
+/// JSON-RPC id of a message, preferring the original id kept in `meta`
+fn envelope_id(msg: &IPCMessage) -> Option<Value> {
+    match msg.meta.as_ref().and_then(|meta| meta.get(ID_META_KEY)) {
+        Some(id) => Some(id.clone()),
+        None => msg.id.clone().map(Value::from),
+    }
+}
+
+/// Convert a message to a JSON-RPC 2.0 envelope
+pub fn to_envelope(msg: &IPCMessage) -> Result<Value, String> {
+    if msg.binary.is_some() {
+        return Err("Failed to encode message: binary payloads cannot be sent as JSON-RPC".to_string());
+    }
+
+    let mut envelope = Map::new();
+    envelope.insert("jsonrpc".to_string(), Value::from("2.0"));
+    match msg.msg_type {
+        IPCMessageType::Chunk => {
+            return Err("Failed to encode message: stream chunks have no JSON-RPC form".to_string());
+        }
+        IPCMessageType::Request | IPCMessageType::Event => {
+            envelope.insert("method".to_string(), Value::from(msg.event.clone()));
+            if !msg.payload.is_null() {
+                envelope.insert("params".to_string(), msg.payload.clone());
+            }
+            if matches!(msg.msg_type, IPCMessageType::Request) {
+                let id = envelope_id(msg).ok_or("Failed to encode message: request has no id")?;
+                envelope.insert("id".to_string(), id);
+            }
+        }
+        IPCMessageType::Response => {
+            match &msg.error {
+                Some(message) => {
+                    let code = msg.payload.get("code").and_then(Value::as_i64).unwrap_or(DEFAULT_ERROR_CODE);
+                    let mut error = Map::new();
+                    error.insert("code".to_string(), Value::from(code));
+                    error.insert("message".to_string(), Value::from(message.clone()));
+                    if !msg.payload.is_null() {
+                        error.insert("data".to_string(), msg.payload.clone());
+                    }
+                    envelope.insert("error".to_string(), Value::Object(error));
+                }
+                None => {
+                    envelope.insert("result".to_string(), msg.payload.clone());
+                }
+            }
+            envelope.insert("id".to_string(), envelope_id(msg).unwrap_or(Value::Null));
+        }
+    }
+    Ok(Value::Object(envelope))
+}
+
+/// Convert a JSON-RPC 2.0 envelope to a message
+pub fn from_envelope(envelope: &Value) -> Result<IPCMessage, String> {
+    let obj = envelope
+        .as_object()
+        .ok_or_else(|| format!("Failed to parse JSON-RPC message: not an object - Input: {}", envelope))?;
+    if obj.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+        return Err(format!("Failed to parse JSON-RPC message: missing \"jsonrpc\":\"2.0\" - Input: {}", envelope));
+    }
+
+    let id = match obj.get("id") {
+        None | Some(Value::Null) => None,
+        Some(Value::String(id)) => Some(id.clone()),
+        Some(Value::Number(id)) => Some(id.to_string()),
+        Some(other) => return Err(format!("Failed to parse JSON-RPC message: invalid id {}", other)),
+    };
+
+    let mut msg = if let Some(method) = obj.get("method") {
+        let event = method
+            .as_str()
+            .ok_or_else(|| format!("Failed to parse JSON-RPC message: invalid method {}", method))?;
+        let params = obj.get("params").cloned().unwrap_or(Value::Null);
+        match &id {
+            Some(id) => IPCMessage::request(id, event, params),
+            None => IPCMessage::event(event, params),
+        }
+    } else if let Some(error) = obj.get("error") {
+        let message = error.get("message").and_then(Value::as_str).unwrap_or("Unknown error");
+        let mut payload = Map::new();
+        if let Some(code) = error.get("code") {
+            payload.insert("code".to_string(), code.clone());
+        }
+        if let Some(data) = error.get("data") {
+            payload.insert("data".to_string(), data.clone());
+        }
+        let mut msg = IPCMessage::error_response("", "", message);
+        msg.payload = Value::Object(payload);
+        msg
+    } else if let Some(result) = obj.get("result") {
+        IPCMessage::response("", "", result.clone())
+    } else {
+        return Err(format!("Failed to parse JSON-RPC message: no method, result or error - Input: {}", envelope));
+    };
+
+    if matches!(msg.msg_type, IPCMessageType::Response) {
+        msg.id = id;
+    }
+    if let Some(id @ Value::Number(_)) = obj.get("id") {
+        msg = msg.with_meta(ID_META_KEY, id.clone());
+    }
+    Ok(msg)
+}
+
+/// Encode a message as a newline-terminated JSON-RPC 2.0 line
+pub fn encode_message_for_stdin_jsonrpc(msg: &IPCMessage) -> Result<String, String> {
+    let envelope = to_envelope(msg)?;
+    serde_json::to_string(&envelope)
+        .map(|s| format!("{}\n", s))
+        .map_err(|e| format!("Failed to encode message: {}", e))
+}
+
+/// Encode several messages as a single JSON-RPC 2.0 batch line
+pub fn encode_batch_jsonrpc(msgs: &[IPCMessage]) -> Result<String, String> {
+    let batch = msgs.iter().map(to_envelope).collect::<Result<Vec<_>, _>>()?;
+    serde_json::to_string(&batch)
+        .map(|s| format!("{}\n", s))
+        .map_err(|e| format!("Failed to encode message: {}", e))
+}
+
+/// Parse a JSON-RPC 2.0 line into messages
+///
+/// A single envelope yields one message, a batch array one message per
+/// element. An invalid element fails the whole line.
+pub fn parse_stdin_message_jsonrpc(raw_message: &str) -> Result<Vec<IPCMessage>, String> {
+    let trimmed = raw_message.trim();
+    if trimmed.is_empty() {
+        return Err("Empty message".to_string());
+    }
+
+    let value: Value = serde_json::from_str(trimmed)
+        .map_err(|e| format!("Failed to parse message: {} - Input: {}", e, trimmed))?;
+    match value {
+        Value::Array(batch) => {
+            if batch.is_empty() {
+                return Err("Failed to parse JSON-RPC message: empty batch".to_string());
+            }
+            batch.iter().map(from_envelope).collect()
+        }
+        envelope => from_envelope(&envelope).map(|msg| vec![msg]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipc::IPCError;
+
+    #[test]
+    fn test_request_and_notification_envelopes() {
+        let line = encode_message_for_stdin_jsonrpc(&IPCMessage::request("req_1", "get_data", serde_json::json!({"q": 1}))).unwrap();
+        let envelope: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(envelope, serde_json::json!({"jsonrpc": "2.0", "method": "get_data", "params": {"q": 1}, "id": "req_1"}));
+
+        let envelope = to_envelope(&IPCMessage::event("progress", serde_json::json!([50]))).unwrap();
+        assert!(envelope.get("id").is_none());
+
+        let parsed = parse_stdin_message_jsonrpc(&line).unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert!(matches!(parsed[0].msg_type, IPCMessageType::Request));
+        assert_eq!(parsed[0].event, "get_data");
+    }
+
+    #[test]
+    fn test_result_and_error_responses() {
+        let parsed = parse_stdin_message_jsonrpc(r#"{"jsonrpc":"2.0","result":[1,2],"id":7}"#).unwrap();
+        assert!(matches!(parsed[0].msg_type, IPCMessageType::Response));
+        assert_eq!(parsed[0].id.as_deref(), Some("7"));
+        assert_eq!(parsed[0].payload, serde_json::json!([1, 2]));
+
+        let parsed = parse_stdin_message_jsonrpc(
+            r#"{"jsonrpc":"2.0","error":{"code":-32601,"message":"Method not found"},"id":"req_2"}"#,
+        )
+        .unwrap();
+        match IPCError::from_response(&parsed[0]) {
+            Some(IPCError::BackendError { code, message }) => {
+                assert_eq!(code.as_deref(), Some("-32601"));
+                assert_eq!(message, "Method not found");
+            }
+            other => panic!("expected a backend error, got {:?}", other),
+        }
+
+        let envelope = to_envelope(&IPCMessage::error_response("req_3", "load", "boom")).unwrap();
+        assert_eq!(envelope["error"]["code"], DEFAULT_ERROR_CODE);
+        assert_eq!(envelope["id"], "req_3");
+    }
+
+    #[test]
+    fn test_numeric_id_is_echoed_unchanged() {
+        let parsed = parse_stdin_message_jsonrpc(r#"{"jsonrpc":"2.0","method":"sum","params":[1,2],"id":7}"#).unwrap();
+        let request = &parsed[0];
+        assert_eq!(request.id.as_deref(), Some("7"));
+
+        let mut response = IPCMessage::response(request.id.as_deref().unwrap(), &request.event, serde_json::json!(3));
+        response.meta = request.meta.clone();
+        let envelope = to_envelope(&response).unwrap();
+        assert_eq!(envelope, serde_json::json!({"jsonrpc": "2.0", "result": 3, "id": 7}));
+
+        let envelope = to_envelope(&IPCMessage::response("7", "sum", serde_json::json!(3))).unwrap();
+        assert_eq!(envelope["id"], "7");
+    }
+
+    #[test]
+    fn test_batch_round_trip() {
+        let msgs = vec![
+            IPCMessage::event("a", serde_json::json!(1)),
+            IPCMessage::request("req_4", "b", serde_json::json!(2)),
+        ];
+        let line = encode_batch_jsonrpc(&msgs).unwrap();
+        assert!(line.starts_with('['));
+
+        let parsed = parse_stdin_message_jsonrpc(&line).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].event, "a");
+        assert_eq!(parsed[1].id.as_deref(), Some("req_4"));
+
+        assert!(parse_stdin_message_jsonrpc("[]").is_err());
+        assert!(parse_stdin_message_jsonrpc(r#"{"method":"x"}"#).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+/**
+ * IPC Loopback Backend
+ *
+ * An in-memory stand-in for the Node.js process, so `request`, timeouts,
+ * retries and coalescing can be exercised without spawning node. Messages
+ * written by the bridge are decoded and handed to a responder; its replies
+ * are fed back through the stdout listener like real Node.js output.
+ * Responders given a `Replier` may also answer later or from another thread.
+ *
+ * Compressed frames are not understood, so do not combine with compression.
+ */
+
+use super::{decode_message, encode_message, IPCMessage, WireFormat};
+use log::warn;
+use std::io::{Read, Write};
+use std::sync::mpsc;
+
+/// Writer standing in for Node.js stdin
+///
+/// Decodes each complete frame and passes it to the responder; replies
+/// are sent to the paired `LoopbackReader`.
+pub struct LoopbackWriter<F> {
+    responder: F,
+    replier: Replier,
+    buf: Vec<u8>,
+}
+
+/// Handle for writing replies to the paired `LoopbackReader`
+#[derive(Clone)]
+pub struct Replier {
+    format: WireFormat,
+    tx: mpsc::Sender<Vec<u8>>,
+}
+
+impl Replier {
+    /// Encode `reply` and feed it to the reader, as if Node.js had written it
+    pub fn send(&self, reply: &IPCMessage) {
+        match encode_message(reply, self.format) {
+            Ok(bytes) => {
+                let _ = self.tx.send(bytes);
+            }
+            Err(e) => warn!("Loopback backend failed to encode a reply: {}", e),
+        }
+    }
+}
+
+/// Reader standing in for Node.js stdout
+///
+/// Ends (returns EOF) once its `LoopbackWriter` and every clone of its
+/// `Replier` are dropped.
+pub struct LoopbackReader {
+    rx: mpsc::Receiver<Vec<u8>>,
+    buf: Vec<u8>,
+}
+
+/// Create a connected writer/reader pair for the given wire format
+///
+/// The responder's return value, if any, is the reply to each message.
+pub fn pipe<F>(
+    format: WireFormat,
+    responder: F,
+) -> (LoopbackWriter<impl Fn(IPCMessage, &Replier)>, LoopbackReader)
+where
+    F: Fn(IPCMessage) -> Option<IPCMessage>,
+{
+    pipe_with_replier(format, move |msg, replier: &Replier| {
+        if let Some(reply) = responder(msg) {
+            replier.send(&reply);
+        }
+    })
+}
+
+/// Create a connected writer/reader pair whose responder replies through a `Replier`
+///
+/// The responder may send any number of replies, and may clone the
+/// `Replier` to answer later, e.g. from another thread.
+pub fn pipe_with_replier<F>(format: WireFormat, responder: F) -> (LoopbackWriter<F>, LoopbackReader)
+where
+    F: Fn(IPCMessage, &Replier),
+{
+    let (tx, rx) = mpsc::channel();
+    (
+        LoopbackWriter { responder, replier: Replier { format, tx }, buf: Vec::new() },
+        LoopbackReader { rx, buf: Vec::new() },
+    )
+}
+
+impl<F> LoopbackWriter<F>
+where
+    F: Fn(IPCMessage, &Replier),
+{
+    /// Take the next complete frame body out of the buffer
+    fn next_frame(&mut self) -> Option<Vec<u8>> {
+        match self.replier.format {
+            WireFormat::Json => {
+                let end = self.buf.iter().position(|&b| b == b'\n')?;
+                let mut frame: Vec<u8> = self.buf.drain(..=end).collect();
+                frame.pop();
+                Some(frame)
+            }
+            WireFormat::MessagePack => {
+                let len = u32::from_be_bytes(self.buf.get(..4)?.try_into().ok()?) as usize;
+                if self.buf.len() < 4 + len {
+                    return None;
+                }
+                let frame = self.buf[4..4 + len].to_vec();
+                self.buf.drain(..4 + len);
+                Some(frame)
+            }
+        }
+    }
+}
+
+impl<F> Write for LoopbackWriter<F>
+where
+    F: Fn(IPCMessage, &Replier),
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        while let Some(frame) = self.next_frame() {
+            let msg = match decode_message(&frame, self.replier.format) {
+                Ok(msg) => msg,
+                Err(e) => {
+                    warn!("Loopback backend skipped a frame: {}", e);
+                    continue;
+                }
+            };
+            (self.responder)(msg, &self.replier);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for LoopbackReader {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        if self.buf.is_empty() {
+            match self.rx.recv() {
+                Ok(bytes) => self.buf = bytes,
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = out.len().min(self.buf.len());
+        out[..n].copy_from_slice(&self.buf[..n]);
+        self.buf.drain(..n);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reassembles_frames_split_across_writes() {
+        let (mut writer, mut reader) = pipe(WireFormat::MessagePack, |msg: IPCMessage| {
+            Some(IPCMessage::response(msg.id.as_deref()?, &msg.event, msg.payload))
+        });
+        let frame = encode_message(&IPCMessage::request("req-1", "echo", serde_json::json!(42)), WireFormat::MessagePack).unwrap();
+        writer.write_all(&frame[..3]).unwrap();
+        writer.write_all(&frame[3..]).unwrap();
+        drop(writer);
+
+        let mut output = Vec::new();
+        reader.read_to_end(&mut output).unwrap();
+        let reply = decode_message(&output[4..], WireFormat::MessagePack).unwrap();
+        assert_eq!(reply.id.as_deref(), Some("req-1"));
+        assert_eq!(reply.payload, serde_json::json!(42));
+    }
+}
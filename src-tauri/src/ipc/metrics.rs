@@ -0,0 +1,176 @@
+/**
+ * IPC Metrics
+ *
+ * Always-on counters for the IPC bridge: requests sent, responses received,
+ * timeouts, bytes over the pipes and request latency. Counters are atomics;
+ * latency samples are kept in a small fixed-size window so the p99 stays
+ * cheap to compute.
+ */
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Number of recent latency samples used for the p99
+const LATENCY_WINDOW: usize = 1024;
+
+/// Live counters updated by the bridge
+#[derive(Debug, Default)]
+pub struct IPCMetrics {
+    requests_sent: AtomicU64,
+    responses_received: AtomicU64,
+    timeouts: AtomicU64,
+    /// Encoded bytes written to Node.js stdin
+    bytes_sent: AtomicU64,
+    /// Bytes read from Node.js stdout
+    bytes_received: AtomicU64,
+    /// Sum of all response latencies in microseconds
+    latency_total_micros: AtomicU64,
+    /// Most recent latency samples in microseconds
+    recent_latencies: Mutex<VecDeque<u64>>,
+}
+
+/// Point-in-time view of the bridge metrics
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IPCMetricsSnapshot {
+    pub requests_sent: u64,
+    pub responses_received: u64,
+    pub timeouts: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Mean latency over all responses, in milliseconds
+    pub avg_latency_ms: f64,
+    /// 99th percentile latency over recent responses, in milliseconds
+    pub p99_latency_ms: f64,
+    pub queue_depth: usize,
+    pub pending_requests: usize,
+}
+
+impl IPCMetrics {
+    /// Create a zeroed metrics collector
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a request written (or queued) for Node.js
+    pub fn record_request_sent(&self) {
+        self.requests_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a response matched to a pending request
+    pub fn record_response(&self, latency: Duration) {
+        let micros = latency.as_micros().min(u64::MAX as u128) as u64;
+        self.responses_received.fetch_add(1, Ordering::Relaxed);
+        self.latency_total_micros.fetch_add(micros, Ordering::Relaxed);
+
+        let mut recent = self.recent_latencies.lock().unwrap();
+        if recent.len() == LATENCY_WINDOW {
+            recent.pop_front();
+        }
+        recent.push_back(micros);
+    }
+
+    /// Record a request that timed out
+    pub fn record_timeout(&self) {
+        self.timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record bytes written to Node.js stdin
+    pub fn record_bytes_sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Record bytes read from Node.js stdout
+    pub fn record_bytes_received(&self, bytes: usize) {
+        self.bytes_received.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Total bytes written to Node.js stdin
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    /// Total bytes read from Node.js stdout
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+
+    /// Take a snapshot, filling in the gauges owned by the bridge
+    pub fn snapshot(&self, queue_depth: usize, pending_requests: usize) -> IPCMetricsSnapshot {
+        let responses_received = self.responses_received.load(Ordering::Relaxed);
+        let avg_latency_ms = if responses_received == 0 {
+            0.0
+        } else {
+            self.latency_total_micros.load(Ordering::Relaxed) as f64 / responses_received as f64 / 1000.0
+        };
+
+        let p99_latency_ms = {
+            let mut samples: Vec<u64> = self.recent_latencies.lock().unwrap().iter().copied().collect();
+            if samples.is_empty() {
+                0.0
+            } else {
+                samples.sort_unstable();
+                let rank = (samples.len() * 99).div_ceil(100).max(1);
+                samples[rank - 1] as f64 / 1000.0
+            }
+        };
+
+        IPCMetricsSnapshot {
+            requests_sent: self.requests_sent.load(Ordering::Relaxed),
+            responses_received,
+            timeouts: self.timeouts.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent(),
+            bytes_received: self.bytes_received(),
+            avg_latency_ms,
+            p99_latency_ms,
+            queue_depth,
+            pending_requests,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_snapshot() {
+        let metrics = IPCMetrics::new();
+        let snapshot = metrics.snapshot(2, 1);
+
+        assert_eq!(snapshot.requests_sent, 0);
+        assert_eq!(snapshot.bytes_sent, 0);
+        assert_eq!(snapshot.avg_latency_ms, 0.0);
+        assert_eq!(snapshot.p99_latency_ms, 0.0);
+        assert_eq!(snapshot.queue_depth, 2);
+        assert_eq!(snapshot.pending_requests, 1);
+    }
+
+    #[test]
+    fn test_latency_average_and_p99() {
+        let metrics = IPCMetrics::new();
+        for ms in 1..=100 {
+            metrics.record_request_sent();
+            metrics.record_response(Duration::from_millis(ms));
+        }
+        metrics.record_timeout();
+
+        let snapshot = metrics.snapshot(0, 0);
+        assert_eq!(snapshot.requests_sent, 100);
+        assert_eq!(snapshot.responses_received, 100);
+        assert_eq!(snapshot.timeouts, 1);
+        assert!((snapshot.avg_latency_ms - 50.5).abs() < 1e-9);
+        assert_eq!(snapshot.p99_latency_ms, 99.0);
+    }
+
+    #[test]
+    fn test_latency_window_is_bounded() {
+        let metrics = IPCMetrics::new();
+        for _ in 0..LATENCY_WINDOW + 10 {
+            metrics.record_response(Duration::from_millis(1));
+        }
+        assert_eq!(metrics.recent_latencies.lock().unwrap().len(), LATENCY_WINDOW);
+    }
+}
@@ -0,0 +1,178 @@
+/**
+ * Mock Backend
+ *
+ * Scriptable stand-in for a flaky Node.js backend, for tests of retries,
+ * timeouts and error handling. It sits behind the loopback transport,
+ * records every message the bridge writes, and answers requests according
+ * to its policy:
+ * - drop the first N requests without answering
+ * - answer after a fixed delay
+ * - answer requests for a given event with an error
+ *
+ * Requests that get an answer are echoed back with their own payload;
+ * events are recorded but never answered.
+ *
+ * # Example
+ * ```ignore
+ * let backend = MockBackend::new().with_dropped_requests(1);
+ * backend.connect(&bridge);
+ * assert!(bridge.request_blocking("status", json!({}), timeout).is_err());
+ * assert!(bridge.request_blocking("status", json!({}), timeout).is_ok());
+ * ```
+ */
+
+use super::loopback::{self, Replier};
+use super::{IPCBridge, IPCMessage, IPCMessageType};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How the mock backend treats incoming requests
+#[derive(Debug, Default)]
+struct MockState {
+    /// Requests still to be dropped without an answer
+    drop_remaining: usize,
+    /// Delay before each answer
+    delay: Duration,
+    /// Error message returned for each failing event
+    errors: HashMap<String, String>,
+    /// Every message received, in order
+    received: Vec<IPCMessage>,
+}
+
+/// In-process backend answering the bridge according to a programmable policy
+#[derive(Clone, Default)]
+pub struct MockBackend {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockBackend {
+    /// Create a backend that echoes every request immediately
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Leave the first `count` requests unanswered
+    pub fn with_dropped_requests(self, count: usize) -> Self {
+        self.state.lock().unwrap().drop_remaining = count;
+        self
+    }
+
+    /// Answer each request only after `delay`
+    pub fn with_delay(self, delay: Duration) -> Self {
+        self.state.lock().unwrap().delay = delay;
+        self
+    }
+
+    /// Answer requests for `event` with `error`
+    pub fn with_error(self, event: &str, error: &str) -> Self {
+        self.state.lock().unwrap().errors.insert(event.to_string(), error.to_string());
+        self
+    }
+
+    /// Connect `bridge` to this backend, returning the stdout listener thread
+    pub fn connect(&self, bridge: &IPCBridge) -> JoinHandle<()> {
+        let state = Arc::clone(&self.state);
+        let (writer, reader) = loopback::pipe_with_replier(bridge.wire_format, move |msg, replier| {
+            respond(&state, msg, replier);
+        });
+        bridge.set_writer(writer);
+        bridge.start_stdout_listener(reader, |_| {})
+    }
+
+    /// Every message the bridge has written, in order
+    pub fn received(&self) -> Vec<IPCMessage> {
+        self.state.lock().unwrap().received.clone()
+    }
+
+    /// Number of requests received for `event`, answered or not
+    pub fn request_count(&self, event: &str) -> usize {
+        self.state
+            .lock()
+            .unwrap()
+            .received
+            .iter()
+            .filter(|msg| matches!(msg.msg_type, IPCMessageType::Request) && msg.event == event)
+            .count()
+    }
+}
+
+/// Record `msg` and answer it according to the policy
+fn respond(state: &Mutex<MockState>, msg: IPCMessage, replier: &Replier) {
+    let mut state = state.lock().unwrap();
+    state.received.push(msg.clone());
+
+    let id = match (&msg.msg_type, &msg.id) {
+        (IPCMessageType::Request, Some(id)) => id.clone(),
+        _ => return,
+    };
+    if state.drop_remaining > 0 {
+        state.drop_remaining -= 1;
+        return;
+    }
+
+    let reply = match state.errors.get(&msg.event) {
+        Some(error) => IPCMessage::error_response(&id, &msg.event, error),
+        None => IPCMessage::response(&id, &msg.event, msg.payload),
+    };
+    let delay = state.delay;
+    drop(state);
+
+    if delay.is_zero() {
+        replier.send(&reply);
+    } else {
+        let replier = replier.clone();
+        thread::spawn(move || {
+            thread::sleep(delay);
+            replier.send(&reply);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipc::IPCError;
+    use serde_json::json;
+
+    const TIMEOUT: Duration = Duration::from_millis(500);
+
+    #[test]
+    fn test_dropped_requests_time_out_then_succeed() {
+        let bridge = IPCBridge::new();
+        let backend = MockBackend::new().with_dropped_requests(1);
+        backend.connect(&bridge);
+
+        let first = bridge.request_blocking("status", json!({"n": 1}), Duration::from_millis(100));
+        assert!(matches!(first, Err(IPCError::Timeout(_))));
+        assert_eq!(bridge.request_blocking("status", json!({"n": 2}), TIMEOUT).unwrap(), json!({"n": 2}));
+        assert_eq!(backend.request_count("status"), 2);
+    }
+
+    #[test]
+    fn test_failing_event_gets_error_response() {
+        let bridge = IPCBridge::new();
+        let backend = MockBackend::new().with_error("save", "disk full");
+        backend.connect(&bridge);
+
+        let err = bridge.request_blocking("save", json!({}), TIMEOUT).unwrap_err();
+        assert!(err.to_string().contains("disk full"));
+        assert!(bridge.request_blocking("load", json!(1), TIMEOUT).is_ok());
+    }
+
+    #[test]
+    fn test_delayed_responses_outlast_short_timeouts() {
+        let bridge = IPCBridge::new();
+        let backend = MockBackend::new().with_delay(Duration::from_millis(200));
+        backend.connect(&bridge);
+
+        let short = bridge.request_blocking("status", json!({}), Duration::from_millis(50));
+        assert!(matches!(short, Err(IPCError::Timeout(_))));
+        assert!(bridge.request_blocking("status", json!({}), TIMEOUT).is_ok());
+
+        bridge.emit("ping", json!({})).unwrap();
+        let events: Vec<String> = backend.received().into_iter().map(|msg| msg.event).collect();
+        assert_eq!(events, vec!["status", "status", "ping"]);
+    }
+}
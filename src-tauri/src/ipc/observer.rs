@@ -0,0 +1,30 @@
+/**
+ * IPC Observer
+ *
+ * A programmatic tap into the message lifecycle, for applications that route
+ * IPC activity into their own telemetry or tracing instead of parsing `log`
+ * output. The bridge keeps logging as before; an observer is told about the
+ * same points in addition.
+ *
+ * Hooks run synchronously on the thread where the event happens (a sending
+ * thread, the stdout listener or the timeout checker) and should return
+ * quickly. `on_send` runs while the bridge holds its stdin lock, so it must
+ * not send through the bridge.
+ */
+
+use super::IPCMessage;
+
+/// Receives IPC lifecycle events; every hook defaults to doing nothing
+pub trait IpcObserver: Send + Sync {
+    /// A message was written to Node.js
+    fn on_send(&self, _msg: &IPCMessage) {}
+
+    /// A message from Node.js was parsed, before it is dispatched
+    fn on_receive(&self, _msg: &IPCMessage) {}
+
+    /// A request timed out without a response
+    fn on_timeout(&self, _id: &str, _event: &str) {}
+
+    /// Output from Node.js could not be parsed as a message
+    fn on_parse_error(&self, _raw: &[u8], _error: &str) {}
+}
@@ -0,0 +1,170 @@
+/**
+ * IPC Outbound Queue
+ *
+ * Buffers messages while Node.js stdin is unavailable. Messages are drained
+ * by priority (High, then Normal, then Low) and in FIFO order within a level.
+ */
+
+use std::collections::VecDeque;
+
+/// Delivery priority of an outbound message
+///
+/// Only affects messages that are buffered; a message written straight to
+/// stdin is sent immediately regardless of its priority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Priority {
+    /// Interactive work, e.g. autocomplete
+    High,
+    #[default]
+    Normal,
+    /// Background work, e.g. reindexing
+    Low,
+}
+
+impl Priority {
+    const ALL: [Priority; 3] = [Priority::High, Priority::Normal, Priority::Low];
+
+    fn index(self) -> usize {
+        match self {
+            Priority::High => 0,
+            Priority::Normal => 1,
+            Priority::Low => 2,
+        }
+    }
+}
+
+/// FIFO queue per priority level
+#[derive(Debug)]
+pub struct PriorityQueue<T> {
+    levels: [VecDeque<T>; 3],
+}
+
+impl<T> PriorityQueue<T> {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        PriorityQueue {
+            levels: [VecDeque::new(), VecDeque::new(), VecDeque::new()],
+        }
+    }
+
+    /// Append an item behind the others of the same priority
+    pub fn push_back(&mut self, priority: Priority, item: T) {
+        self.levels[priority.index()].push_back(item);
+    }
+
+    /// Put an item back in front of the others of the same priority
+    pub fn push_front(&mut self, priority: Priority, item: T) {
+        self.levels[priority.index()].push_front(item);
+    }
+
+    /// Take the next item to send: the oldest of the highest priority
+    pub fn pop_front(&mut self) -> Option<(Priority, T)> {
+        Priority::ALL
+            .into_iter()
+            .find_map(|priority| self.levels[priority.index()].pop_front().map(|item| (priority, item)))
+    }
+
+    /// Take the item that matters least: the oldest of the lowest priority
+    pub fn pop_lowest(&mut self) -> Option<(Priority, T)> {
+        Priority::ALL
+            .into_iter()
+            .rev()
+            .find_map(|priority| self.levels[priority.index()].pop_front().map(|item| (priority, item)))
+    }
+
+    /// Number of queued items across all levels
+    pub fn len(&self) -> usize {
+        self.levels.iter().map(VecDeque::len).sum()
+    }
+
+    /// Whether nothing is queued
+    pub fn is_empty(&self) -> bool {
+        self.levels.iter().all(VecDeque::is_empty)
+    }
+
+    /// Iterate in drain order
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.levels.iter().flatten()
+    }
+
+    /// Remove the first item, in drain order, that matches `pred`
+    pub fn remove_first<F: FnMut(&T) -> bool>(&mut self, mut pred: F) -> Option<T> {
+        self.levels
+            .iter_mut()
+            .find_map(|level| level.iter().position(&mut pred).and_then(|index| level.remove(index)))
+    }
+
+    /// Remove every item, returning them in drain order
+    pub fn clear(&mut self) -> Vec<T> {
+        self.levels.iter_mut().flat_map(|level| level.drain(..)).collect()
+    }
+}
+
+impl<T> Default for PriorityQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drains_by_priority_then_fifo() {
+        let mut queue = PriorityQueue::new();
+        queue.push_back(Priority::Low, "reindex-1");
+        queue.push_back(Priority::Normal, "save");
+        queue.push_back(Priority::High, "complete-1");
+        queue.push_back(Priority::Low, "reindex-2");
+        queue.push_back(Priority::High, "complete-2");
+
+        assert_eq!(queue.len(), 5);
+        assert_eq!(
+            queue.iter().copied().collect::<Vec<_>>(),
+            vec!["complete-1", "complete-2", "save", "reindex-1", "reindex-2"]
+        );
+
+        let mut drained = Vec::new();
+        while let Some((_, item)) = queue.pop_front() {
+            drained.push(item);
+        }
+        assert_eq!(drained, vec!["complete-1", "complete-2", "save", "reindex-1", "reindex-2"]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_push_front_and_pop_lowest() {
+        let mut queue = PriorityQueue::new();
+        queue.push_back(Priority::Normal, 2);
+        queue.push_front(Priority::Normal, 1);
+        queue.push_back(Priority::Low, 3);
+
+        assert_eq!(queue.pop_lowest(), Some((Priority::Low, 3)));
+        assert_eq!(queue.pop_lowest(), Some((Priority::Normal, 1)));
+        assert_eq!(queue.pop_front(), Some((Priority::Normal, 2)));
+        assert_eq!(queue.pop_front(), None);
+    }
+
+    #[test]
+    fn test_clear_returns_items_in_drain_order() {
+        let mut queue = PriorityQueue::new();
+        queue.push_back(Priority::Low, "reindex");
+        queue.push_back(Priority::High, "complete");
+
+        assert_eq!(queue.clear(), vec!["complete", "reindex"]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_remove_first_takes_one_match() {
+        let mut queue = PriorityQueue::new();
+        queue.push_back(Priority::Low, "scan");
+        queue.push_back(Priority::High, "scan");
+        queue.push_back(Priority::Normal, "save");
+
+        assert_eq!(queue.remove_first(|item| *item == "scan"), Some("scan"));
+        assert_eq!(queue.iter().copied().collect::<Vec<_>>(), vec!["save", "scan"]);
+        assert_eq!(queue.remove_first(|item| *item == "missing"), None);
+    }
+}
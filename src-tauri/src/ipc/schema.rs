@@ -0,0 +1,241 @@
+/**
+ * Payload Schema Validation
+ *
+ * Lightweight validator for the subset of JSON Schema used to describe
+ * IPC payloads. Schemas are registered per event on the `IPCBridge` and
+ * checked before a message is written to Node.js stdin.
+ *
+ * Supported keywords:
+ * - `type` (string or array of strings), `enum`, `const`
+ * - `properties`, `required`, `additionalProperties`
+ * - `items`, `minItems`, `maxItems`
+ * - `minLength`, `maxLength`, `minimum`, `maximum`
+ *
+ * Unknown keywords are ignored, so richer schemas degrade gracefully.
+ */
+
+use serde_json::Value;
+
+/// A payload that does not match its schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaError {
+    /// Location of the offending value, e.g. `$.files[2].path`
+    pub path: String,
+    /// What was wrong with it
+    pub message: String,
+}
+
+impl std::fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+/// Validate a value against a schema
+///
+/// # Arguments
+/// * `schema` - JSON Schema (object or boolean)
+/// * `instance` - Value to check
+///
+/// # Returns
+/// * `Ok(())` - The value matches
+/// * `Err(SchemaError)` - The first mismatch found
+pub fn validate(schema: &Value, instance: &Value) -> Result<(), SchemaError> {
+    validate_at(schema, instance, "$")
+}
+
+fn fail(path: &str, message: String) -> Result<(), SchemaError> {
+    Err(SchemaError {
+        path: path.to_string(),
+        message,
+    })
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn matches_type(expected: &str, value: &Value) -> bool {
+    match expected {
+        "integer" => value.is_i64()
+            || value.is_u64()
+            || value.as_f64().map(|f| f.fract() == 0.0).unwrap_or(false),
+        other => type_name(value) == other,
+    }
+}
+
+fn validate_at(schema: &Value, instance: &Value, path: &str) -> Result<(), SchemaError> {
+    let schema = match schema {
+        Value::Bool(true) => return Ok(()),
+        Value::Bool(false) => return fail(path, "no value is allowed here".to_string()),
+        Value::Object(schema) => schema,
+        _ => return Ok(()),
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| matches_type(t, instance)) {
+            return fail(path, format!("expected {}, got {}", allowed.join(" or "), type_name(instance)));
+        }
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        if !options.contains(instance) {
+            return fail(path, format!("value {} is not one of the allowed values", instance));
+        }
+    }
+
+    if let Some(expected) = schema.get("const") {
+        if expected != instance {
+            return fail(path, format!("expected {}", expected));
+        }
+    }
+
+    match instance {
+        Value::Object(object) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for key in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(key) {
+                        return fail(path, format!("missing required property '{}'", key));
+                    }
+                }
+            }
+
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (key, value) in object {
+                let child_path = format!("{}.{}", path, key);
+                match properties.and_then(|p| p.get(key)) {
+                    Some(property_schema) => validate_at(property_schema, value, &child_path)?,
+                    None => {
+                        if let Some(additional) = schema.get("additionalProperties") {
+                            if additional == &Value::Bool(false) {
+                                return fail(&child_path, "property is not allowed".to_string());
+                            }
+                            validate_at(additional, value, &child_path)?;
+                        }
+                    }
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+                if (items.len() as u64) < min {
+                    return fail(path, format!("expected at least {} items, got {}", min, items.len()));
+                }
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+                if items.len() as u64 > max {
+                    return fail(path, format!("expected at most {} items, got {}", max, items.len()));
+                }
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate_at(item_schema, item, &format!("{}[{}]", path, i))?;
+                }
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                if len < min {
+                    return fail(path, format!("expected at least {} characters, got {}", min, len));
+                }
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                if len > max {
+                    return fail(path, format!("expected at most {} characters, got {}", max, len));
+                }
+            }
+        }
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or(0.0);
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+                if n < min {
+                    return fail(path, format!("{} is less than the minimum of {}", n, min));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+                if n > max {
+                    return fail(path, format!("{} is greater than the maximum of {}", n, max));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn file_schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["path"],
+            "properties": {
+                "path": {"type": "string", "minLength": 1},
+                "mode": {"enum": ["read", "write"]},
+                "tags": {"type": "array", "items": {"type": "string"}, "maxItems": 3},
+                "size": {"type": "integer", "minimum": 0}
+            },
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn test_valid_payload() {
+        let payload = json!({"path": "/tmp/a", "mode": "read", "tags": ["x"], "size": 3});
+        assert!(validate(&file_schema(), &payload).is_ok());
+    }
+
+    #[test]
+    fn test_missing_required_property() {
+        let err = validate(&file_schema(), &json!({"mode": "read"})).unwrap_err();
+        assert_eq!(err.path, "$");
+        assert!(err.message.contains("path"));
+    }
+
+    #[test]
+    fn test_nested_path_in_error() {
+        let payload = json!({"path": "/tmp/a", "tags": ["x", 5]});
+        let err = validate(&file_schema(), &payload).unwrap_err();
+        assert_eq!(err.path, "$.tags[1]");
+        assert_eq!(err.message, "expected string, got number");
+    }
+
+    #[test]
+    fn test_additional_properties_rejected() {
+        let err = validate(&file_schema(), &json!({"path": "a", "extra": true})).unwrap_err();
+        assert_eq!(err.path, "$.extra");
+    }
+
+    #[test]
+    fn test_enum_and_bounds() {
+        assert!(validate(&file_schema(), &json!({"path": "a", "mode": "delete"})).is_err());
+        assert!(validate(&file_schema(), &json!({"path": "a", "size": -1})).is_err());
+        assert!(validate(&file_schema(), &json!({"path": "a", "size": 1.5})).is_err());
+        assert!(validate(&file_schema(), &json!({"path": ""})).is_err());
+    }
+
+    #[test]
+    fn test_boolean_schemas() {
+        assert!(validate(&json!(true), &json!({"anything": 1})).is_ok());
+        assert!(validate(&json!(false), &json!(null)).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+/**
+ * IPC Transports
+ *
+ * The bridge speaks its framing over any byte stream. A `Transport` hands it
+ * the two halves of a connection to Node.js:
+ * - `StdioTransport`: the child's stdout/stdin (the default setup)
+ * - `UnixSocketTransport`: a Unix domain socket, leaving stdout free for logs,
+ *   so a stray `console.log` in the backend cannot corrupt the IPC stream
+ * - `NamedPipeTransport`: the Windows counterpart, a pair of named pipes
+ *
+ * The halves are driven independently: the listener thread blocks reading
+ * while other threads send, so a transport is split rather than exposing
+ * `send`/`recv` on one object. The transport is chosen when the backend is
+ * up, by passing it to `IPCBridge::connect`.
+ */
+
+use std::io::{self, Read, Write};
+
+/// A connection to Node.js the bridge can receive from and send to
+pub trait Transport {
+    /// Half the bridge receives messages from
+    type Reader: Read + Send + 'static;
+    /// Half the bridge sends messages to
+    type Writer: Write + Send + 'static;
+
+    /// Split the connection into its receiving and sending halves
+    fn split(self) -> io::Result<(Self::Reader, Self::Writer)>;
+}
+
+/// Transport over a pair of pipes, normally the child's stdout and stdin
+pub struct StdioTransport<R, W> {
+    reader: R,
+    writer: W,
+}
+
+impl<R, W> StdioTransport<R, W> {
+    /// Receive from `stdout` and send to `stdin`
+    pub fn new(stdout: R, stdin: W) -> Self {
+        StdioTransport { reader: stdout, writer: stdin }
+    }
+}
+
+impl<R, W> Transport for StdioTransport<R, W>
+where
+    R: Read + Send + 'static,
+    W: Write + Send + 'static,
+{
+    type Reader = R;
+    type Writer = W;
+
+    fn split(self) -> io::Result<(R, W)> {
+        Ok((self.reader, self.writer))
+    }
+}
+
+/// Transport over a Unix domain socket
+///
+/// Typically Rust binds a `UnixListener`, passes its path to the backend
+/// (e.g. in an environment variable) and accepts the backend's connection.
+#[cfg(unix)]
+pub struct UnixSocketTransport {
+    stream: std::os::unix::net::UnixStream,
+}
+
+#[cfg(unix)]
+impl UnixSocketTransport {
+    /// Wrap an already connected stream
+    pub fn new(stream: std::os::unix::net::UnixStream) -> Self {
+        UnixSocketTransport { stream }
+    }
+
+    /// Connect to a socket the backend is listening on
+    pub fn connect<P: AsRef<std::path::Path>>(path: P) -> io::Result<Self> {
+        std::os::unix::net::UnixStream::connect(path).map(Self::new)
+    }
+
+    /// Wait for the backend to connect to `listener`
+    pub fn accept(listener: &std::os::unix::net::UnixListener) -> io::Result<Self> {
+        listener.accept().map(|(stream, _)| Self::new(stream))
+    }
+}
+
+#[cfg(unix)]
+impl Transport for UnixSocketTransport {
+    type Reader = std::os::unix::net::UnixStream;
+    type Writer = std::os::unix::net::UnixStream;
+
+    fn split(self) -> io::Result<(Self::Reader, Self::Writer)> {
+        let reader = self.stream.try_clone()?;
+        Ok((reader, self.stream))
+    }
+}
+
+/// Transport over Windows named pipes
+///
+/// The backend creates two pipe servers, `\\.\pipe\<name>-in` for the
+/// messages it receives and `\\.\pipe\<name>-out` for those it sends, and
+/// Rust connects to both as a client. Two pipes are needed because reads and
+/// writes on one synchronous pipe handle are serialized, so the blocked
+/// listener would hold up every send.
+#[cfg(windows)]
+pub struct NamedPipeTransport {
+    reader: std::fs::File,
+    writer: std::fs::File,
+}
+
+#[cfg(windows)]
+impl NamedPipeTransport {
+    /// Connect to the pipes named `name` the backend is listening on
+    pub fn connect(name: &str) -> io::Result<Self> {
+        let writer = std::fs::OpenOptions::new().write(true).open(Self::pipe_path(name, "in"))?;
+        let reader = std::fs::OpenOptions::new().read(true).open(Self::pipe_path(name, "out"))?;
+        Ok(NamedPipeTransport { reader, writer })
+    }
+
+    /// Full path of one of the pipes, e.g. `\\.\pipe\cowork-in`
+    fn pipe_path(name: &str, direction: &str) -> String {
+        format!(r"\\.\pipe\{}-{}", name, direction)
+    }
+}
+
+#[cfg(windows)]
+impl Transport for NamedPipeTransport {
+    type Reader = std::fs::File;
+    type Writer = std::fs::File;
+
+    fn split(self) -> io::Result<(Self::Reader, Self::Writer)> {
+        Ok((self.reader, self.writer))
+    }
+}
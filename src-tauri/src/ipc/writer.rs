@@ -0,0 +1,274 @@
+/**
+ * Background Writer
+ *
+ * Writing to a pipe blocks once the pipe buffer is full, i.e. when the
+ * backend stops reading. Since the bridge writes under its stdin lock, one
+ * wedged write would stall every sending thread. `ThreadedWriter` hands the
+ * bytes to a dedicated thread instead, so writes return immediately:
+ * - at most `capacity` writes wait for the thread; beyond that `write` fails
+ *   with `WouldBlock` and the caller keeps the bytes
+ * - write errors on the thread are reported by the next `write`, and every
+ *   buffer the thread did not write is handed back through the unwritten callback
+ * - a write or flush that takes longer than the stall timeout is reported
+ *   once through the stall callback, e.g. so a supervisor can restart the backend
+ */
+
+use log::{debug, warn};
+use std::io::{self, ErrorKind, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the watchdog checks for a stalled write
+const STALL_POLL_INTERVAL_MS: u64 = 50;
+
+/// Callback told how long the writer thread has been stuck
+pub type StallCallback = Arc<dyn Fn(Duration) + Send + Sync + 'static>;
+
+/// Callback given the buffers left unwritten when the writer thread fails, in order
+pub type UnwrittenCallback = Box<dyn FnOnce(Vec<Vec<u8>>) + Send + 'static>;
+
+/// State shared between the writer handle, its thread and the watchdog
+#[derive(Default)]
+struct WriterState {
+    /// Error that ended the writer thread, reported to later writes
+    error: Mutex<Option<(ErrorKind, String)>>,
+    /// When the current write started, while one is in progress
+    busy_since: Mutex<Option<Instant>>,
+    /// Set once the writer thread has exited
+    done: AtomicBool,
+}
+
+/// Writer that performs the actual writes on a dedicated thread
+pub struct ThreadedWriter {
+    tx: mpsc::SyncSender<Vec<u8>>,
+    state: Arc<WriterState>,
+}
+
+impl ThreadedWriter {
+    /// Move `writer` to a new thread, buffering up to `capacity` writes and
+    /// reporting writes stuck for `stall_timeout`
+    pub fn spawn<W>(
+        mut writer: W,
+        capacity: usize,
+        stall_timeout: Duration,
+        on_stall: Option<StallCallback>,
+        on_unwritten: Option<UnwrittenCallback>,
+    ) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        let (tx, rx) = mpsc::sync_channel::<Vec<u8>>(capacity.max(1));
+        let state = Arc::new(WriterState::default());
+
+        let thread_state = Arc::clone(&state);
+        thread::spawn(move || {
+            while let Ok(bytes) = rx.recv() {
+                *thread_state.busy_since.lock().unwrap() = Some(Instant::now());
+                let result = writer.write_all(&bytes).and_then(|_| writer.flush());
+                *thread_state.busy_since.lock().unwrap() = None;
+                if let Err(e) = result {
+                    warn!("Writer thread failed: {}", e);
+                    // Once the error is set `write` accepts nothing, so the drain below is complete
+                    *thread_state.error.lock().unwrap() = Some((e.kind(), e.to_string()));
+                    let mut unwritten = vec![bytes];
+                    unwritten.extend(rx.try_iter());
+                    debug!("Handing back {} unwritten buffer(s)", unwritten.len());
+                    if let Some(on_unwritten) = on_unwritten {
+                        on_unwritten(unwritten);
+                    }
+                    break;
+                }
+            }
+            thread_state.done.store(true, Ordering::SeqCst);
+            debug!("Writer thread stopped");
+        });
+
+        if let Some(on_stall) = on_stall {
+            let watchdog_state = Arc::clone(&state);
+            thread::spawn(move || {
+                // Start of the stall already reported, so each stall fires once
+                let mut reported: Option<Instant> = None;
+                while !watchdog_state.done.load(Ordering::SeqCst) {
+                    thread::sleep(Duration::from_millis(STALL_POLL_INTERVAL_MS));
+                    let busy_since = *watchdog_state.busy_since.lock().unwrap();
+                    if let Some(since) = busy_since {
+                        if since.elapsed() >= stall_timeout && reported != Some(since) {
+                            warn!("Write to backend stalled for {:?}", since.elapsed());
+                            reported = Some(since);
+                            on_stall(since.elapsed());
+                        }
+                    }
+                }
+            });
+        }
+
+        ThreadedWriter { tx, state }
+    }
+
+    /// Whether a write has been in progress for at least `threshold`
+    pub fn is_stalled(&self, threshold: Duration) -> bool {
+        self.state
+            .busy_since
+            .lock()
+            .unwrap()
+            .map(|since| since.elapsed() >= threshold)
+            .unwrap_or(false)
+    }
+}
+
+impl Write for ThreadedWriter {
+    /// Hand `buf` to the writer thread as a whole, or fail without taking any of it
+    ///
+    /// Fails with `WouldBlock` while `capacity` writes are waiting.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Held across the send so the thread cannot fail in between and miss this buffer
+        let error = self.state.error.lock().unwrap();
+        if let Some((kind, msg)) = error.as_ref() {
+            return Err(io::Error::new(*kind, msg.clone()));
+        }
+        self.tx.try_send(buf.to_vec()).map_err(|e| match e {
+            mpsc::TrySendError::Full(_) => io::Error::new(ErrorKind::WouldBlock, "writer queue is full"),
+            mpsc::TrySendError::Disconnected(_) => io::Error::new(ErrorKind::BrokenPipe, "writer thread has stopped"),
+        })?;
+        Ok(buf.len())
+    }
+
+    /// Returns immediately; the writer thread flushes after every write
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writer that blocks until released, like a full pipe
+    struct BlockingWriter {
+        release: mpsc::Receiver<()>,
+        written: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Write for BlockingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let _ = self.release.recv();
+            self.written.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_wedged_write_does_not_block_caller() {
+        let (release_tx, release_rx) = mpsc::channel();
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let (stall_tx, stall_rx) = mpsc::channel();
+        let mut writer = ThreadedWriter::spawn(
+            BlockingWriter { release: release_rx, written: Arc::clone(&written) },
+            8,
+            Duration::from_millis(100),
+            Some(Arc::new(move |stuck| {
+                let _ = stall_tx.send(stuck);
+            })),
+            None,
+        );
+
+        writer.write_all(b"one\n").unwrap();
+        writer.write_all(b"two\n").unwrap();
+        writer.flush().unwrap();
+
+        let stuck = stall_rx.recv_timeout(Duration::from_secs(2)).expect("stall should be reported");
+        assert!(stuck >= Duration::from_millis(100));
+        assert!(writer.is_stalled(Duration::from_millis(100)));
+        assert!(written.lock().unwrap().is_empty());
+
+        release_tx.send(()).unwrap();
+        release_tx.send(()).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while written.lock().unwrap().len() < 8 {
+            assert!(Instant::now() < deadline, "writes should complete once released");
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(written.lock().unwrap().as_slice(), b"one\ntwo\n");
+    }
+
+    /// Writer that always fails like a closed pipe
+    struct ClosedPipe;
+
+    impl Write for ClosedPipe {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(ErrorKind::BrokenPipe, "broken pipe"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_full_queue_rejects_write() {
+        let (release_tx, release_rx) = mpsc::channel();
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let mut writer = ThreadedWriter::spawn(
+            BlockingWriter { release: release_rx, written: Arc::clone(&written) },
+            1,
+            Duration::from_secs(5),
+            None,
+            None,
+        );
+
+        // The thread takes the first buffer and blocks; the second fills the queue
+        writer.write_all(b"one\n").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while !writer.is_stalled(Duration::ZERO) {
+            assert!(Instant::now() < deadline, "writer thread should pick up the first buffer");
+            thread::sleep(Duration::from_millis(10));
+        }
+        writer.write_all(b"two\n").unwrap();
+        let err = writer.write_all(b"three\n").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WouldBlock);
+
+        release_tx.send(()).unwrap();
+        release_tx.send(()).unwrap();
+    }
+
+    #[test]
+    fn test_write_error_reported_on_next_write() {
+        let (unwritten_tx, unwritten_rx) = mpsc::channel();
+        let mut writer = ThreadedWriter::spawn(
+            ClosedPipe,
+            8,
+            Duration::from_secs(5),
+            None,
+            Some(Box::new(move |unwritten| {
+                let _ = unwritten_tx.send(unwritten);
+            })),
+        );
+        writer.write_all(b"lost\n").unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        loop {
+            match writer.write_all(b"next\n") {
+                Err(e) => {
+                    assert_eq!(e.kind(), ErrorKind::BrokenPipe);
+                    break;
+                }
+                Ok(()) => {
+                    assert!(Instant::now() < deadline, "write error should be reported");
+                    thread::sleep(Duration::from_millis(10));
+                }
+            }
+        }
+
+        // The failed buffer comes back first, followed by any accepted after it
+        let unwritten = unwritten_rx.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(unwritten[0], b"lost\n");
+        assert!(unwritten[1..].iter().all(|bytes| bytes == b"next\n"));
+    }
+}
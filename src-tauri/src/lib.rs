@@ -1,5 +1,6 @@
 pub mod process;
 pub mod ipc;
+pub mod backend;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -12,28 +12,251 @@
  * - 配置环境变量和工作目录
  * - 优雅关闭支持
  * - 健康检查机制
+ * - CPU 与内存使用监控
+ * - 空闲自动关闭，有流量时按需重启
+ * - 启动前检查 BACKEND_PORT 是否被占用
+ * - 输出写入按大小轮转的日志文件
  * - 详细的日志记录
  */
 
-use std::process::{Child, Command, Stdio};
+mod log_file;
+mod output;
+mod resource;
+mod supervisor;
+
+pub use resource::ResourceUsage;
+pub use supervisor::{FanOutMode, ProcessSupervisor};
+
+use log_file::LogFile;
+use output::RecentOutput;
+use resource::CpuSample;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, ErrorKind};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 use log::{info, error, warn, debug};
 
 const MAX_RESTART_ATTEMPTS: u32 = 5;
-const RESTART_COOLDOWN_SECS: u64 = 5;
+const RESTART_BASE_DELAY_SECS: u64 = 1;
+const RESTART_MAX_DELAY_SECS: u64 = 60;
+const RESTART_STABLE_WINDOW_SECS: u64 = 60;
 const HEALTH_CHECK_INTERVAL_SECS: u64 = 10;
+const DEFAULT_UNHEALTHY_THRESHOLD: u32 = 3;
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 3;
+const SHUTDOWN_POLL_INTERVAL_MS: u64 = 100;
+const EXIT_POLL_INTERVAL_MS: u64 = 50;
+const DEFAULT_NODE_PATH: &str = "node";
+const DEFAULT_BACKEND_PORT: &str = "3000";
+const DEFAULT_RECENT_OUTPUT_LINES: usize = 200;
+const IDLE_CHECKS_PER_WINDOW: u32 = 4;
+const MAX_IDLE_CHECK_INTERVAL_SECS: u64 = 1;
+
+/// When the monitor restarts an exited backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RestartPolicy {
+    /// Never restart; an exited backend stays down
+    Never,
+    /// Restart only after a non-zero exit (default)
+    #[default]
+    OnFailure,
+    /// Restart after any exit, including a clean exit with code 0
+    Always,
+}
+
+impl RestartPolicy {
+    /// Whether a backend that exited with the given success flag should be restarted
+    pub fn should_restart(&self, success: bool) -> bool {
+        match self {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure => !success,
+            RestartPolicy::Always => true,
+        }
+    }
+}
+
+/// What the monitor does after a non-clean exit, as decided by an exit classifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RestartDecision {
+    /// Restart after the backoff delay, until attempts run out (default)
+    #[default]
+    Restart,
+    /// Leave the backend down (e.g. a configuration error that will recur)
+    GiveUp,
+    /// Restart after this delay instead of the backoff delay
+    RestartAfter(Duration),
+}
+
+/// Backoff settings for restarting a crashed backend
+///
+/// The delay before restart attempt `n` is `base_delay * 2^n`, capped at
+/// `max_delay`, then randomly moved by up to `jitter` times itself in either
+/// direction. Once a restarted backend stays up for `stable_reset_after`,
+/// the attempt counter resets to zero.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RestartBackoff {
+    /// Delay before the first restart attempt
+    pub base_delay: Duration,
+    /// Upper bound for the restart delay, before jitter
+    pub max_delay: Duration,
+    /// Uptime after which the restart attempt counter is reset
+    pub stable_reset_after: Duration,
+    /// Random spread as a fraction of the delay (0.5 gives `delay ± delay/2`);
+    /// 0 (the default) keeps delays deterministic
+    pub jitter: f64,
+}
+
+impl RestartBackoff {
+    /// Create a new restart policy
+    pub fn new(base_delay: Duration, max_delay: Duration, stable_reset_after: Duration) -> Self {
+        RestartBackoff {
+            base_delay,
+            max_delay,
+            stable_reset_after,
+            jitter: 0.0,
+        }
+    }
+
+    /// Randomize each delay by up to `factor` times itself, clamped to 0..=1
+    ///
+    /// Desynchronizes backends that crash together (e.g. after a shared
+    /// resource hiccup) so their restarts do not collide again.
+    pub fn with_jitter(mut self, factor: f64) -> Self {
+        self.jitter = factor.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Delay before the given (zero-based) restart attempt
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt).unwrap_or(u32::MAX);
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        if self.jitter <= 0.0 {
+            return delay;
+        }
+        // Uniform in [-jitter, +jitter]
+        let offset = (random_unit() * 2.0 - 1.0) * self.jitter;
+        delay.mul_f64(1.0 + offset)
+    }
+}
+
+impl Default for RestartBackoff {
+    fn default() -> Self {
+        RestartBackoff::new(
+            Duration::from_secs(RESTART_BASE_DELAY_SECS),
+            Duration::from_secs(RESTART_MAX_DELAY_SECS),
+            Duration::from_secs(RESTART_STABLE_WINDOW_SECS),
+        )
+    }
+}
+
+/// How `shutdown_gracefully` ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownOutcome {
+    /// There was no backend process to stop
+    NotRunning,
+    /// The backend exited on its own within the timeout, with this exit code
+    /// (`None` if it was terminated by a signal)
+    Exited(Option<i32>),
+    /// The backend did not exit in time and was force-killed
+    Killed,
+}
+
+/// Signal that can be sent to the backend with `ProcessManager::signal`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// SIGHUP, conventionally "reload configuration"
+    Hup,
+    /// SIGINT, as from Ctrl+C
+    Int,
+    /// SIGTERM, a polite request to exit
+    Term,
+    /// SIGKILL, cannot be handled
+    Kill,
+    /// SIGUSR1, application defined (e.g. rotate logs)
+    Usr1,
+    /// SIGUSR2, application defined
+    Usr2,
+}
+
+impl Signal {
+    /// Signal name as accepted by `kill -<NAME>`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Signal::Hup => "HUP",
+            Signal::Int => "INT",
+            Signal::Term => "TERM",
+            Signal::Kill => "KILL",
+            Signal::Usr1 => "USR1",
+            Signal::Usr2 => "USR2",
+        }
+    }
+}
+
+/// Callback receiving a single line of backend output
+type LineCallback = Box<dyn Fn(String) + Send + 'static>;
+
+/// Callback invoked with a freshly restarted child
+type RestartCallback = Box<dyn Fn(&mut Child) + Send + 'static>;
+
+/// Callback invoked with the status of an exited child and its recent output
+type ExitCallback = Box<dyn Fn(ExitStatus, &[String]) + Send + 'static>;
+
+/// Classifier deciding how to handle a non-clean exit
+type ExitClassifier = Arc<dyn Fn(ExitStatus) -> RestartDecision + Send + Sync + 'static>;
+
+/// Callback invoked before the backend is asked to shut down
+type ShutdownCallback = Box<dyn Fn() + Send + 'static>;
 
 /// Process manager for Node.js backend
 pub struct ProcessManager {
     child: Arc<Mutex<Option<Child>>>,
-    backend_script: String,
+    /// Script and node arguments, replaceable at runtime with `update_backend`
+    backend: Arc<Mutex<BackendCommand>>,
+    /// Serializes replacing the child between the restart monitor, idle
+    /// wake-ups and `update_backend`
+    restart_lock: Arc<Mutex<()>>,
     working_dir: String,
-    #[allow(dead_code)]
-    auto_restart: bool,
+    /// Path to the node binary
+    node_path: String,
+    /// Extra environment variables, overriding the defaults
+    env: HashMap<String, String>,
+    restart_policy: RestartPolicy,
     restart_attempts: Arc<Mutex<u32>>,
     last_restart: Arc<Mutex<Option<Instant>>>,
+    restart_backoff: RestartBackoff,
+    /// Consulted on each non-clean exit the restart policy would restart
+    exit_classifier: ExitClassifier,
+    stderr_callback: Arc<Mutex<Option<LineCallback>>>,
+    restart_callback: Arc<Mutex<Option<RestartCallback>>>,
+    exit_callback: Arc<Mutex<Option<ExitCallback>>>,
+    shutdown_callback: Arc<Mutex<Option<ShutdownCallback>>>,
+    /// Previous CPU reading for `resource_usage`
+    cpu_sample: Arc<Mutex<Option<CpuSample>>>,
+    /// Bumped by `shutdown_gracefully` so monitor threads started earlier exit
+    monitor_generation: Arc<AtomicU64>,
+    /// How long `shutdown_gracefully` waits before force-killing
+    shutdown_timeout: Duration,
+    /// Number of live `restart_on_crash` monitor threads
+    active_monitors: Arc<AtomicUsize>,
+    /// Consecutive failed health probes before `on_unhealthy` fires
+    unhealthy_threshold: u32,
+    /// Most recent stdout/stderr lines, for crash diagnostics
+    recent_output: Arc<Mutex<RecentOutput>>,
+    /// Inactivity after which the idle monitor stops the backend
+    idle_shutdown: Option<Duration>,
+    /// Last IPC traffic reported through `activity_recorder`
+    last_activity: Arc<Mutex<Instant>>,
+    /// Set while the backend is stopped for inactivity
+    idle_stopped: Arc<AtomicBool>,
+    idle_shutdown_callback: Arc<Mutex<Option<ShutdownCallback>>>,
+    /// Pick a free port when `BACKEND_PORT` is taken, instead of failing
+    port_fallback: bool,
+    /// Rotating file receiving a copy of the backend's output
+    log_file: Option<LogFile>,
 }
 
 impl ProcessManager {
@@ -42,34 +265,231 @@ impl ProcessManager {
         info!("Creating ProcessManager for script: {} in directory: {}", backend_script, working_dir);
         ProcessManager {
             child: Arc::new(Mutex::new(None)),
-            backend_script,
+            backend: Arc::new(Mutex::new(BackendCommand {
+                script: backend_script,
+                node_args: Vec::new(),
+            })),
+            restart_lock: Arc::new(Mutex::new(())),
             working_dir,
-            auto_restart: true,
+            node_path: DEFAULT_NODE_PATH.to_string(),
+            env: HashMap::new(),
+            restart_policy: RestartPolicy::default(),
             restart_attempts: Arc::new(Mutex::new(0)),
             last_restart: Arc::new(Mutex::new(None)),
+            restart_backoff: RestartBackoff::default(),
+            exit_classifier: Arc::new(|_| RestartDecision::Restart),
+            stderr_callback: Arc::new(Mutex::new(None)),
+            restart_callback: Arc::new(Mutex::new(None)),
+            exit_callback: Arc::new(Mutex::new(None)),
+            shutdown_callback: Arc::new(Mutex::new(None)),
+            cpu_sample: Arc::new(Mutex::new(None)),
+            monitor_generation: Arc::new(AtomicU64::new(0)),
+            shutdown_timeout: Duration::from_secs(DEFAULT_SHUTDOWN_TIMEOUT_SECS),
+            active_monitors: Arc::new(AtomicUsize::new(0)),
+            unhealthy_threshold: DEFAULT_UNHEALTHY_THRESHOLD,
+            recent_output: Arc::new(Mutex::new(RecentOutput::new(DEFAULT_RECENT_OUTPUT_LINES))),
+            idle_shutdown: None,
+            last_activity: Arc::new(Mutex::new(Instant::now())),
+            idle_stopped: Arc::new(AtomicBool::new(false)),
+            idle_shutdown_callback: Arc::new(Mutex::new(None)),
+            port_fallback: false,
+            log_file: None,
+        }
+    }
+
+    /// Use a specific node binary instead of `node` from PATH
+    pub fn with_node_path(mut self, path: String) -> Self {
+        self.node_path = path;
+        self
+    }
+
+    /// Pass extra arguments to node, placed before the backend script
+    /// (e.g. `--max-old-space-size=4096`)
+    pub fn with_node_args(self, args: Vec<String>) -> Self {
+        self.backend.lock().unwrap().node_args = args;
+        self
+    }
+
+    /// Set extra environment variables for the backend process
+    ///
+    /// Applied on every spawn, including restarts. `NODE_ENV` and
+    /// `BACKEND_PORT` keep their defaults unless overridden here.
+    pub fn with_env(mut self, env: HashMap<String, String>) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Start the backend on a free port when `BACKEND_PORT` is already in use
+    ///
+    /// The chosen port is passed to the backend through `BACKEND_PORT` and
+    /// kept for restarts; see `backend_port`. Without this, `start_node_backend`
+    /// fails when the port is taken.
+    pub fn with_port_fallback(mut self) -> Self {
+        self.port_fallback = true;
+        self
+    }
+
+    /// Port the backend is told to listen on through `BACKEND_PORT`
+    ///
+    /// `None` if the configured value is not a port number.
+    pub fn backend_port(&self) -> Option<u16> {
+        resolve_backend_port(&self.env)
+    }
+
+    /// Set the backoff used by `restart_on_crash`
+    pub fn with_restart_backoff(mut self, backoff: RestartBackoff) -> Self {
+        self.restart_backoff = backoff;
+        self
+    }
+
+    /// Set how long a restarted backend must stay up before the restart
+    /// attempt counter resets to zero
+    pub fn with_stable_window(mut self, window: Duration) -> Self {
+        self.restart_backoff.stable_reset_after = window;
+        self
+    }
+
+    /// Decide per exit status whether and when a crashed backend is restarted
+    ///
+    /// Consulted by `restart_on_crash` on every non-clean exit that the
+    /// restart policy would restart, so backend-specific exit codes (e.g.
+    /// "config error, don't retry") can be honored. `RestartAfter` replaces
+    /// the backoff delay but still counts against the attempt limit. The
+    /// default always returns `RestartDecision::Restart`.
+    pub fn with_exit_classifier<F>(mut self, classifier: F) -> Self
+    where
+        F: Fn(ExitStatus) -> RestartDecision + Send + Sync + 'static,
+    {
+        self.exit_classifier = Arc::new(classifier);
+        self
+    }
+
+    /// Set when the monitor should restart an exited backend
+    pub fn with_restart_policy(mut self, policy: RestartPolicy) -> Self {
+        self.restart_policy = policy;
+        self
+    }
+
+    /// Set how long `shutdown_gracefully` waits for the backend to exit
+    /// before force-killing it
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Set how many consecutive failed health probes mark the backend unhealthy
+    pub fn with_unhealthy_threshold(mut self, threshold: u32) -> Self {
+        self.unhealthy_threshold = threshold.max(1);
+        self
+    }
+
+    /// Copy the backend's output lines to a log file rotated at `max_size` bytes
+    ///
+    /// Stderr is copied automatically; stdout lines are copied when fed
+    /// through `output_recorder`. Each line is tagged with its stream. Full
+    /// files are renamed to `<path>.1`, `<path>.2` and so on. Lines are
+    /// written on a separate thread, so the file never slows the IPC read path.
+    pub fn with_log_file(mut self, path: String, max_size: u64) -> Self {
+        self.log_file = Some(LogFile::spawn(PathBuf::from(path), max_size));
+        self
+    }
+
+    /// Set how many recent output lines are kept for `recent_output`
+    pub fn with_recent_output_lines(mut self, lines: usize) -> Self {
+        self.recent_output = Arc::new(Mutex::new(RecentOutput::new(lines)));
+        self
+    }
+
+    /// Stop the backend after `window` without IPC traffic
+    ///
+    /// Takes effect once `start_idle_monitor` is running. Traffic is reported
+    /// by the bridge through `activity_recorder`; the first message after an
+    /// idle shutdown starts the backend again and is queued by the bridge
+    /// until `on_restart` reconnects it:
+    ///
+    /// ```ignore
+    /// let pm = ProcessManager::new(script, dir).with_idle_shutdown(Duration::from_secs(300));
+    /// bridge.on_activity(pm.activity_recorder());
+    /// let idle_bridge = Arc::clone(&bridge);
+    /// pm.on_idle_shutdown(move || idle_bridge.clear_writer());
+    /// pm.on_restart(/* set_stdin + start_stdout_listener, as usual */);
+    /// pm.start_idle_monitor();
+    /// ```
+    pub fn with_idle_shutdown(mut self, window: Duration) -> Self {
+        self.idle_shutdown = Some(window);
+        self
+    }
+
+    /// Get the restart policy
+    pub fn restart_policy(&self) -> RestartPolicy {
+        self.restart_policy
+    }
+
+    /// Switch to a different backend script and node arguments
+    ///
+    /// A running backend is shut down gracefully and started again with the
+    /// new command, going through `on_restart` so the IPC bridge reconnects;
+    /// this is not counted as a crash. Otherwise the command is only stored
+    /// and used by the next start, restart or idle wake-up. Meant for
+    /// reloading a rebuilt backend bundle during development.
+    pub fn update_backend(&self, script: String, args: Vec<String>) -> Result<(), String> {
+        // Hold off the restart monitor and idle wake-ups until the new child is in place
+        let _restarting = self.restart_lock.lock().unwrap();
+        info!("Updating backend command to script: {} {:?}", script, args);
+        let backend = {
+            let mut backend = self.backend.lock().unwrap();
+            *backend = BackendCommand { script, node_args: args };
+            backend.clone()
+        };
+
+        let Some(mut old) = self.child.lock().unwrap().take() else {
+            debug!("No backend running, new command applies on next start");
+            return Ok(());
+        };
+        stop_child(&mut old, self.shutdown_timeout)?;
+
+        let mut process = build_command(&self.node_path, &backend, &self.working_dir, &self.env)
+            .spawn()
+            .map_err(|e| {
+                error!("Failed to start updated backend: {}", e);
+                format!("Failed to start backend: {}", e)
+            })?;
+        info!("Updated backend started with PID: {}", process.id());
+        drain_stderr(&mut process, Arc::clone(&self.stderr_callback), Arc::clone(&self.recent_output), self.log_file.clone());
+        if let Some(on_restart) = self.restart_callback.lock().unwrap().as_ref() {
+            on_restart(&mut process);
         }
+        *self.child.lock().unwrap() = Some(process);
+        *self.last_activity.lock().unwrap() = Instant::now();
+        Ok(())
     }
 
     /// Start the Node.js backend process
     pub fn start_node_backend(&mut self) -> Result<(), String> {
         info!("Starting Node.js backend process");
+        self.check_backend_port()?;
 
-        let child = Command::new("node")
-            .arg(&self.backend_script)
-            .current_dir(&self.working_dir)
-            .env("NODE_ENV", std::env::var("NODE_ENV").unwrap_or_else(|_| "production".to_string()))
-            .env("BACKEND_PORT", std::env::var("BACKEND_PORT").unwrap_or_else(|_| "3000".to_string()))
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
+        let backend = self.backend.lock().unwrap().clone();
+        // Node.js would start, fail to load the script and exit, which looks like a crash
+        let script = Path::new(&self.working_dir).join(&backend.script);
+        if !script.exists() {
+            error!("Backend script not found: {}", script.display());
+            return Err(format!("backend script not found: {}", script.display()));
+        }
+
+        let child = build_command(&self.node_path, &backend, &self.working_dir, &self.env)
             .spawn();
 
         match child {
-            Ok(process) => {
+            Ok(mut process) => {
                 let pid = process.id();
                 info!("Node.js backend started successfully with PID: {}", pid);
-                debug!("Process details - Script: {}, WorkDir: {}", self.backend_script, self.working_dir);
+                drain_stderr(&mut process, Arc::clone(&self.stderr_callback), Arc::clone(&self.recent_output), self.log_file.clone());
+                debug!("Process details - Node: {} {:?}, Script: {}, WorkDir: {}",
+                       self.node_path, backend.node_args, backend.script, self.working_dir);
                 *self.child.lock().unwrap() = Some(process);
+                *self.last_activity.lock().unwrap() = Instant::now();
+                self.idle_stopped.store(false, Ordering::SeqCst);
                 Ok(())
             }
             Err(e) => {
@@ -79,77 +499,171 @@ impl ProcessManager {
         }
     }
 
+    /// Make sure the backend's port is free before launching it
+    ///
+    /// A backend that cannot bind its port exits right away, and every
+    /// restart would fail the same way.
+    fn check_backend_port(&mut self) -> Result<(), String> {
+        let port = match self.backend_port() {
+            Some(port) if port != 0 => port,
+            _ => return Ok(()),
+        };
+        if !port_in_use(port) {
+            return Ok(());
+        }
+        if !self.port_fallback {
+            error!("Backend port {} is already in use", port);
+            return Err(format!("Failed to start backend: port {} is already in use (BACKEND_PORT)", port));
+        }
+
+        let free = free_port().map_err(|e| format!("Failed to find a free backend port: {}", e))?;
+        warn!("Backend port {} is already in use, using port {} instead", port, free);
+        self.env.insert("BACKEND_PORT".to_string(), free.to_string());
+        Ok(())
+    }
+
     /// Monitor process and restart on crash with exponential backoff
     pub fn restart_on_crash(&self) {
         let child_clone = Arc::clone(&self.child);
-        let backend_script = self.backend_script.clone();
+        let backend = Arc::clone(&self.backend);
+        let restart_lock = Arc::clone(&self.restart_lock);
         let working_dir = self.working_dir.clone();
+        let node_path = self.node_path.clone();
+        let env = self.env.clone();
         let restart_attempts = Arc::clone(&self.restart_attempts);
         let last_restart = Arc::clone(&self.last_restart);
+        let policy = self.restart_policy;
+        let backoff = self.restart_backoff;
+        let exit_classifier = Arc::clone(&self.exit_classifier);
+        let stderr_callback = Arc::clone(&self.stderr_callback);
+        let restart_callback = Arc::clone(&self.restart_callback);
+        let exit_callback = Arc::clone(&self.exit_callback);
+        let recent_output = Arc::clone(&self.recent_output);
+        let log_file = self.log_file.clone();
+        let monitor_generation = Arc::clone(&self.monitor_generation);
+        let generation = monitor_generation.load(Ordering::SeqCst);
+        let active_monitors = Arc::clone(&self.active_monitors);
+        active_monitors.fetch_add(1, Ordering::SeqCst);
 
         thread::spawn(move || {
+            // PID whose exit was last reported, so a dead child left in place
+            // after a failed restart is not reported again on the next poll
+            let mut reported_exit: Option<u32> = None;
+
             loop {
                 thread::sleep(Duration::from_secs(1));
+                if monitor_generation.load(Ordering::SeqCst) != generation {
+                    debug!("Restart monitor stopped");
+                    break;
+                }
 
                 let mut child_lock = child_clone.lock().unwrap();
                 if let Some(child) = child_lock.as_mut() {
                     match child.try_wait() {
                         Ok(Some(status)) => {
-                            if status.success() {
-                                info!("Backend exited normally with status code 0");
-                                *restart_attempts.lock().unwrap() = 0;
-                            } else {
-                                let attempts = *restart_attempts.lock().unwrap();
-                                warn!("Backend crashed with status: {}. Restart attempt: {}/{}",
-                                      status, attempts + 1, MAX_RESTART_ATTEMPTS);
+                            let pid = child.id();
+                            // Release lock for the callbacks, backoff and restart
+                            drop(child_lock);
 
-                                if attempts >= MAX_RESTART_ATTEMPTS {
-                                    error!("Maximum restart attempts ({}) reached. Giving up.", MAX_RESTART_ATTEMPTS);
-                                    break;
+                            if reported_exit != Some(pid) {
+                                reported_exit = Some(pid);
+                                // It may have been stable before crashing between polls
+                                reset_attempts_if_stable(&restart_attempts, &last_restart, backoff.stable_reset_after);
+                                if let Some(on_exit) = exit_callback.lock().unwrap().as_ref() {
+                                    let tail = recent_output.lock().unwrap().lines();
+                                    on_exit(status, &tail);
                                 }
+                            }
 
-                                // Check cooldown period
-                                if let Some(last) = *last_restart.lock().unwrap() {
-                                    let elapsed = last.elapsed().as_secs();
-                                    if elapsed < RESTART_COOLDOWN_SECS {
-                                        let wait_time = RESTART_COOLDOWN_SECS - elapsed;
-                                        info!("Waiting {} seconds before restart (cooldown period)", wait_time);
-                                        thread::sleep(Duration::from_secs(wait_time));
-                                    }
+                            if !policy.should_restart(status.success()) {
+                                if status.success() {
+                                    info!("Backend exited normally with status code 0");
+                                    *restart_attempts.lock().unwrap() = 0;
+                                } else {
+                                    warn!("Backend crashed with status: {}. Not restarting (policy: {:?})", status, policy);
                                 }
+                                break;
+                            }
+
+                            let decision = if status.success() {
+                                RestartDecision::Restart
+                            } else {
+                                exit_classifier(status)
+                            };
+                            if decision == RestartDecision::GiveUp {
+                                let tail = recent_output.lock().unwrap().lines();
+                                error!("Backend exited with status: {}. Not restarting (exit classified as fatal). Recent backend output:\n{}",
+                                       status, tail.join("\n"));
+                                break;
+                            }
 
-                                // Restart the process
-                                drop(child_lock); // Release lock before restarting
-
-                                debug!("Attempting to restart backend process");
-                                let new_child = Command::new("node")
-                                    .arg(&backend_script)
-                                    .current_dir(&working_dir)
-                                    .env("NODE_ENV", std::env::var("NODE_ENV").unwrap_or_else(|_| "production".to_string()))
-                                    .env("BACKEND_PORT", std::env::var("BACKEND_PORT").unwrap_or_else(|_| "3000".to_string()))
-                                    .stdin(Stdio::piped())
-                                    .stdout(Stdio::piped())
-                                    .stderr(Stdio::piped())
-                                    .spawn();
-
-                                match new_child {
-                                    Ok(process) => {
-                                        let pid = process.id();
-                                        info!("Backend restarted successfully with PID: {}", pid);
-                                        *child_clone.lock().unwrap() = Some(process);
-                                        *restart_attempts.lock().unwrap() += 1;
-                                        *last_restart.lock().unwrap() = Some(Instant::now());
+                            let attempts = *restart_attempts.lock().unwrap();
+                            warn!("Backend exited with status: {}. Restart attempt: {}/{}",
+                                  status, attempts + 1, MAX_RESTART_ATTEMPTS);
+
+                            if attempts >= MAX_RESTART_ATTEMPTS {
+                                let tail = recent_output.lock().unwrap().lines();
+                                error!("Maximum restart attempts ({}) reached. Giving up. Recent backend output:\n{}",
+                                       MAX_RESTART_ATTEMPTS, tail.join("\n"));
+                                if let Some(port) = resolve_backend_port(&env).filter(|&port| port != 0 && port_in_use(port)) {
+                                    error!("Backend port {} is in use by another process, which likely kept the backend from starting", port);
+                                }
+                                break;
+                            }
+
+                            let delay = match decision {
+                                RestartDecision::RestartAfter(delay) => delay,
+                                _ => backoff.delay_for(attempts),
+                            };
+                            info!("Waiting {:?} before restart", delay);
+                            thread::sleep(delay);
+                            if monitor_generation.load(Ordering::SeqCst) != generation {
+                                debug!("Restart monitor stopped during backoff");
+                                break;
+                            }
+
+                            // `update_backend` may have replaced the dead child during the backoff
+                            let _restarting = restart_lock.lock().unwrap();
+                            if child_clone.lock().unwrap().as_ref().map(|c| c.id()) != Some(pid) {
+                                debug!("Backend replaced during backoff, skipping restart");
+                                continue;
+                            }
+
+                            debug!("Attempting to restart backend process");
+                            let command = backend.lock().unwrap().clone();
+                            let new_child = build_command(&node_path, &command, &working_dir, &env)
+                                .spawn();
+
+                            *restart_attempts.lock().unwrap() += 1;
+                            match new_child {
+                                Ok(mut process) => {
+                                    let pid = process.id();
+                                    info!("Backend restarted successfully with PID: {}", pid);
+                                    drain_stderr(&mut process, Arc::clone(&stderr_callback), Arc::clone(&recent_output), log_file.clone());
+                                    if let Some(on_restart) = restart_callback.lock().unwrap().as_ref() {
+                                        on_restart(&mut process);
                                     }
-                                    Err(e) => {
-                                        error!("Failed to restart backend: {}", e);
-                                        *restart_attempts.lock().unwrap() += 1;
+                                    let mut child_lock = child_clone.lock().unwrap();
+                                    if monitor_generation.load(Ordering::SeqCst) != generation {
+                                        // Shut down while we were spawning; don't leave an orphan
+                                        warn!("Backend shut down during restart, killing PID: {}", pid);
+                                        let _ = process.kill();
+                                        let _ = process.wait();
+                                        break;
                                     }
+                                    *child_lock = Some(process);
+                                    drop(child_lock);
+                                    *last_restart.lock().unwrap() = Some(Instant::now());
+                                }
+                                Err(e) => {
+                                    // The dead child stays in place, so the next poll retries
+                                    error!("Failed to restart backend: {}", e);
                                 }
                             }
-                            break;
                         }
                         Ok(None) => {
-                            // Process is still running
+                            // Process is still running; reset the budget once it has been stable
+                            reset_attempts_if_stable(&restart_attempts, &last_restart, backoff.stable_reset_after);
                         }
                         Err(e) => {
                             error!("Error checking process status: {}", e);
@@ -157,13 +671,91 @@ impl ProcessManager {
                     }
                 }
             }
+
+            active_monitors.fetch_sub(1, Ordering::SeqCst);
         });
     }
 
+    /// Deliver backend stderr output to a callback, one line at a time
+    ///
+    /// Stderr is always drained on a background thread from the moment the
+    /// backend is spawned (lines are logged when no callback is set), so this
+    /// only routes the lines. It may be called before or after the backend
+    /// starts and applies across restarts; a later call replaces the callback.
+    pub fn start_stderr_listener<F>(&self, on_line: F)
+    where
+        F: Fn(String) + Send + 'static,
+    {
+        *self.stderr_callback.lock().unwrap() = Some(Box::new(on_line));
+        debug!("Registered stderr line callback");
+    }
+
+    /// Register a callback fired after the monitor successfully restarts the backend
+    ///
+    /// The callback receives the new `Child` before it is stored, so the IPC
+    /// layer can take the fresh `stdin`/`stdout` and reconnect the bridge:
+    ///
+    /// ```ignore
+    /// pm.on_restart(move |child| {
+    ///     if let Some(stdin) = child.stdin.take() {
+    ///         bridge.set_stdin(stdin);
+    ///     }
+    ///     if let Some(stdout) = child.stdout.take() {
+    ///         bridge.start_stdout_listener(stdout, |_| {});
+    ///     }
+    /// });
+    /// ```
+    pub fn on_restart<F>(&self, callback: F)
+    where
+        F: Fn(&mut Child) + Send + 'static,
+    {
+        *self.restart_callback.lock().unwrap() = Some(Box::new(callback));
+        debug!("Registered restart callback");
+    }
+
+    /// Register a callback fired by the monitor each time the backend exits
+    ///
+    /// Fires exactly once per exit, before any restart, including the final
+    /// exit after restarts are exhausted or when the policy forbids a restart.
+    /// Requires `restart_on_crash` to be running; exits caused by
+    /// `shutdown_gracefully` are not reported. The callback also receives the
+    /// tail of the backend's output (see `recent_output`) for diagnostics.
+    pub fn on_exit<F>(&self, callback: F)
+    where
+        F: Fn(ExitStatus, &[String]) + Send + 'static,
+    {
+        *self.exit_callback.lock().unwrap() = Some(Box::new(callback));
+        debug!("Registered exit callback");
+    }
+
+    /// Register a callback run by `shutdown_gracefully` before the backend is stopped
+    ///
+    /// Use it to drain in-flight IPC requests (see `IPCBridge::drain`) so the
+    /// process is not killed while work is still outstanding.
+    pub fn on_shutdown<F>(&self, callback: F)
+    where
+        F: Fn() + Send + 'static,
+    {
+        *self.shutdown_callback.lock().unwrap() = Some(Box::new(callback));
+        debug!("Registered shutdown callback");
+    }
+
+    /// Register a callback run before the idle monitor stops the backend
+    ///
+    /// Use it to detach the bridge (see `IPCBridge::clear_writer`) so
+    /// messages sent while the backend is down are queued rather than
+    /// written to the closing pipe.
+    pub fn on_idle_shutdown<F>(&self, callback: F)
+    where
+        F: Fn() + Send + 'static,
+    {
+        *self.idle_shutdown_callback.lock().unwrap() = Some(Box::new(callback));
+        debug!("Registered idle shutdown callback");
+    }
+
     /// Perform health check on the backend process
     pub fn health_check(&self) -> bool {
-        let child_lock = self.child.lock().unwrap();
-        if let Some(_) = child_lock.as_ref() {
+        if self.poll_alive() {
             debug!("Health check: Process is running");
             true
         } else {
@@ -172,92 +764,325 @@ impl ProcessManager {
         }
     }
 
-    /// Start periodic health checks
-    pub fn start_health_checks(&self) {
+    /// Get the backend's current memory and CPU usage
+    ///
+    /// CPU usage is measured since the previous call (or monitor sample),
+    /// so the first reading for a process reports 0%. Returns `None` when no
+    /// process is running or the platform is not supported.
+    pub fn resource_usage(&self) -> Option<ResourceUsage> {
+        sample_resource_usage(&self.child, &self.cpu_sample)
+    }
+
+    /// Periodically sample resource usage and pass each reading to `on_sample`
+    ///
+    /// Intervals with no running process are skipped.
+    pub fn start_resource_monitor<F>(&self, interval: Duration, on_sample: F)
+    where
+        F: Fn(ResourceUsage) + Send + 'static,
+    {
         let child_clone = Arc::clone(&self.child);
+        let cpu_sample = Arc::clone(&self.cpu_sample);
+        let monitor_generation = Arc::clone(&self.monitor_generation);
+        let generation = monitor_generation.load(Ordering::SeqCst);
+
+        thread::spawn(move || {
+            loop {
+                thread::sleep(interval);
+                if monitor_generation.load(Ordering::SeqCst) != generation {
+                    debug!("Resource monitor stopped");
+                    break;
+                }
+
+                if let Some(usage) = sample_resource_usage(&child_clone, &cpu_sample) {
+                    debug!("Backend resource usage: {} bytes RSS, {:.1}% CPU",
+                           usage.rss_bytes, usage.cpu_percent);
+                    on_sample(usage);
+                }
+            }
+        });
+    }
+
+    /// Start stopping the backend once it has been idle (see `with_idle_shutdown`)
+    ///
+    /// The backend is stopped gracefully without counting as a crash, so the
+    /// restart monitor leaves it down until traffic wakes it. Does nothing
+    /// when no idle window is configured.
+    pub fn start_idle_monitor(&self) {
+        let Some(window) = self.idle_shutdown else {
+            debug!("Idle shutdown not configured, not starting idle monitor");
+            return;
+        };
+        let waker = self.idle_waker();
+        let idle_shutdown_callback = Arc::clone(&self.idle_shutdown_callback);
+        let shutdown_timeout = self.shutdown_timeout;
+        let monitor_generation = Arc::clone(&self.monitor_generation);
+        let generation = monitor_generation.load(Ordering::SeqCst);
+        let interval = (window / IDLE_CHECKS_PER_WINDOW).min(Duration::from_secs(MAX_IDLE_CHECK_INTERVAL_SECS));
 
         thread::spawn(move || {
             loop {
-                thread::sleep(Duration::from_secs(HEALTH_CHECK_INTERVAL_SECS));
+                thread::sleep(interval);
+                if monitor_generation.load(Ordering::SeqCst) != generation {
+                    debug!("Idle monitor stopped");
+                    break;
+                }
 
+                let idle_since = *waker.last_activity.lock().unwrap();
+                if waker.idle_stopped.load(Ordering::SeqCst)
+                    || idle_since.elapsed() < window
+                    || waker.child.lock().unwrap().is_none()
+                {
+                    continue;
+                }
+
+                info!("No IPC traffic for {:?}, stopping idle backend", window);
+                if let Some(callback) = idle_shutdown_callback.lock().unwrap().as_ref() {
+                    callback();
+                }
+                let child = {
+                    let mut child_lock = waker.child.lock().unwrap();
+                    let child = child_lock.take();
+                    waker.idle_stopped.store(child.is_some(), Ordering::SeqCst);
+                    child
+                };
+                let Some(mut child) = child else {
+                    continue;
+                };
+                match stop_child(&mut child, shutdown_timeout) {
+                    Ok(outcome) => debug!("Idle backend stopped: {:?}", outcome),
+                    Err(e) => warn!("Failed to stop idle backend: {}", e),
+                }
+
+                // Traffic that arrived while stopping saw the backend still up
+                if *waker.last_activity.lock().unwrap() > idle_since {
+                    waker.wake();
+                }
+            }
+        });
+    }
+
+    /// A callback that reports IPC traffic, for `IPCBridge::on_activity`
+    ///
+    /// Resets the idle timer, and starts the backend again (on a separate
+    /// thread) if it was stopped for inactivity.
+    pub fn activity_recorder(&self) -> impl Fn() + Send + Sync + 'static {
+        let waker = self.idle_waker();
+        move || waker.touch()
+    }
+
+    /// Whether the backend is currently stopped for inactivity
+    pub fn is_idle_stopped(&self) -> bool {
+        self.idle_stopped.load(Ordering::SeqCst)
+    }
+
+    fn idle_waker(&self) -> IdleWaker {
+        IdleWaker {
+            child: Arc::clone(&self.child),
+            idle_stopped: Arc::clone(&self.idle_stopped),
+            last_activity: Arc::clone(&self.last_activity),
+            backend: Arc::clone(&self.backend),
+            restart_lock: Arc::clone(&self.restart_lock),
+            node_path: self.node_path.clone(),
+            working_dir: self.working_dir.clone(),
+            env: self.env.clone(),
+            stderr_callback: Arc::clone(&self.stderr_callback),
+            restart_callback: Arc::clone(&self.restart_callback),
+            recent_output: Arc::clone(&self.recent_output),
+            log_file: self.log_file.clone(),
+        }
+    }
+
+    /// Start periodic health checks
+    ///
+    /// Uses the default probe, which only checks that a child process exists,
+    /// and logs when the backend is unhealthy.
+    pub fn start_health_checks(&self) {
+        let child_clone = Arc::clone(&self.child);
+        self.start_health_checks_with(
+            Duration::from_secs(HEALTH_CHECK_INTERVAL_SECS),
+            move || {
                 let child_lock = child_clone.lock().unwrap();
                 if let Some(child) = child_lock.as_ref() {
                     debug!("Health check: Backend process (PID: {}) is alive", child.id());
+                    true
                 } else {
                     warn!("Health check: No backend process running");
+                    false
+                }
+            },
+            |failures| warn!("Backend unhealthy after {} failed health checks", failures),
+        );
+    }
+
+    /// Start periodic health checks with a custom probe
+    ///
+    /// `probe` runs every `interval` (e.g. pinging through the IPC bridge) and
+    /// returns whether the backend is healthy. After `unhealthy_threshold`
+    /// consecutive failures (see `with_unhealthy_threshold`), `on_unhealthy`
+    /// receives the failure count so the caller can restart the backend; the
+    /// count then starts over. Any successful probe resets it.
+    pub fn start_health_checks_with<P, F>(&self, interval: Duration, probe: P, on_unhealthy: F)
+    where
+        P: Fn() -> bool + Send + 'static,
+        F: Fn(u32) + Send + 'static,
+    {
+        let monitor_generation = Arc::clone(&self.monitor_generation);
+        let generation = monitor_generation.load(Ordering::SeqCst);
+        let threshold = self.unhealthy_threshold;
+
+        thread::spawn(move || {
+            let mut failures = 0;
+            loop {
+                thread::sleep(interval);
+                if monitor_generation.load(Ordering::SeqCst) != generation {
+                    debug!("Health checks stopped");
+                    break;
+                }
+
+                if probe() {
+                    failures = 0;
+                    continue;
+                }
+
+                failures += 1;
+                debug!("Health probe failed ({}/{})", failures, threshold);
+                if failures >= threshold {
+                    on_unhealthy(failures);
+                    failures = 0;
                 }
             }
         });
     }
 
     /// Gracefully shutdown the backend process
-    pub fn shutdown_gracefully(&mut self) -> Result<(), String> {
+    ///
+    /// Waits up to the shutdown timeout (see `with_shutdown_timeout`) before
+    /// force-killing. Also stops the restart, health-check, resource and idle
+    /// monitor threads started so far; start them again after the next `start_node_backend`.
+    pub fn shutdown_gracefully(&mut self) -> Result<ShutdownOutcome, String> {
         info!("Initiating graceful shutdown of Node.js backend");
+        if let Some(callback) = self.shutdown_callback.lock().unwrap().as_ref() {
+            callback();
+        }
+        self.monitor_generation.fetch_add(1, Ordering::SeqCst);
+        self.idle_stopped.store(false, Ordering::SeqCst);
 
         let mut child_lock = self.child.lock().unwrap();
         if let Some(mut child) = child_lock.take() {
-            let pid = child.id();
-            debug!("Sending SIGTERM to process (PID: {})", pid);
+            stop_child(&mut child, self.shutdown_timeout)
+        } else {
+            debug!("No backend process to shutdown");
+            Ok(ShutdownOutcome::NotRunning)
+        }
+    }
 
-            // Send SIGTERM on Unix systems
-            #[cfg(unix)]
-            {
-                let _ = Command::new("kill")
-                    .arg("-TERM")
-                    .arg(pid.to_string())
-                    .status();
-            }
+    /// Stop the restart, health-check, resource and idle monitor threads
+    ///
+    /// The backend keeps running but is no longer restarted if it exits.
+    /// `shutdown_gracefully` does this too, but only after its `on_shutdown`
+    /// callback; call this first when that callback may take a while, so a
+    /// crash in the meantime does not bring the backend back.
+    pub fn stop_monitors(&self) {
+        debug!("Stopping backend monitor threads");
+        self.monitor_generation.fetch_add(1, Ordering::SeqCst);
+    }
 
-            // On Windows, just kill it
-            #[cfg(windows)]
-            {
-                let _ = child.kill();
+    /// Wait up to `timeout` for stopped restart monitors to exit
+    ///
+    /// Returns `true` once no `restart_on_crash` monitor thread is running.
+    pub fn wait_for_monitors(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.active_monitors.load(Ordering::SeqCst) > 0 {
+            if Instant::now() >= deadline {
+                return false;
             }
+            thread::sleep(Duration::from_millis(EXIT_POLL_INTERVAL_MS));
+        }
+        true
+    }
+
+    /// Block until the backend exits, polling `try_wait`
+    ///
+    /// On exit the child is taken and its status returned. With a timeout,
+    /// returns an error once it elapses and leaves the child in place so the
+    /// caller can still force-kill it. Useful after asking the backend to shut
+    /// down over IPC. The lock is not held between polls.
+    pub fn wait_for_exit(&self, timeout: Option<Duration>) -> Result<ExitStatus, String> {
+        let deadline = timeout.map(|t| Instant::now() + t);
 
-            // Wait for process to exit (with timeout)
-            let shutdown_timeout = 30;
-            for i in 0..shutdown_timeout {
+        loop {
+            {
+                let mut child_lock = self.child.lock().unwrap();
+                let child = child_lock
+                    .as_mut()
+                    .ok_or_else(|| "No backend process running".to_string())?;
                 match child.try_wait() {
                     Ok(Some(status)) => {
-                        info!("Backend shut down gracefully with exit code: {}",
-                              status.code().unwrap_or(-1));
-                        return Ok(());
-                    }
-                    Ok(None) => {
-                        if i % 10 == 0 {
-                            debug!("Waiting for backend to shutdown... ({}/{}s)", i / 10, shutdown_timeout / 10);
-                        }
-                        thread::sleep(Duration::from_millis(100));
-                    }
-                    Err(e) => {
-                        error!("Error during shutdown: {}", e);
-                        return Err(format!("Shutdown error: {}", e));
+                        info!("Backend (PID: {}) exited with {}", child.id(), status);
+                        *child_lock = None;
+                        return Ok(status);
                     }
+                    Ok(None) => {}
+                    Err(e) => return Err(format!("Failed to wait for backend: {}", e)),
                 }
             }
 
-            // Force kill if not exited after timeout
-            warn!("Backend did not exit gracefully within {}s, forcing shutdown", shutdown_timeout / 10);
-            match child.kill() {
-                Ok(_) => {
-                    info!("Backend process forcefully terminated");
-                    Ok(())
-                }
-                Err(e) => {
-                    error!("Failed to force kill backend process: {}", e);
-                    Err(format!("Force kill failed: {}", e))
+            if let Some(deadline) = deadline {
+                if Instant::now() >= deadline {
+                    return Err(format!("Backend did not exit within {:?}", timeout.unwrap_or_default()));
                 }
             }
-        } else {
-            debug!("No backend process to shutdown");
-            Ok(())
+            thread::sleep(Duration::from_millis(EXIT_POLL_INTERVAL_MS));
         }
     }
 
     /// Check if the backend process is running
     pub fn is_running(&self) -> bool {
-        let child_lock = self.child.lock().unwrap();
-        child_lock.is_some()
+        self.poll_alive()
+    }
+
+    /// Send a signal to the backend process
+    ///
+    /// On Unix the signal goes to the backend itself (not its process group)
+    /// via `kill`. Windows has no signals: `Kill` terminates the process,
+    /// `Int` and `Term` send CTRL_BREAK (see `shutdown_gracefully`), and the
+    /// rest return an error. Fails if no backend is running.
+    pub fn signal(&self, sig: Signal) -> Result<(), String> {
+        let mut child_lock = self.child.lock().unwrap();
+        let Some(child) = child_lock.as_mut() else {
+            return Err("Backend process is not running".to_string());
+        };
+        if !matches!(child.try_wait(), Ok(None)) {
+            return Err("Backend process is not running".to_string());
+        }
+        info!("Sending SIG{} to backend (PID: {})", sig.name(), child.id());
+        send_signal(child, sig)
+    }
+
+    /// Check whether the stored child is still alive via `try_wait`
+    ///
+    /// An exited child is cleared, unless the restart monitor is running, in
+    /// which case it is left for the monitor to report and restart.
+    fn poll_alive(&self) -> bool {
+        let mut child_lock = self.child.lock().unwrap();
+        let Some(child) = child_lock.as_mut() else {
+            return false;
+        };
+
+        match child.try_wait() {
+            Ok(None) => true,
+            Ok(Some(status)) => {
+                if self.active_monitors.load(Ordering::SeqCst) == 0 {
+                    debug!("Backend (PID: {}) has exited with {}, clearing", child.id(), status);
+                    *child_lock = None;
+                }
+                false
+            }
+            Err(e) => {
+                warn!("Error checking process status: {}", e);
+                false
+            }
+        }
     }
 
     /// Get the process ID of the backend
@@ -266,10 +1091,447 @@ impl ProcessManager {
         child_lock.as_ref().map(|c| c.id())
     }
 
+    /// Take the piped stdin of the current backend process
+    ///
+    /// Pass the handle to `IPCBridge::set_stdin`. It can only be taken once
+    /// per spawn; later calls return `None` until the backend is started again.
+    pub fn take_stdin(&self) -> Option<ChildStdin> {
+        let mut child_lock = self.child.lock().unwrap();
+        child_lock.as_mut().and_then(|c| c.stdin.take())
+    }
+
+    /// Take the piped stdout of the current backend process
+    ///
+    /// Pass the handle to `IPCBridge::start_stdout_listener`. It can only be
+    /// taken once per spawn; later calls return `None` until the backend is
+    /// started again.
+    pub fn take_stdout(&self) -> Option<ChildStdout> {
+        let mut child_lock = self.child.lock().unwrap();
+        child_lock.as_mut().and_then(|c| c.stdout.take())
+    }
+
     /// Get restart attempt count
     pub fn get_restart_attempts(&self) -> u32 {
         *self.restart_attempts.lock().unwrap()
     }
+
+    /// Give the restart monitor a fresh budget of restart attempts
+    ///
+    /// Use it once the cause of repeated crashes has been fixed. A monitor
+    /// that already gave up has exited; bring the backend back with
+    /// `start_node_backend` followed by `restart_on_crash`.
+    pub fn reset_restart_attempts(&self) {
+        info!("Resetting restart attempts (was {})", self.get_restart_attempts());
+        *self.restart_attempts.lock().unwrap() = 0;
+        *self.last_restart.lock().unwrap() = None;
+    }
+
+    /// The most recent backend output lines, oldest first
+    ///
+    /// Stderr is recorded automatically. Stdout is owned by the IPC bridge,
+    /// so its lines are only included when fed through `output_recorder`.
+    pub fn recent_output(&self) -> Vec<String> {
+        self.recent_output.lock().unwrap().lines()
+    }
+
+    /// A line sink that records backend stdout for `recent_output`
+    ///
+    /// Also copies the lines to the log file set with `with_log_file`.
+    ///
+    /// ```ignore
+    /// bridge.start_stdout_listener_with_raw(stdout, |_| {}, pm.output_recorder());
+    /// ```
+    pub fn output_recorder(&self) -> impl Fn(String) + Send + Sync + 'static {
+        let recent_output = Arc::clone(&self.recent_output);
+        let log_file = self.log_file.clone();
+        move |line| {
+            if let Some(log_file) = &log_file {
+                log_file.write("stdout", &line);
+            }
+            recent_output.lock().unwrap().push(line)
+        }
+    }
+}
+
+/// Random number in `[0, 1)`, good enough to spread restart delays
+fn random_unit() -> f64 {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default(),
+    );
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// What the idle monitor and activity recorder need to restart the backend
+#[derive(Clone)]
+struct IdleWaker {
+    child: Arc<Mutex<Option<Child>>>,
+    idle_stopped: Arc<AtomicBool>,
+    last_activity: Arc<Mutex<Instant>>,
+    backend: Arc<Mutex<BackendCommand>>,
+    restart_lock: Arc<Mutex<()>>,
+    node_path: String,
+    working_dir: String,
+    env: HashMap<String, String>,
+    stderr_callback: Arc<Mutex<Option<LineCallback>>>,
+    restart_callback: Arc<Mutex<Option<RestartCallback>>>,
+    recent_output: Arc<Mutex<RecentOutput>>,
+    log_file: Option<LogFile>,
+}
+
+impl IdleWaker {
+    /// Record traffic, waking an idle-stopped backend in the background
+    fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+        if self.idle_stopped.load(Ordering::SeqCst) {
+            let waker = self.clone();
+            thread::spawn(move || waker.wake());
+        }
+    }
+
+    /// Start the backend again if it is stopped for inactivity
+    ///
+    /// The new child goes through the `on_restart` callback like a crash
+    /// restart, but does not count as a restart attempt.
+    fn wake(&self) {
+        // Only one caller gets to start it
+        if !self.idle_stopped.swap(false, Ordering::SeqCst) {
+            return;
+        }
+
+        info!("IPC traffic after idle shutdown, starting backend");
+        let _restarting = self.restart_lock.lock().unwrap();
+        let backend = self.backend.lock().unwrap().clone();
+        match build_command(&self.node_path, &backend, &self.working_dir, &self.env).spawn() {
+            Ok(mut process) => {
+                info!("Backend woken with PID: {}", process.id());
+                drain_stderr(&mut process, Arc::clone(&self.stderr_callback), Arc::clone(&self.recent_output), self.log_file.clone());
+                if let Some(on_restart) = self.restart_callback.lock().unwrap().as_ref() {
+                    on_restart(&mut process);
+                }
+                *self.child.lock().unwrap() = Some(process);
+                *self.last_activity.lock().unwrap() = Instant::now();
+            }
+            Err(e) => {
+                // Stay idle-stopped so the next message tries again
+                error!("Failed to start backend after idle shutdown: {}", e);
+                self.idle_stopped.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+/// Ask a child to exit, force-killing it and its process group after `timeout`
+fn stop_child(child: &mut Child, timeout: Duration) -> Result<ShutdownOutcome, String> {
+    let pid = child.id();
+    if !send_graceful_signal(pid) {
+        warn!("Could not signal backend (PID: {}) to shut down, killing it", pid);
+        let _ = child.kill();
+    }
+
+    // Wait for process to exit (with timeout)
+    let deadline = Instant::now() + timeout;
+    let mut next_log = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                info!("Backend shut down gracefully with exit code: {}",
+                      status.code().unwrap_or(-1));
+                return Ok(ShutdownOutcome::Exited(status.code()));
+            }
+            Ok(None) => {
+                if Instant::now() >= deadline {
+                    break;
+                }
+                if Instant::now() >= next_log {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    debug!("Waiting for backend to shutdown... ({:.1}s remaining)", remaining.as_secs_f64());
+                    next_log += Duration::from_secs(1);
+                }
+                thread::sleep(Duration::from_millis(SHUTDOWN_POLL_INTERVAL_MS));
+            }
+            Err(e) => {
+                error!("Error during shutdown: {}", e);
+                return Err(format!("Shutdown error: {}", e));
+            }
+        }
+    }
+
+    // Force kill if not exited after timeout, taking descendants with it
+    warn!("Backend did not exit gracefully within {:?}, forcing shutdown", timeout);
+    let group_killed = kill_process_group(pid);
+    match child.kill() {
+        // The group kill may already have reaped the leader
+        Err(_) if group_killed => {
+            let _ = child.wait();
+            info!("Backend process group forcefully terminated");
+            Ok(ShutdownOutcome::Killed)
+        }
+        Ok(_) => {
+            let _ = child.wait();
+            info!("Backend process forcefully terminated");
+            Ok(ShutdownOutcome::Killed)
+        }
+        Err(e) => {
+            error!("Failed to force kill backend process: {}", e);
+            Err(format!("Force kill failed: {}", e))
+        }
+    }
+}
+
+/// Port passed to the backend as `BACKEND_PORT`, with the same precedence as `build_command`
+fn resolve_backend_port(env: &HashMap<String, String>) -> Option<u16> {
+    env.get("BACKEND_PORT")
+        .cloned()
+        .or_else(|| std::env::var("BACKEND_PORT").ok())
+        .unwrap_or_else(|| DEFAULT_BACKEND_PORT.to_string())
+        .parse()
+        .ok()
+}
+
+/// Whether something is already listening on `port` on the loopback interface
+fn port_in_use(port: u16) -> bool {
+    matches!(TcpListener::bind(("127.0.0.1", port)), Err(e) if e.kind() == ErrorKind::AddrInUse)
+}
+
+/// Ask the OS for a currently unused port
+fn free_port() -> std::io::Result<u16> {
+    Ok(TcpListener::bind(("127.0.0.1", 0))?.local_addr()?.port())
+}
+
+/// Reset the restart attempt counter if the last restart is older than `window`
+fn reset_attempts_if_stable(attempts: &Mutex<u32>, last_restart: &Mutex<Option<Instant>>, window: Duration) {
+    let mut attempts = attempts.lock().unwrap();
+    if *attempts == 0 {
+        return;
+    }
+    let stable = last_restart
+        .lock()
+        .unwrap()
+        .map(|t| t.elapsed() >= window)
+        .unwrap_or(false);
+    if stable {
+        info!("Backend stable for {:?}, resetting restart attempts", window);
+        *attempts = 0;
+    }
+}
+
+/// Sample the current child's resource usage, if there is one
+fn sample_resource_usage(
+    child: &Arc<Mutex<Option<Child>>>,
+    cpu_sample: &Arc<Mutex<Option<CpuSample>>>,
+) -> Option<ResourceUsage> {
+    let child_lock = child.lock().unwrap();
+    let child = child_lock.as_ref()?;
+    resource::sample(child, &mut cpu_sample.lock().unwrap())
+}
+
+/// Script and node arguments the backend is launched with
+#[derive(Debug, Clone)]
+struct BackendCommand {
+    script: String,
+    /// Extra arguments passed to node before the script
+    node_args: Vec<String>,
+}
+
+/// Build the command used to launch (and relaunch) the backend
+fn build_command(
+    node_path: &str,
+    backend: &BackendCommand,
+    working_dir: &str,
+    env: &HashMap<String, String>,
+) -> Command {
+    let mut command = Command::new(node_path);
+    command
+        .args(&backend.node_args)
+        .arg(&backend.script)
+        .current_dir(working_dir)
+        .env("NODE_ENV", std::env::var("NODE_ENV").unwrap_or_else(|_| "production".to_string()))
+        .env("BACKEND_PORT", std::env::var("BACKEND_PORT").unwrap_or_else(|_| DEFAULT_BACKEND_PORT.to_string()))
+        .envs(env)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    // Run the backend in its own process group so shutdown can signal it
+    // together with anything it spawned (language servers, workers)
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    // On Windows the new group also lets `shutdown_gracefully` deliver
+    // CTRL_BREAK to the backend alone instead of to every process sharing our console
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        command.creation_flags(CREATE_NEW_PROCESS_GROUP);
+    }
+
+    command
+}
+
+/// Force-kill the backend's process group, including its descendants
+///
+/// The backend is a group leader (see `build_command`), so its PID is the
+/// group ID. Windows uses `taskkill /T`, which walks the process tree.
+fn kill_process_group(pid: u32) -> bool {
+    #[cfg(unix)]
+    let status = Command::new("kill")
+        .arg("-KILL")
+        .arg("--")
+        .arg(format!("-{}", pid))
+        .status();
+
+    #[cfg(windows)]
+    let status = Command::new("taskkill")
+        .args(["/T", "/F", "/PID"])
+        .arg(pid.to_string())
+        .status();
+
+    #[cfg(not(any(unix, windows)))]
+    let status: std::io::Result<std::process::ExitStatus> =
+        Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "process groups not supported"));
+
+    match status {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            debug!("Killing process group {} exited with {}", pid, status);
+            false
+        }
+        Err(e) => {
+            debug!("Failed to kill process group {}: {}", pid, e);
+            false
+        }
+    }
+}
+
+/// `CREATE_NEW_PROCESS_GROUP` process creation flag
+#[cfg(windows)]
+const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+
+/// Ask the backend to shut down so its cleanup handlers can run
+///
+/// Unix sends SIGTERM to the backend's process group. Windows sends CTRL_BREAK_EVENT, which Node.js surfaces
+/// as `SIGBREAK`; this only reaches the backend because it is spawned with
+/// `CREATE_NEW_PROCESS_GROUP` (its PID is the group ID) and requires both
+/// processes to share a console. Returns `false` if the signal could not be
+/// sent, in which case the caller kills the process instead.
+fn send_graceful_signal(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        debug!("Sending SIGTERM to process group (PGID: {})", pid);
+        Command::new("kill")
+            .arg("-TERM")
+            .arg("--")
+            .arg(format!("-{}", pid))
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false)
+    }
+
+    #[cfg(windows)]
+    {
+        const CTRL_BREAK_EVENT: u32 = 1;
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn GenerateConsoleCtrlEvent(ctrl_event: u32, process_group_id: u32) -> i32;
+        }
+
+        debug!("Sending CTRL_BREAK to process group (PID: {})", pid);
+        // SAFETY: plain Win32 call with no pointer arguments
+        unsafe { GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, pid) != 0 }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = pid;
+        false
+    }
+}
+
+/// Deliver `sig` to the child, see `ProcessManager::signal`
+fn send_signal(child: &mut Child, sig: Signal) -> Result<(), String> {
+    let pid = child.id();
+
+    #[cfg(unix)]
+    {
+        let status = Command::new("kill")
+            .arg(format!("-{}", sig.name()))
+            .arg("--")
+            .arg(pid.to_string())
+            .status()
+            .map_err(|e| format!("Failed to send SIG{}: {}", sig.name(), e))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("Failed to send SIG{} to PID {}: kill exited with {}", sig.name(), pid, status))
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        match sig {
+            Signal::Kill => child.kill().map_err(|e| format!("Failed to kill PID {}: {}", pid, e)),
+            Signal::Int | Signal::Term if send_graceful_signal(pid) => Ok(()),
+            Signal::Int | Signal::Term => Err(format!("Failed to send CTRL_BREAK to PID {}", pid)),
+            _ => Err(format!("SIG{} is not supported on this platform", sig.name())),
+        }
+    }
+}
+
+/// Drain the child's stderr on a background thread
+///
+/// Keeps the pipe from filling up and blocking the backend. Each line is
+/// passed to the registered callback, or logged if there is none.
+fn drain_stderr(
+    child: &mut Child,
+    callback: Arc<Mutex<Option<LineCallback>>>,
+    recent_output: Arc<Mutex<RecentOutput>>,
+    log_file: Option<LogFile>,
+) {
+    let Some(stderr) = child.stderr.take() else {
+        return;
+    };
+    let pid = child.id();
+
+    thread::spawn(move || {
+        let mut reader = BufReader::new(stderr);
+        let mut buf = Vec::new();
+
+        loop {
+            buf.clear();
+            match reader.read_until(b'\n', &mut buf) {
+                Ok(0) => break,
+                Ok(_) => {
+                    let line = String::from_utf8_lossy(&buf).trim_end_matches(['\r', '\n']).to_string();
+                    recent_output.lock().unwrap().push(line.clone());
+                    if let Some(log_file) = &log_file {
+                        log_file.write("stderr", &line);
+                    }
+                    match callback.lock().unwrap().as_ref() {
+                        Some(on_line) => on_line(line),
+                        None => warn!("Backend stderr (PID: {}): {}", pid, line),
+                    }
+                }
+                Err(e) => {
+                    error!("Error reading backend stderr (PID: {}): {}", pid, e);
+                    break;
+                }
+            }
+        }
+
+        debug!("stderr drain for PID {} stopped", pid);
+    });
 }
 
 #[cfg(test)]
@@ -283,7 +1545,72 @@ mod tests {
             ".".to_string(),
         );
         assert!(!pm.is_running());
+        assert!(!pm.health_check());
         assert_eq!(pm.get_restart_attempts(), 0);
+        assert_eq!(pm.restart_policy(), RestartPolicy::OnFailure);
+    }
+
+    #[test]
+    fn test_with_shutdown_timeout() {
+        let mut pm = ProcessManager::new(
+            "test.js".to_string(),
+            ".".to_string(),
+        ).with_shutdown_timeout(Duration::from_millis(250));
+        assert_eq!(pm.shutdown_timeout, Duration::from_millis(250));
+        assert_eq!(pm.shutdown_gracefully(), Ok(ShutdownOutcome::NotRunning));
+    }
+
+    #[test]
+    fn test_health_probe_reports_consecutive_failures() {
+        let mut pm = ProcessManager::new(
+            "test.js".to_string(),
+            ".".to_string(),
+        ).with_unhealthy_threshold(2);
+
+        // Popped from the end: four failures, a success, one failure, then healthy
+        let results = Arc::new(Mutex::new(vec![false, true, false, false, false, false]));
+        let (tx, rx) = std::sync::mpsc::channel();
+        let probe_results = Arc::clone(&results);
+        pm.start_health_checks_with(
+            Duration::from_millis(10),
+            move || probe_results.lock().unwrap().pop().unwrap_or(true),
+            move |failures| {
+                let _ = tx.send(failures);
+            },
+        );
+
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), 2);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), 2);
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+        pm.shutdown_gracefully().unwrap();
+    }
+
+    #[test]
+    fn test_wait_for_exit_without_process() {
+        let pm = ProcessManager::new(
+            "test.js".to_string(),
+            ".".to_string(),
+        );
+        assert!(pm.wait_for_exit(Some(Duration::from_millis(10))).is_err());
+    }
+
+    #[test]
+    fn test_resource_usage_without_process() {
+        let pm = ProcessManager::new(
+            "test.js".to_string(),
+            ".".to_string(),
+        );
+        assert!(pm.resource_usage().is_none());
+    }
+
+    #[test]
+    fn test_restart_policy_decisions() {
+        assert!(!RestartPolicy::Never.should_restart(false));
+        assert!(!RestartPolicy::Never.should_restart(true));
+        assert!(RestartPolicy::OnFailure.should_restart(false));
+        assert!(!RestartPolicy::OnFailure.should_restart(true));
+        assert!(RestartPolicy::Always.should_restart(false));
+        assert!(RestartPolicy::Always.should_restart(true));
     }
 
     #[test]
@@ -294,4 +1621,153 @@ mod tests {
         );
         assert_eq!(pm.get_pid(), None);
     }
+
+    #[test]
+    fn test_restart_backoff_exponential_delay() {
+        let policy = RestartBackoff::new(
+            Duration::from_secs(1),
+            Duration::from_secs(10),
+            Duration::from_secs(60),
+        );
+        assert_eq!(policy.delay_for(0), Duration::from_secs(1));
+        assert_eq!(policy.delay_for(1), Duration::from_secs(2));
+        assert_eq!(policy.delay_for(3), Duration::from_secs(8));
+        assert_eq!(policy.delay_for(4), Duration::from_secs(10));
+        assert_eq!(policy.delay_for(40), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_restart_backoff_jitter() {
+        let policy = RestartBackoff::new(
+            Duration::from_secs(10),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        )
+        .with_jitter(0.5);
+        let delays: Vec<Duration> = (0..50).map(|_| policy.delay_for(0)).collect();
+        assert!(delays.iter().all(|d| *d >= Duration::from_secs(5) && *d <= Duration::from_secs(15)));
+        assert!(delays.iter().any(|d| *d != delays[0]), "jitter should vary the delay");
+
+        assert_eq!(RestartBackoff::default().jitter, 0.0);
+        assert_eq!(RestartBackoff::default().with_jitter(3.0).jitter, 1.0);
+    }
+
+    #[test]
+    fn test_with_restart_backoff() {
+        let policy = RestartBackoff::new(
+            Duration::from_millis(100),
+            Duration::from_secs(5),
+            Duration::from_secs(30),
+        );
+        let pm = ProcessManager::new(
+            "backend.js".to_string(),
+            ".".to_string(),
+        ).with_restart_backoff(policy);
+        assert_eq!(pm.restart_backoff, policy);
+
+        let pm = pm.with_stable_window(Duration::from_secs(5));
+        assert_eq!(pm.restart_backoff.stable_reset_after, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_build_command_node_path_and_args() {
+        let pm = ProcessManager::new(
+            "backend.js".to_string(),
+            ".".to_string(),
+        )
+        .with_node_path("/opt/node/bin/node".to_string())
+        .with_node_args(vec!["--max-old-space-size=4096".to_string()]);
+
+        let command = build_command(&pm.node_path, &pm.backend.lock().unwrap(), &pm.working_dir, &pm.env);
+        assert_eq!(command.get_program(), "/opt/node/bin/node");
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, ["--max-old-space-size=4096", "backend.js"]);
+    }
+
+    #[test]
+    fn test_default_node_path() {
+        let pm = ProcessManager::new(
+            "backend.js".to_string(),
+            ".".to_string(),
+        );
+        let command = build_command(&pm.node_path, &pm.backend.lock().unwrap(), &pm.working_dir, &pm.env);
+        assert_eq!(command.get_program(), "node");
+    }
+
+    #[test]
+    fn test_env_overrides_defaults() {
+        let mut env = HashMap::new();
+        env.insert("BACKEND_PORT".to_string(), "4100".to_string());
+        env.insert("LANG".to_string(), "zh_CN.UTF-8".to_string());
+
+        let pm = ProcessManager::new(
+            "backend.js".to_string(),
+            ".".to_string(),
+        ).with_env(env);
+
+        let command = build_command(&pm.node_path, &pm.backend.lock().unwrap(), &pm.working_dir, &pm.env);
+        let envs: HashMap<_, _> = command
+            .get_envs()
+            .map(|(k, v)| (k.to_string_lossy().into_owned(), v.map(|v| v.to_string_lossy().into_owned())))
+            .collect();
+
+        assert_eq!(envs["BACKEND_PORT"], Some("4100".to_string()));
+        assert_eq!(envs["LANG"], Some("zh_CN.UTF-8".to_string()));
+        assert!(envs.contains_key("NODE_ENV"));
+    }
+
+    #[test]
+    fn test_start_fails_fast_when_port_in_use() {
+        let taken = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = taken.local_addr().unwrap().port();
+        let mut env = HashMap::new();
+        env.insert("BACKEND_PORT".to_string(), port.to_string());
+
+        let mut pm = ProcessManager::new("backend.js".to_string(), ".".to_string()).with_env(env);
+        let err = pm.start_node_backend().unwrap_err();
+        assert!(err.contains(&format!("port {} is already in use", port)), "{}", err);
+        assert!(!pm.is_running());
+    }
+
+    #[test]
+    fn test_port_fallback_picks_free_port() {
+        let taken = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = taken.local_addr().unwrap().port();
+        let mut env = HashMap::new();
+        env.insert("BACKEND_PORT".to_string(), port.to_string());
+
+        let mut pm = ProcessManager::new("backend.js".to_string(), ".".to_string())
+            .with_env(env)
+            .with_port_fallback();
+        pm.check_backend_port().unwrap();
+        let chosen = pm.backend_port().unwrap();
+        assert_ne!(chosen, port);
+        assert!(!port_in_use(chosen));
+    }
+
+    #[test]
+    fn test_start_fails_when_script_missing() {
+        let mut pm = ProcessManager::new("no_such_backend.js".to_string(), ".".to_string());
+        let err = pm.start_node_backend().unwrap_err();
+        let expected = Path::new(".").join("no_such_backend.js");
+        assert_eq!(err, format!("backend script not found: {}", expected.display()));
+        assert!(!pm.is_running());
+    }
+
+    #[test]
+    fn test_signal_without_process() {
+        let pm = ProcessManager::new("backend.js".to_string(), ".".to_string());
+        assert!(pm.signal(Signal::Usr1).is_err());
+        assert_eq!(Signal::Hup.name(), "HUP");
+    }
+
+    #[test]
+    fn test_take_handles_without_process() {
+        let pm = ProcessManager::new(
+            "backend.js".to_string(),
+            ".".to_string(),
+        );
+        assert!(pm.take_stdin().is_none());
+        assert!(pm.take_stdout().is_none());
+    }
 }
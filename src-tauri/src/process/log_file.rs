@@ -0,0 +1,155 @@
+/**
+ * Backend Log File
+ *
+ * Persists the backend's stdout/stderr lines to a size-capped file so that
+ * crashes can be investigated after the fact. Once the file would exceed its
+ * cap it is rotated: `backend.log` becomes `backend.log.1`, `.1` becomes `.2`
+ * and so on, keeping `MAX_ROTATED_FILES` old files.
+ *
+ * Lines are handed to a writer thread, so slow disks never hold up the
+ * threads draining the backend's pipes. At most `MAX_PENDING_LINES` lines
+ * wait for the thread; lines beyond that are dropped, and the number dropped
+ * is noted in the file once the thread catches up.
+ */
+
+use log::{debug, warn};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+
+/// Number of rotated files kept next to the active one
+const MAX_ROTATED_FILES: usize = 5;
+
+/// Lines waiting for the writer thread before new ones are dropped
+const MAX_PENDING_LINES: usize = 1024;
+
+/// Handle for appending lines to a rotating log file
+#[derive(Debug, Clone)]
+pub(crate) struct LogFile {
+    tx: mpsc::SyncSender<String>,
+    /// Lines dropped since the writer thread last caught up
+    dropped: Arc<AtomicU64>,
+}
+
+impl LogFile {
+    /// Start a writer thread appending to `path`, rotating past `max_size` bytes
+    pub(crate) fn spawn(path: PathBuf, max_size: u64) -> Self {
+        let (tx, rx) = mpsc::sync_channel::<String>(MAX_PENDING_LINES);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let thread_dropped = Arc::clone(&dropped);
+        thread::spawn(move || {
+            let mut file = RotatingFile { path, max_size, file: None, size: 0 };
+            for line in rx {
+                let skipped = thread_dropped.swap(0, Ordering::Relaxed);
+                let result = if skipped > 0 {
+                    file.write_line(&format!("[log] {} line(s) dropped, writer fell behind\n", skipped))
+                        .and_then(|_| file.write_line(&line))
+                } else {
+                    file.write_line(&line)
+                };
+                if let Err(e) = result {
+                    warn!("Failed to write backend log file {}: {}", file.path.display(), e);
+                    // Reopen on the next line
+                    file.file = None;
+                }
+            }
+            debug!("Backend log writer for {} stopped", file.path.display());
+        });
+        LogFile { tx, dropped }
+    }
+
+    /// Queue a line from the given stream (`stdout` or `stderr`)
+    ///
+    /// The line is dropped and counted if the writer thread is too far behind.
+    pub(crate) fn write(&self, stream: &str, line: &str) {
+        if let Err(mpsc::TrySendError::Full(_)) = self.tx.try_send(format!("[{}] {}\n", stream, line)) {
+            if self.dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+                warn!("Backend log writer is behind, dropping lines");
+            }
+        }
+    }
+}
+
+/// Log file that rotates itself once it reaches `max_size` bytes
+struct RotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    file: Option<File>,
+    /// Bytes in the active file
+    size: u64,
+}
+
+impl RotatingFile {
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.file.is_none() {
+            self.open()?;
+        }
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        if let Some(file) = self.file.as_mut() {
+            file.write_all(line.as_bytes())?;
+            self.size += line.len() as u64;
+        }
+        Ok(())
+    }
+
+    fn open(&mut self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = file.metadata()?.len();
+        self.file = Some(file);
+        Ok(())
+    }
+
+    /// Shift `path.N` to `path.N+1`, move the active file to `path.1` and start afresh
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+        for n in (1..MAX_ROTATED_FILES).rev() {
+            let from = rotated_path(&self.path, n);
+            if from.exists() {
+                fs::rename(&from, rotated_path(&self.path, n + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        debug!("Rotated backend log file {}", self.path.display());
+        self.open()
+    }
+}
+
+/// Path of the `n`th rotated file, e.g. `backend.log.2`
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotates_when_cap_is_hit() {
+        let dir = std::env::temp_dir().join(format!("backend-log-test-{}", std::process::id()));
+        let path = dir.join("backend.log");
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut file = RotatingFile { path: path.clone(), max_size: 32, file: None, size: 0 };
+        for i in 0..4 {
+            file.write_line(&format!("[stdout] line number {}\n", i)).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[stdout] line number 3\n");
+        assert_eq!(fs::read_to_string(rotated_path(&path, 1)).unwrap(), "[stdout] line number 2\n");
+        assert_eq!(fs::read_to_string(rotated_path(&path, 3)).unwrap(), "[stdout] line number 0\n");
+        assert!(!rotated_path(&path, 4).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,64 @@
+/**
+ * Recent Backend Output
+ *
+ * Keeps the last N lines the backend wrote to stdout/stderr so that crash
+ * reports can include the tail of its logs. Stderr is recorded by the
+ * process manager itself; stdout belongs to the IPC bridge and is recorded
+ * through `ProcessManager::output_recorder`.
+ */
+
+use std::collections::VecDeque;
+
+/// Fixed-size ring buffer of output lines
+#[derive(Debug, Clone)]
+pub(crate) struct RecentOutput {
+    lines: VecDeque<String>,
+    capacity: usize,
+}
+
+impl RecentOutput {
+    /// Create a buffer retaining at most `capacity` lines
+    pub(crate) fn new(capacity: usize) -> Self {
+        RecentOutput {
+            lines: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Append a line, dropping the oldest once full
+    pub(crate) fn push(&mut self, line: String) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    /// Retained lines, oldest first
+    pub(crate) fn lines(&self) -> Vec<String> {
+        self.lines.iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_most_recent_lines() {
+        let mut output = RecentOutput::new(3);
+        for i in 0..5 {
+            output.push(format!("line {}", i));
+        }
+        assert_eq!(output.lines(), vec!["line 2", "line 3", "line 4"]);
+    }
+
+    #[test]
+    fn test_zero_capacity_keeps_nothing() {
+        let mut output = RecentOutput::new(0);
+        output.push("line".to_string());
+        assert!(output.lines().is_empty());
+    }
+}
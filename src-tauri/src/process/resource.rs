@@ -0,0 +1,204 @@
+/**
+ * Backend Resource Usage Sampling
+ *
+ * Reads the resident memory and accumulated CPU time of the Node.js child:
+ * - Linux: `/proc/<pid>/stat` (utime + stime) and `/proc/<pid>/statm` (resident pages)
+ * - macOS: `proc_pid_rusage`
+ * - Windows: `GetProcessTimes` and `K32GetProcessMemoryInfo`
+ *
+ * CPU percentage is derived from the CPU time consumed between two samples,
+ * so it is relative to a single core (a busy multi-threaded process can exceed 100).
+ */
+
+use serde::Serialize;
+use std::process::Child;
+use std::time::{Duration, Instant};
+
+/// Resource usage of the backend process
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ResourceUsage {
+    /// Resident set size in bytes
+    pub rss_bytes: u64,
+    /// CPU usage since the previous sample, as a percentage of one core
+    pub cpu_percent: f64,
+}
+
+/// Previous CPU reading used to compute the next percentage
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CpuSample {
+    pid: u32,
+    taken_at: Instant,
+    cpu_time: Duration,
+}
+
+/// Sample the child's usage, updating `last` for the next CPU percentage
+///
+/// The first sample for a given PID reports `cpu_percent` as 0.0.
+/// Returns `None` if the process cannot be inspected or the platform is unsupported.
+pub(crate) fn sample(child: &Child, last: &mut Option<CpuSample>) -> Option<ResourceUsage> {
+    let pid = child.id();
+    let (rss_bytes, cpu_time) = read_usage(child)?;
+    let now = Instant::now();
+
+    let cpu_percent = match *last {
+        Some(prev) if prev.pid == pid => {
+            let wall = now.duration_since(prev.taken_at).as_secs_f64();
+            let cpu = cpu_time.saturating_sub(prev.cpu_time).as_secs_f64();
+            if wall > 0.0 { cpu / wall * 100.0 } else { 0.0 }
+        }
+        _ => 0.0,
+    };
+
+    *last = Some(CpuSample { pid, taken_at: now, cpu_time });
+    Some(ResourceUsage { rss_bytes, cpu_percent })
+}
+
+#[cfg(target_os = "linux")]
+fn read_usage(child: &Child) -> Option<(u64, Duration)> {
+    use std::os::raw::{c_int, c_long};
+
+    extern "C" {
+        fn sysconf(name: c_int) -> c_long;
+    }
+    const SC_CLK_TCK: c_int = 2;
+    const SC_PAGESIZE: c_int = 30;
+
+    let pid = child.id();
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name may contain spaces, so split after its closing paren;
+    // the remaining fields start at field 3 (state), putting utime/stime at 11/12
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+
+    let statm = std::fs::read_to_string(format!("/proc/{}/statm", pid)).ok()?;
+    let resident_pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+
+    // SAFETY: sysconf has no preconditions and only reads system configuration
+    let (clk_tck, page_size) = unsafe { (sysconf(SC_CLK_TCK), sysconf(SC_PAGESIZE)) };
+    if clk_tck <= 0 || page_size <= 0 {
+        return None;
+    }
+
+    let cpu_time = Duration::from_secs_f64((utime + stime) as f64 / clk_tck as f64);
+    Some((resident_pages * page_size as u64, cpu_time))
+}
+
+#[cfg(target_os = "macos")]
+fn read_usage(child: &Child) -> Option<(u64, Duration)> {
+    use std::os::raw::{c_int, c_void};
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct RusageInfoV0 {
+        ri_uuid: [u8; 16],
+        ri_user_time: u64,
+        ri_system_time: u64,
+        ri_pkg_idle_wkups: u64,
+        ri_interrupt_wkups: u64,
+        ri_pageins: u64,
+        ri_wired_size: u64,
+        ri_resident_size: u64,
+        ri_phys_footprint: u64,
+        ri_proc_start_abstime: u64,
+        ri_proc_exit_abstime: u64,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct MachTimebaseInfo {
+        numer: u32,
+        denom: u32,
+    }
+
+    const RUSAGE_INFO_V0: c_int = 0;
+
+    extern "C" {
+        fn proc_pid_rusage(pid: c_int, flavor: c_int, buffer: *mut c_void) -> c_int;
+        fn mach_timebase_info(info: *mut MachTimebaseInfo) -> c_int;
+    }
+
+    let mut info = RusageInfoV0::default();
+    let mut timebase = MachTimebaseInfo::default();
+    // SAFETY: both buffers are properly sized, repr(C) and live for the call
+    let ok = unsafe {
+        proc_pid_rusage(child.id() as c_int, RUSAGE_INFO_V0, &mut info as *mut _ as *mut c_void) == 0
+            && mach_timebase_info(&mut timebase) == 0
+    };
+    if !ok || timebase.denom == 0 {
+        return None;
+    }
+
+    // CPU times are in Mach absolute time units
+    let ticks = (info.ri_user_time + info.ri_system_time) as u128;
+    let nanos = ticks * timebase.numer as u128 / timebase.denom as u128;
+    Some((info.ri_resident_size, Duration::from_nanos(nanos.min(u64::MAX as u128) as u64)))
+}
+
+#[cfg(windows)]
+fn read_usage(child: &Child) -> Option<(u64, Duration)> {
+    use std::os::raw::c_void;
+    use std::os::windows::io::AsRawHandle;
+
+    #[repr(C)]
+    #[derive(Default, Clone, Copy)]
+    struct FileTime {
+        low: u32,
+        high: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct ProcessMemoryCounters {
+        cb: u32,
+        page_fault_count: u32,
+        peak_working_set_size: usize,
+        working_set_size: usize,
+        quota_peak_paged_pool_usage: usize,
+        quota_paged_pool_usage: usize,
+        quota_peak_non_paged_pool_usage: usize,
+        quota_non_paged_pool_usage: usize,
+        pagefile_usage: usize,
+        peak_pagefile_usage: usize,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetProcessTimes(
+            process: *mut c_void,
+            creation: *mut FileTime,
+            exit: *mut FileTime,
+            kernel: *mut FileTime,
+            user: *mut FileTime,
+        ) -> i32;
+        fn K32GetProcessMemoryInfo(process: *mut c_void, counters: *mut ProcessMemoryCounters, cb: u32) -> i32;
+    }
+
+    let handle = child.as_raw_handle() as *mut c_void;
+    let mut creation = FileTime::default();
+    let mut exit = FileTime::default();
+    let mut kernel = FileTime::default();
+    let mut user = FileTime::default();
+    let mut counters = ProcessMemoryCounters {
+        cb: std::mem::size_of::<ProcessMemoryCounters>() as u32,
+        ..Default::default()
+    };
+    // SAFETY: the handle is owned by `child` and all out-parameters are valid
+    let ok = unsafe {
+        GetProcessTimes(handle, &mut creation, &mut exit, &mut kernel, &mut user) != 0
+            && K32GetProcessMemoryInfo(handle, &mut counters, counters.cb) != 0
+    };
+    if !ok {
+        return None;
+    }
+
+    // FILETIME values are in 100ns units
+    let to_u64 = |t: FileTime| ((t.high as u64) << 32) | t.low as u64;
+    let cpu_time = Duration::from_nanos((to_u64(kernel) + to_u64(user)) * 100);
+    Some((counters.working_set_size as u64, cpu_time))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn read_usage(_child: &Child) -> Option<(u64, Duration)> {
+    None
+}
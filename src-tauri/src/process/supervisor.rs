@@ -0,0 +1,282 @@
+/**
+ * Supervisor for Multiple Named Backend Processes
+ *
+ * Holds one `ProcessManager` per worker (e.g. indexer, LSP host, chat) and
+ * starts restart monitoring and health checks for each, so callers don't
+ * have to juggle several managers and their threads.
+ *
+ * With a bridge attached to each worker, `request_all` sends one request
+ * to every backend and aggregates the responses (e.g. search across several
+ * indexers).
+ */
+
+use super::ProcessManager;
+use crate::ipc::{IPCBridge, IPCError};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+use log::{debug, info, warn};
+
+/// Default deadline shared by all requests of a `request_all` call (30 seconds)
+const DEFAULT_FAN_OUT_TIMEOUT_SECS: u64 = 30;
+
+/// How `request_all` aggregates the responses of several backends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FanOutMode {
+    /// Resolve on the first successful response and cancel the rest
+    FirstSuccess,
+    /// Wait for every backend's response, up to the shared deadline
+    All,
+}
+
+/// Manages several named `ProcessManager`s
+pub struct ProcessSupervisor {
+    processes: HashMap<String, ProcessManager>,
+    /// Bridges connected to the managed processes, keyed by the same names
+    bridges: HashMap<String, Arc<IPCBridge>>,
+    /// Deadline shared by the requests of one `request_all` call
+    fan_out_timeout: Duration,
+}
+
+impl Default for ProcessSupervisor {
+    fn default() -> Self {
+        ProcessSupervisor {
+            processes: HashMap::new(),
+            bridges: HashMap::new(),
+            fan_out_timeout: Duration::from_secs(DEFAULT_FAN_OUT_TIMEOUT_SECS),
+        }
+    }
+}
+
+impl ProcessSupervisor {
+    /// Create an empty supervisor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the deadline shared by the requests of one `request_all` call
+    pub fn with_fan_out_timeout(mut self, timeout: Duration) -> Self {
+        self.fan_out_timeout = timeout;
+        self
+    }
+
+    /// Start a backend script under `name` with default settings
+    pub fn spawn(&mut self, name: &str, backend_script: &str, working_dir: &str) -> Result<(), String> {
+        let manager = ProcessManager::new(backend_script.to_string(), working_dir.to_string());
+        self.spawn_with(name, manager)
+    }
+
+    /// Start a pre-configured manager under `name`
+    ///
+    /// Restart monitoring, health checks and (if configured) the idle monitor
+    /// are started for it. Fails if a running process is already registered
+    /// under the same name.
+    pub fn spawn_with(&mut self, name: &str, mut manager: ProcessManager) -> Result<(), String> {
+        if self.processes.get(name).map(|pm| pm.is_running()).unwrap_or(false) {
+            return Err(format!("Process '{}' is already running", name));
+        }
+
+        info!("Supervisor starting process: {}", name);
+        manager.start_node_backend()?;
+        manager.restart_on_crash();
+        manager.start_health_checks();
+        manager.start_idle_monitor();
+
+        if let Some(mut previous) = self.processes.insert(name.to_string(), manager) {
+            // Stop the monitor threads of the stale entry
+            let _ = previous.shutdown_gracefully();
+        }
+        Ok(())
+    }
+
+    /// Get a managed process by name
+    pub fn get(&self, name: &str) -> Option<&ProcessManager> {
+        self.processes.get(name)
+    }
+
+    /// Get a managed process by name, mutably
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut ProcessManager> {
+        self.processes.get_mut(name)
+    }
+
+    /// Register the bridge connected to the process named `name`
+    ///
+    /// Connecting the bridge to the process's stdin and stdout is up to the
+    /// caller. Replaces any bridge registered before under the same name.
+    pub fn attach_bridge(&mut self, name: &str, bridge: Arc<IPCBridge>) -> Result<(), String> {
+        if !self.processes.contains_key(name) {
+            return Err(format!("No process named '{}'", name));
+        }
+        self.bridges.insert(name.to_string(), bridge);
+        Ok(())
+    }
+
+    /// Get the bridge attached to a managed process
+    pub fn bridge(&self, name: &str) -> Option<&Arc<IPCBridge>> {
+        self.bridges.get(name)
+    }
+
+    /// Send the same request to every backend with an attached bridge
+    ///
+    /// Returns `(name, result)` pairs sorted by name. With `FanOutMode::All`
+    /// there is one pair per backend; a backend that is not running fails
+    /// right away, and requests still unanswered at the deadline (see
+    /// `with_fan_out_timeout`) fail with a timeout and are cancelled. With
+    /// `FanOutMode::FirstSuccess` backends that are down are skipped, and the
+    /// only pair returned is the first successful response; the other requests
+    /// are cancelled. If no backend succeeds, all failures are returned.
+    pub fn request_all(&self, event: &str, payload: Value, mode: FanOutMode) -> Vec<(String, Result<Value, String>)> {
+        let deadline = Instant::now() + self.fan_out_timeout;
+        let mut names: Vec<&String> = self.bridges.keys().collect();
+        names.sort();
+
+        let (tx, rx) = mpsc::channel();
+        let mut results: Vec<(String, Option<Result<Value, String>>)> = Vec::with_capacity(names.len());
+        // Requests awaiting a response, by index into `results`
+        let mut in_flight: HashMap<usize, (&Arc<IPCBridge>, String)> = HashMap::new();
+        for (index, name) in names.into_iter().enumerate() {
+            let bridge = &self.bridges[name];
+            let running = self.processes.get(name).map(|pm| pm.is_running()).unwrap_or(false);
+            let sent = if running {
+                let tx = tx.clone();
+                bridge.request(event, payload.clone(), move |result| {
+                    let _ = tx.send((index, result.map_err(String::from)));
+                })
+            } else {
+                Err(format!("Process '{}' is not running", name))
+            };
+
+            match sent {
+                Ok(id) => {
+                    in_flight.insert(index, (bridge, id));
+                    results.push((name.clone(), None));
+                }
+                Err(e) => {
+                    warn!("Fan-out request {} not sent to {}: {}", event, name, e);
+                    results.push((name.clone(), Some(Err(e))));
+                }
+            }
+        }
+        drop(tx);
+
+        while !in_flight.is_empty() {
+            let Ok((index, result)) = rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) else {
+                break;
+            };
+            if in_flight.remove(&index).is_none() {
+                continue;
+            }
+            if result.is_ok() && mode == FanOutMode::FirstSuccess {
+                debug!("Fan-out request {} answered first by {}", event, results[index].0);
+                for (bridge, id) in in_flight.values() {
+                    bridge.cancel_request(id);
+                }
+                return vec![(results.swap_remove(index).0, result)];
+            }
+            results[index].1 = Some(result);
+        }
+
+        for (index, (bridge, id)) in in_flight {
+            bridge.cancel_request(&id);
+            let err = IPCError::Timeout(format!("{} not answered by {} within {:?}", event, results[index].0, self.fan_out_timeout));
+            results[index].1 = Some(Err(err.into()));
+        }
+        results
+            .into_iter()
+            .filter_map(|(name, result)| result.map(|result| (name, result)))
+            .collect()
+    }
+
+    /// Names of all managed processes
+    pub fn names(&self) -> Vec<String> {
+        self.processes.keys().cloned().collect()
+    }
+
+    /// Number of managed processes
+    pub fn len(&self) -> usize {
+        self.processes.len()
+    }
+
+    /// Whether no processes are managed
+    pub fn is_empty(&self) -> bool {
+        self.processes.is_empty()
+    }
+
+    /// Gracefully shut down one process and stop supervising it
+    pub fn shutdown(&mut self, name: &str) -> Result<(), String> {
+        let mut manager = self
+            .processes
+            .remove(name)
+            .ok_or_else(|| format!("No process named '{}'", name))?;
+        self.bridges.remove(name);
+        info!("Supervisor shutting down process: {}", name);
+        manager.shutdown_gracefully().map(|_| ())
+    }
+
+    /// Gracefully shut down every process
+    ///
+    /// All processes are shut down even if some fail; the failures are
+    /// returned together.
+    pub fn shutdown_all(&mut self) -> Result<(), String> {
+        let mut errors = Vec::new();
+        self.bridges.clear();
+        for (name, mut manager) in self.processes.drain() {
+            info!("Supervisor shutting down process: {}", name);
+            if let Err(e) = manager.shutdown_gracefully() {
+                warn!("Failed to shut down process {}: {}", name, e);
+                errors.push(format!("{}: {}", name, e));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supervisor_creation() {
+        let supervisor = ProcessSupervisor::new();
+        assert!(supervisor.is_empty());
+        assert!(supervisor.get("indexer").is_none());
+    }
+
+    #[test]
+    fn test_shutdown_unknown_process() {
+        let mut supervisor = ProcessSupervisor::new();
+        assert!(supervisor.shutdown("indexer").is_err());
+        assert!(supervisor.shutdown_all().is_ok());
+    }
+
+    #[test]
+    fn test_spawn_failure_is_not_registered() {
+        // The script exists, so it is the missing Node.js binary that fails
+        let dir = std::env::temp_dir().join(format!("supervisor-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("test.js"), "").unwrap();
+
+        let mut supervisor = ProcessSupervisor::new();
+        let manager = ProcessManager::new("test.js".to_string(), dir.to_string_lossy().into_owned())
+            .with_node_path("/nonexistent/node".to_string());
+
+        let err = supervisor.spawn_with("indexer", manager).unwrap_err();
+        assert!(err.starts_with("Failed to start backend"), "{}", err);
+        assert!(supervisor.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_attach_bridge_requires_process() {
+        let mut supervisor = ProcessSupervisor::new();
+        assert!(supervisor.attach_bridge("indexer", Arc::new(IPCBridge::new())).is_err());
+        assert!(supervisor.bridge("indexer").is_none());
+        assert!(supervisor.request_all("search", serde_json::json!({}), FanOutMode::All).is_empty());
+    }
+}
@@ -13,6 +13,9 @@ use serde_json;
 
 // Import ipc module from the main crate
 use app_lib::ipc::{IPCMessage, IPCMessageType, forward_to_frontend, parse_stdin_message, encode_message_for_stdin};
+use app_lib::ipc::{encode_message, encode_message_compressed, Compression, CompressionConfig, WireFormat};
+use app_lib::ipc::{IPCBridge, IPCError};
+use std::time::Duration;
 
 /// Test IPCMessage serialization and deserialization
 #[test]
@@ -24,6 +27,10 @@ fn test_ipc_message_serialization() {
         event: "test_event".to_string(),
         payload: serde_json::json!({"key": "value"}),
         error: None,
+        seq: None,
+        version: None,
+        binary: None,
+        meta: None,
     };
 
     let serialized = serde_json::to_string(&event_msg).expect("Failed to serialize");
@@ -43,6 +50,10 @@ fn test_ipc_message_request_type() {
         event: "get_data".to_string(),
         payload: serde_json::json!({"query": "test"}),
         error: None,
+        seq: None,
+        version: None,
+        binary: None,
+        meta: None,
     };
 
     let serialized = serde_json::to_string(&request_msg).expect("Failed to serialize");
@@ -61,6 +72,10 @@ fn test_ipc_message_response_type() {
         event: "get_data".to_string(),
         payload: serde_json::json!({"result": [1, 2, 3]}),
         error: None,
+        seq: None,
+        version: None,
+        binary: None,
+        meta: None,
     };
 
     let serialized = serde_json::to_string(&response_msg).expect("Failed to serialize");
@@ -79,6 +94,10 @@ fn test_ipc_message_with_error() {
         event: "get_data".to_string(),
         payload: serde_json::Value::Null,
         error: Some("Something went wrong".to_string()),
+        seq: None,
+        version: None,
+        binary: None,
+        meta: None,
     };
 
     let serialized = serde_json::to_string(&error_msg).expect("Failed to serialize");
@@ -117,6 +136,10 @@ fn test_encode_message_for_stdin() {
         event: "execute_command".to_string(),
         payload: serde_json::json!({"command": "ls"}),
         error: None,
+        seq: None,
+        version: None,
+        binary: None,
+        meta: None,
     };
 
     let encoded = encode_message_for_stdin(&msg);
@@ -136,6 +159,10 @@ fn test_forward_to_frontend_payload() {
         event: "display_message".to_string(),
         payload: serde_json::json!({"text": "Hello from Node.js", "role": "assistant"}),
         error: None,
+        seq: None,
+        version: None,
+        binary: None,
+        meta: None,
     };
 
     // Test that forward_to_frontend returns the correct event name and payload
@@ -165,6 +192,10 @@ fn test_complex_payload_serialization() {
         event: "complex_event".to_string(),
         payload: complex_payload.clone(),
         error: None,
+        seq: None,
+        version: None,
+        binary: None,
+        meta: None,
     };
 
     let serialized = serde_json::to_string(&msg).expect("Failed to serialize");
@@ -191,9 +222,74 @@ fn test_message_type_variants() {
             event: "test".to_string(),
             payload: serde_json::Value::Null,
             error: None,
+            seq: None,
+            version: None,
+            binary: None,
+            meta: None,
         };
 
         let serialized = serde_json::to_string(&msg).expect("Failed to serialize");
         assert!(serialized.contains(expected_str));
     }
 }
+
+/// Test request round trips against the in-memory loopback backend
+#[test]
+fn test_loopback_request_round_trip() {
+    for format in [WireFormat::Json, WireFormat::MessagePack] {
+        let bridge = IPCBridge::new().with_wire_format(format);
+        bridge.connect_echo();
+
+        let result = bridge.request_blocking("echo", serde_json::json!({"text": "hi"}), Duration::from_secs(1));
+        assert_eq!(result.unwrap()["text"], "hi");
+    }
+}
+
+/// Test request timeouts against a loopback backend that never answers
+#[test]
+fn test_loopback_request_timeout() {
+    let bridge = IPCBridge::new();
+    bridge.connect_loopback(|_| None);
+
+    let result = bridge.request_blocking("slow", serde_json::json!({}), Duration::from_millis(50));
+    assert!(matches!(result, Err(IPCError::Timeout(_))));
+    assert_eq!(bridge.pending_request_count(), 0);
+}
+
+/// Benchmark compression of a 2MB file-tree payload
+///
+/// Run with `cargo test --release --test ipc_test -- --ignored --nocapture`
+#[test]
+#[ignore]
+fn bench_compress_2mb_file_tree() {
+    let entries: Vec<_> = (0..27_000)
+        .map(|i| serde_json::json!({
+            "path": format!("src/components/module_{}/index.tsx", i),
+            "kind": "file",
+            "size": i * 17,
+        }))
+        .collect();
+    let msg = IPCMessage::event("file_tree", serde_json::json!({ "entries": entries }));
+    let config = Some(CompressionConfig { codec: Compression::Zstd, threshold: 64 * 1024 });
+
+    for format in [WireFormat::Json, WireFormat::MessagePack] {
+        let start = std::time::Instant::now();
+        let plain = encode_message(&msg, format).unwrap();
+        let plain_time = start.elapsed();
+
+        let start = std::time::Instant::now();
+        let compressed = encode_message_compressed(&msg, format, config).unwrap();
+        let compressed_time = start.elapsed();
+
+        println!(
+            "{:?}: {} bytes in {:?} -> {} bytes in {:?} ({:.1}x)",
+            format,
+            plain.len(),
+            plain_time,
+            compressed.len(),
+            compressed_time,
+            plain.len() as f64 / compressed.len() as f64
+        );
+        assert!(compressed.len() < plain.len());
+    }
+}
@@ -8,9 +8,14 @@
  */
 
 use std::process::{Command, Stdio};
+use std::sync::{mpsc, Arc};
 use std::time::Duration;
 use std::thread;
 
+use app_lib::backend::AppBackend;
+use app_lib::ipc::{IPCBridge, IPCError};
+use app_lib::process::{FanOutMode, ProcessManager, ProcessSupervisor, RestartBackoff, RestartDecision, RestartPolicy, ShutdownOutcome, Signal};
+
 #[test]
 fn test_process_module_exists() {
     // This test will fail until we create the process module
@@ -208,3 +213,820 @@ fn test_working_directory_configuration() {
     // Cleanup
     std::fs::remove_file("test_cwd.js").ok();
 }
+
+#[test]
+fn test_stderr_lines_forwarded_to_callback() {
+    let stderr_script = r#"
+        console.error('first error line');
+        console.error('second error line');
+        setTimeout(() => process.exit(0), 200);
+    "#;
+
+    std::fs::write("test_stderr.js", stderr_script).unwrap();
+
+    let mut pm = ProcessManager::new("test_stderr.js".to_string(), ".".to_string());
+    let (tx, rx) = mpsc::channel();
+    pm.start_stderr_listener(move |line| {
+        let _ = tx.send(line);
+    });
+    pm.start_node_backend().unwrap();
+
+    let first = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    let second = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(first, "first error line");
+    assert_eq!(second, "second error line");
+
+    pm.shutdown_gracefully().unwrap();
+
+    // Cleanup
+    std::fs::remove_file("test_stderr.js").ok();
+}
+
+#[test]
+#[cfg(unix)]
+fn test_signal_reaches_backend() {
+    let signal_script = r#"
+        process.on('SIGUSR1', () => console.error('rotating logs'));
+        console.error('ready');
+        setTimeout(() => process.exit(0), 5000);
+    "#;
+
+    std::fs::write("test_signal.js", signal_script).unwrap();
+
+    let mut pm = ProcessManager::new("test_signal.js".to_string(), ".".to_string());
+    let (tx, rx) = mpsc::channel();
+    pm.start_stderr_listener(move |line| {
+        let _ = tx.send(line);
+    });
+    pm.start_node_backend().unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), "ready");
+
+    pm.signal(Signal::Usr1).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), "rotating logs");
+    assert!(pm.is_running(), "SIGUSR1 should not stop the backend");
+
+    pm.shutdown_gracefully().unwrap();
+    assert!(pm.signal(Signal::Usr1).is_err());
+
+    // Cleanup
+    std::fs::remove_file("test_signal.js").ok();
+}
+
+#[test]
+fn test_ipc_bridge_connected_to_backend_handles() {
+    // Echo backend: answers every request with its own payload
+    let echo_script = r#"
+        const readline = require('readline');
+        const rl = readline.createInterface({ input: process.stdin });
+        rl.on('line', (line) => {
+            const msg = JSON.parse(line);
+            if (msg.msg_type === 'request') {
+                console.log(JSON.stringify({
+                    id: msg.id, msg_type: 'response', event: msg.event, payload: msg.payload, error: null
+                }));
+            }
+        });
+        rl.on('close', () => process.exit(0));
+    "#;
+
+    std::fs::write("test_echo_handles.js", echo_script).unwrap();
+
+    let mut pm = ProcessManager::new("test_echo_handles.js".to_string(), ".".to_string());
+    pm.start_node_backend().unwrap();
+
+    let bridge = IPCBridge::new();
+    bridge.set_stdin(pm.take_stdin().expect("stdin should be available"));
+    bridge.start_stdout_listener(pm.take_stdout().expect("stdout should be available"), |_| {});
+
+    // Handles can only be taken once per spawn
+    assert!(pm.take_stdin().is_none());
+    assert!(pm.take_stdout().is_none());
+
+    let result = bridge.request_blocking("echo", serde_json::json!({"value": 7}), Duration::from_secs(5));
+    assert_eq!(result.unwrap()["value"], 7);
+
+    pm.shutdown_gracefully().unwrap();
+
+    // Cleanup
+    std::fs::remove_file("test_echo_handles.js").ok();
+}
+
+#[test]
+fn test_on_restart_reconnects_ipc_bridge() {
+    // Crashes on the first run, then behaves as an echo backend
+    let flaky_script = r#"
+        const fs = require('fs');
+        if (!fs.existsSync('test_restart_marker')) {
+            fs.writeFileSync('test_restart_marker', '1');
+            process.exit(1);
+        }
+        const readline = require('readline');
+        const rl = readline.createInterface({ input: process.stdin });
+        rl.on('line', (line) => {
+            const msg = JSON.parse(line);
+            if (msg.msg_type === 'request') {
+                console.log(JSON.stringify({
+                    id: msg.id, msg_type: 'response', event: msg.event, payload: { restarted: true }, error: null
+                }));
+            }
+        });
+        rl.on('close', () => process.exit(0));
+    "#;
+
+    std::fs::remove_file("test_restart_marker").ok();
+    std::fs::write("test_restart.js", flaky_script).unwrap();
+
+    let mut pm = ProcessManager::new("test_restart.js".to_string(), ".".to_string());
+    let bridge = Arc::new(IPCBridge::new());
+    let (tx, rx) = mpsc::channel();
+
+    let restart_bridge = Arc::clone(&bridge);
+    pm.on_restart(move |child| {
+        restart_bridge.set_stdin(child.stdin.take().unwrap());
+        restart_bridge.start_stdout_listener(child.stdout.take().unwrap(), |_| {});
+        let _ = tx.send(child.id());
+    });
+
+    pm.start_node_backend().unwrap();
+    pm.restart_on_crash();
+
+    let new_pid = rx.recv_timeout(Duration::from_secs(10)).expect("backend should be restarted");
+
+    let result = bridge.request_blocking("status", serde_json::json!({}), Duration::from_secs(5));
+    assert_eq!(result.unwrap()["restarted"], true);
+    assert_eq!(pm.get_pid(), Some(new_pid));
+
+    pm.shutdown_gracefully().unwrap();
+
+    // Cleanup
+    std::fs::remove_file("test_restart.js").ok();
+    std::fs::remove_file("test_restart_marker").ok();
+}
+
+#[test]
+fn test_custom_env_passed_to_backend() {
+    let env_script = r#"
+        console.error('FEATURE_FLAG:', process.env.FEATURE_FLAG);
+        setTimeout(() => process.exit(0), 200);
+    "#;
+
+    std::fs::write("test_custom_env.js", env_script).unwrap();
+
+    let mut env = std::collections::HashMap::new();
+    env.insert("FEATURE_FLAG".to_string(), "enabled".to_string());
+
+    let mut pm = ProcessManager::new("test_custom_env.js".to_string(), ".".to_string()).with_env(env);
+    let (tx, rx) = mpsc::channel();
+    pm.start_stderr_listener(move |line| {
+        let _ = tx.send(line);
+    });
+    pm.start_node_backend().unwrap();
+
+    let line = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(line, "FEATURE_FLAG: enabled");
+
+    pm.shutdown_gracefully().unwrap();
+
+    // Cleanup
+    std::fs::remove_file("test_custom_env.js").ok();
+}
+
+#[test]
+fn test_restart_policy_never_leaves_crashed_backend_down() {
+    let crash_script = r#"
+        process.exit(1);
+    "#;
+
+    std::fs::write("test_never_restart.js", crash_script).unwrap();
+
+    let mut pm = ProcessManager::new("test_never_restart.js".to_string(), ".".to_string())
+        .with_restart_policy(RestartPolicy::Never);
+    let (tx, rx) = mpsc::channel();
+    pm.on_restart(move |child| {
+        let _ = tx.send(child.id());
+    });
+
+    pm.start_node_backend().unwrap();
+    pm.restart_on_crash();
+
+    assert!(rx.recv_timeout(Duration::from_secs(3)).is_err(), "backend should not be restarted");
+    assert_eq!(pm.get_restart_attempts(), 0);
+
+    // Cleanup
+    std::fs::remove_file("test_never_restart.js").ok();
+}
+
+#[test]
+fn test_restart_attempts_reset_after_stable_uptime() {
+    // Each run stays up longer than the stable window before crashing
+    let crash_script = r#"
+        setTimeout(() => process.exit(1), 700);
+    "#;
+
+    std::fs::write("test_stable_reset.js", crash_script).unwrap();
+
+    let mut pm = ProcessManager::new("test_stable_reset.js".to_string(), ".".to_string())
+        .with_restart_backoff(RestartBackoff::new(
+            Duration::from_millis(50),
+            Duration::from_millis(50),
+            Duration::from_millis(300),
+        ));
+    let (tx, rx) = mpsc::channel();
+    pm.on_restart(move |child| {
+        let _ = tx.send(child.id());
+    });
+
+    pm.start_node_backend().unwrap();
+    pm.restart_on_crash();
+
+    // Crash, stable uptime, crash again: the counter never builds up
+    for _ in 0..3 {
+        rx.recv_timeout(Duration::from_secs(5)).expect("backend should be restarted");
+        assert!(pm.get_restart_attempts() <= 1, "restart attempts should reset after stable uptime");
+    }
+
+    pm.shutdown_gracefully().unwrap();
+
+    // Cleanup
+    std::fs::remove_file("test_stable_reset.js").ok();
+}
+
+#[test]
+fn test_exit_classifier_gives_up_on_fatal_exit_code() {
+    // Exit code 78 (EX_CONFIG) means a configuration error that will recur
+    let crash_script = r#"
+        process.exit(78);
+    "#;
+
+    std::fs::write("test_exit_classifier.js", crash_script).unwrap();
+
+    let mut pm = ProcessManager::new("test_exit_classifier.js".to_string(), ".".to_string())
+        .with_exit_classifier(|status| match status.code() {
+            Some(78) => RestartDecision::GiveUp,
+            _ => RestartDecision::RestartAfter(Duration::from_millis(10)),
+        });
+    let (restart_tx, restart_rx) = mpsc::channel();
+    pm.on_restart(move |child| {
+        let _ = restart_tx.send(child.id());
+    });
+    let (exit_tx, exit_rx) = mpsc::channel();
+    pm.on_exit(move |status, _| {
+        let _ = exit_tx.send(status.code());
+    });
+
+    pm.start_node_backend().unwrap();
+    pm.restart_on_crash();
+
+    assert_eq!(exit_rx.recv_timeout(Duration::from_secs(3)).unwrap(), Some(78));
+    assert!(restart_rx.recv_timeout(Duration::from_secs(2)).is_err(), "fatal exit should not be restarted");
+    assert_eq!(pm.get_restart_attempts(), 0);
+
+    // Cleanup
+    std::fs::remove_file("test_exit_classifier.js").ok();
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "macos", windows))]
+fn test_resource_usage_of_running_backend() {
+    let busy_script = r#"
+        const start = Date.now();
+        while (Date.now() - start < 300) {}
+        setInterval(() => {}, 1000);
+    "#;
+
+    std::fs::write("test_resource_usage.js", busy_script).unwrap();
+
+    let mut pm = ProcessManager::new("test_resource_usage.js".to_string(), ".".to_string());
+    pm.start_node_backend().unwrap();
+
+    let first = pm.resource_usage().expect("usage should be available");
+    assert!(first.rss_bytes > 0);
+    assert_eq!(first.cpu_percent, 0.0);
+
+    let (tx, rx) = mpsc::channel();
+    pm.start_resource_monitor(Duration::from_millis(100), move |usage| {
+        let _ = tx.send(usage);
+    });
+    let sample = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+    assert!(sample.rss_bytes > 0);
+    assert!(sample.cpu_percent >= 0.0);
+
+    pm.shutdown_gracefully().ok();
+
+    // Cleanup
+    std::fs::remove_file("test_resource_usage.js").ok();
+}
+
+#[test]
+fn test_supervisor_manages_named_processes() {
+    let worker_script = r#"
+        setInterval(() => {}, 1000);
+    "#;
+
+    std::fs::write("test_supervisor_worker.js", worker_script).unwrap();
+
+    let mut supervisor = ProcessSupervisor::new();
+    supervisor.spawn("indexer", "test_supervisor_worker.js", ".").unwrap();
+    supervisor.spawn("chat", "test_supervisor_worker.js", ".").unwrap();
+
+    assert_eq!(supervisor.len(), 2);
+    assert!(supervisor.spawn("chat", "test_supervisor_worker.js", ".").is_err());
+
+    let indexer_pid = supervisor.get("indexer").unwrap().get_pid().unwrap();
+    let chat_pid = supervisor.get("chat").unwrap().get_pid().unwrap();
+    assert_ne!(indexer_pid, chat_pid);
+
+    supervisor.shutdown("indexer").unwrap();
+    assert!(supervisor.get("indexer").is_none());
+    assert!(supervisor.get("chat").unwrap().is_running());
+
+    supervisor.shutdown_all().unwrap();
+    assert!(supervisor.is_empty());
+
+    // Cleanup
+    std::fs::remove_file("test_supervisor_worker.js").ok();
+}
+
+#[test]
+fn test_on_exit_fires_once_per_exit() {
+    let crash_script = r#"
+        console.error('fatal: out of widgets');
+        process.exit(3);
+    "#;
+
+    std::fs::write("test_on_exit.js", crash_script).unwrap();
+
+    let mut pm = ProcessManager::new("test_on_exit.js".to_string(), ".".to_string())
+        .with_restart_policy(RestartPolicy::Never);
+    let (tx, rx) = mpsc::channel();
+    pm.on_exit(move |status, tail| {
+        let _ = tx.send((status.code(), tail.to_vec()));
+    });
+
+    pm.start_node_backend().unwrap();
+    pm.restart_on_crash();
+
+    let (code, tail) = rx.recv_timeout(Duration::from_secs(3)).unwrap();
+    assert_eq!(code, Some(3));
+    assert_eq!(tail, vec!["fatal: out of widgets".to_string()]);
+    assert_eq!(pm.recent_output(), tail);
+    assert!(rx.recv_timeout(Duration::from_millis(2500)).is_err(), "exit reported more than once");
+
+    // Cleanup
+    std::fs::remove_file("test_on_exit.js").ok();
+}
+
+#[test]
+#[cfg(any(unix, windows))]
+fn test_shutdown_reports_exit_code_or_kill() {
+    // SIGBREAK is what Windows delivers for CTRL_BREAK_EVENT
+    let graceful_script = r#"
+        process.on('SIGTERM', () => process.exit(7));
+        process.on('SIGBREAK', () => process.exit(7));
+        setInterval(() => {}, 1000);
+    "#;
+    let stubborn_script = r#"
+        process.on('SIGTERM', () => {});
+        process.on('SIGBREAK', () => {});
+        setInterval(() => {}, 1000);
+    "#;
+
+    std::fs::write("test_shutdown_graceful.js", graceful_script).unwrap();
+    std::fs::write("test_shutdown_stubborn.js", stubborn_script).unwrap();
+
+    let mut graceful = ProcessManager::new("test_shutdown_graceful.js".to_string(), ".".to_string());
+    graceful.start_node_backend().unwrap();
+    std::thread::sleep(Duration::from_millis(500));
+    assert_eq!(graceful.shutdown_gracefully().unwrap(), ShutdownOutcome::Exited(Some(7)));
+
+    let mut stubborn = ProcessManager::new("test_shutdown_stubborn.js".to_string(), ".".to_string())
+        .with_shutdown_timeout(Duration::from_millis(300));
+    stubborn.start_node_backend().unwrap();
+    std::thread::sleep(Duration::from_millis(500));
+    assert_eq!(stubborn.shutdown_gracefully().unwrap(), ShutdownOutcome::Killed);
+
+    // Cleanup
+    std::fs::remove_file("test_shutdown_graceful.js").ok();
+    std::fs::remove_file("test_shutdown_stubborn.js").ok();
+}
+
+#[test]
+#[cfg(unix)]
+fn test_shutdown_cleans_up_grandchildren() {
+    let parent_script = r#"
+        const { spawn } = require('child_process');
+        const fs = require('fs');
+        const worker = spawn(process.execPath, ['-e', 'setInterval(() => {}, 1000)'], { stdio: 'ignore' });
+        fs.writeFileSync('test_grandchild.pid', String(worker.pid));
+        setInterval(() => {}, 1000);
+    "#;
+
+    std::fs::write("test_process_group.js", parent_script).unwrap();
+
+    let mut pm = ProcessManager::new("test_process_group.js".to_string(), ".".to_string());
+    pm.start_node_backend().unwrap();
+
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    let grandchild_pid = loop {
+        if let Ok(pid) = std::fs::read_to_string("test_grandchild.pid") {
+            if !pid.is_empty() {
+                break pid;
+            }
+        }
+        assert!(std::time::Instant::now() < deadline, "grandchild never started");
+        std::thread::sleep(Duration::from_millis(50));
+    };
+
+    pm.shutdown_gracefully().unwrap();
+    std::thread::sleep(Duration::from_millis(500));
+
+    // An exited grandchild may linger as a zombie until init reaps it
+    let state = std::process::Command::new("ps")
+        .args(["-o", "stat=", "-p", grandchild_pid.trim()])
+        .output()
+        .unwrap();
+    let state = String::from_utf8_lossy(&state.stdout);
+    let alive = !state.trim().is_empty() && !state.trim().starts_with('Z');
+    assert!(!alive, "grandchild process should be terminated with the group");
+
+    // Cleanup
+    std::fs::remove_file("test_process_group.js").ok();
+    std::fs::remove_file("test_grandchild.pid").ok();
+}
+
+#[test]
+fn test_is_running_detects_exit_without_monitor() {
+    let short_script = r#"
+        setTimeout(() => process.exit(0), 300);
+    "#;
+
+    std::fs::write("test_short_lived.js", short_script).unwrap();
+
+    let mut pm = ProcessManager::new("test_short_lived.js".to_string(), ".".to_string());
+    pm.start_node_backend().unwrap();
+    assert!(pm.is_running());
+    assert!(pm.health_check());
+
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    while pm.is_running() {
+        assert!(std::time::Instant::now() < deadline, "process should have exited");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    assert!(!pm.health_check());
+    assert_eq!(pm.get_pid(), None, "exited child should be cleared");
+
+    // Cleanup
+    std::fs::remove_file("test_short_lived.js").ok();
+}
+
+#[test]
+fn test_wait_for_exit() {
+    let exiting_script = r#"
+        setTimeout(() => process.exit(4), 200);
+    "#;
+    let long_running_script = r#"
+        setInterval(() => {}, 1000);
+    "#;
+
+    std::fs::write("test_wait_exit.js", exiting_script).unwrap();
+    std::fs::write("test_wait_timeout.js", long_running_script).unwrap();
+
+    let mut exiting = ProcessManager::new("test_wait_exit.js".to_string(), ".".to_string());
+    exiting.start_node_backend().unwrap();
+    let status = exiting.wait_for_exit(Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(status.code(), Some(4));
+    assert_eq!(exiting.get_pid(), None);
+
+    let mut long_running = ProcessManager::new("test_wait_timeout.js".to_string(), ".".to_string());
+    long_running.start_node_backend().unwrap();
+    assert!(long_running.wait_for_exit(Some(Duration::from_millis(200))).is_err());
+    assert!(long_running.is_running(), "child should be left intact after a timeout");
+    long_running.shutdown_gracefully().unwrap();
+
+    // Cleanup
+    std::fs::remove_file("test_wait_exit.js").ok();
+    std::fs::remove_file("test_wait_timeout.js").ok();
+}
+
+#[test]
+fn test_shutdown_drains_ipc_bridge_first() {
+    // Answers "quick" requests and ignores everything else
+    let partial_script = r#"
+        const readline = require('readline');
+        const rl = readline.createInterface({ input: process.stdin });
+        rl.on('line', (line) => {
+            const msg = JSON.parse(line);
+            if (msg.msg_type === 'request' && msg.event === 'quick') {
+                setTimeout(() => console.log(JSON.stringify({
+                    id: msg.id, msg_type: 'response', event: msg.event, payload: { done: true }, error: null
+                })), 100);
+            }
+        });
+        rl.on('close', () => process.exit(0));
+    "#;
+
+    std::fs::write("test_drain.js", partial_script).unwrap();
+
+    let mut pm = ProcessManager::new("test_drain.js".to_string(), ".".to_string());
+    pm.start_node_backend().unwrap();
+
+    let bridge = Arc::new(IPCBridge::new());
+    bridge.set_stdin(pm.take_stdin().unwrap());
+    bridge.start_stdout_listener(pm.take_stdout().unwrap(), |_| {});
+
+    let drain_bridge = Arc::clone(&bridge);
+    pm.on_shutdown(move || {
+        drain_bridge.drain(Duration::from_secs(1));
+    });
+
+    let (tx, rx) = mpsc::channel();
+    for event in ["quick", "ignored"] {
+        let tx = tx.clone();
+        bridge
+            .request(event, serde_json::json!({}), move |result| {
+                let _ = tx.send((event, result));
+            })
+            .unwrap();
+    }
+
+    pm.shutdown_gracefully().unwrap();
+
+    let mut results: Vec<_> = rx.try_iter().collect();
+    results.sort_by_key(|(event, _)| *event);
+    assert_eq!(results.len(), 2);
+    assert!(matches!(results[0], ("ignored", Err(IPCError::ShuttingDown))));
+    assert_eq!(results[1].1.as_ref().unwrap()["done"], true);
+
+    // Cleanup
+    std::fs::remove_file("test_drain.js").ok();
+}
+
+#[test]
+fn test_idle_shutdown_and_wake_on_traffic() {
+    let echo_script = r#"
+        const readline = require('readline');
+        const rl = readline.createInterface({ input: process.stdin });
+        rl.on('line', (line) => {
+            const msg = JSON.parse(line);
+            if (msg.msg_type === 'request') {
+                console.log(JSON.stringify({
+                    id: msg.id, msg_type: 'response', event: msg.event, payload: { pid: process.pid }, error: null
+                }));
+            }
+        });
+    "#;
+
+    std::fs::write("test_idle.js", echo_script).unwrap();
+
+    let mut pm = ProcessManager::new("test_idle.js".to_string(), ".".to_string())
+        .with_idle_shutdown(Duration::from_millis(400));
+    pm.start_node_backend().unwrap();
+    let first_pid = pm.get_pid().unwrap();
+
+    let bridge = Arc::new(IPCBridge::new());
+    bridge.set_stdin(pm.take_stdin().unwrap());
+    bridge.start_stdout_listener(pm.take_stdout().unwrap(), |_| {});
+    bridge.on_activity(pm.activity_recorder());
+    let idle_bridge = Arc::clone(&bridge);
+    pm.on_idle_shutdown(move || idle_bridge.clear_writer());
+    let restart_bridge = Arc::clone(&bridge);
+    pm.on_restart(move |child| {
+        restart_bridge.set_stdin(child.stdin.take().unwrap());
+        restart_bridge.start_stdout_listener(child.stdout.take().unwrap(), |_| {});
+    });
+    pm.start_idle_monitor();
+
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    while !pm.is_idle_stopped() {
+        assert!(std::time::Instant::now() < deadline, "backend should be stopped when idle");
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(!pm.is_running());
+
+    // The next request starts the backend again and waits for it
+    let result = bridge.request_blocking("status", serde_json::json!({}), Duration::from_secs(5)).unwrap();
+    let woken_pid = result["pid"].as_u64().unwrap() as u32;
+    assert_ne!(woken_pid, first_pid);
+    assert_eq!(pm.get_pid(), Some(woken_pid));
+    assert!(!pm.is_idle_stopped());
+
+    pm.shutdown_gracefully().unwrap();
+
+    // Cleanup
+    std::fs::remove_file("test_idle.js").ok();
+}
+
+#[test]
+fn test_update_backend_restarts_with_new_script() {
+    let script = |version: u32| format!(r#"
+        const readline = require('readline');
+        const rl = readline.createInterface({{ input: process.stdin }});
+        rl.on('line', (line) => {{
+            const msg = JSON.parse(line);
+            if (msg.msg_type === 'request') {{
+                console.log(JSON.stringify({{
+                    id: msg.id, msg_type: 'response', event: msg.event,
+                    payload: {{ version: {}, args: process.execArgv }}, error: null
+                }}));
+            }}
+        }});
+    "#, version);
+
+    std::fs::write("test_update_v1.js", script(1)).unwrap();
+    std::fs::write("test_update_v2.js", script(2)).unwrap();
+
+    let mut pm = ProcessManager::new("test_update_v1.js".to_string(), ".".to_string());
+    let bridge = Arc::new(IPCBridge::new());
+    let restart_bridge = Arc::clone(&bridge);
+    pm.on_restart(move |child| {
+        restart_bridge.set_stdin(child.stdin.take().unwrap());
+        restart_bridge.start_stdout_listener(child.stdout.take().unwrap(), |_| {});
+    });
+
+    pm.start_node_backend().unwrap();
+    bridge.set_stdin(pm.take_stdin().unwrap());
+    bridge.start_stdout_listener(pm.take_stdout().unwrap(), |_| {});
+    pm.restart_on_crash();
+
+    let result = bridge.request_blocking("version", serde_json::json!({}), Duration::from_secs(5)).unwrap();
+    assert_eq!(result["version"], 1);
+    let old_pid = pm.get_pid();
+
+    pm.update_backend("test_update_v2.js".to_string(), vec!["--no-warnings".to_string()]).unwrap();
+    let result = bridge.request_blocking("version", serde_json::json!({}), Duration::from_secs(5)).unwrap();
+    assert_eq!(result["version"], 2);
+    assert_eq!(result["args"], serde_json::json!(["--no-warnings"]));
+    assert_ne!(pm.get_pid(), old_pid);
+    assert_eq!(pm.get_restart_attempts(), 0, "a reload is not a crash restart");
+
+    pm.shutdown_gracefully().unwrap();
+
+    // Cleanup
+    std::fs::remove_file("test_update_v1.js").ok();
+    std::fs::remove_file("test_update_v2.js").ok();
+}
+
+#[test]
+fn test_log_file_receives_backend_output() {
+    let script = r#"
+        console.error('stderr diagnostics');
+        console.log('stdout diagnostics');
+        setTimeout(() => {}, 5000);
+    "#;
+    std::fs::write("test_log_file_backend.js", script).unwrap();
+    let log_path = std::env::temp_dir().join(format!("test-backend-{}.log", std::process::id()));
+    std::fs::remove_file(&log_path).ok();
+
+    let mut pm = ProcessManager::new("test_log_file_backend.js".to_string(), ".".to_string())
+        .with_log_file(log_path.to_string_lossy().into_owned(), 1024 * 1024);
+    pm.start_node_backend().unwrap();
+    let bridge = IPCBridge::new();
+    bridge.start_stdout_listener_with_raw(pm.take_stdout().unwrap(), |_| {}, pm.output_recorder());
+
+    let mut contents = String::new();
+    for _ in 0..50 {
+        contents = std::fs::read_to_string(&log_path).unwrap_or_default();
+        if contents.contains("[stdout] stdout diagnostics") && contents.contains("[stderr] stderr diagnostics") {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    assert!(contents.contains("[stderr] stderr diagnostics"), "{}", contents);
+    assert!(contents.contains("[stdout] stdout diagnostics"), "{}", contents);
+
+    pm.shutdown_gracefully().unwrap();
+
+    // Cleanup
+    std::fs::remove_file("test_log_file_backend.js").ok();
+    std::fs::remove_file(&log_path).ok();
+}
+
+#[test]
+fn test_app_backend_shutdown_does_not_restart() {
+    // Answers requests, and crashes as soon as stdin closes
+    let script = r#"
+        const readline = require('readline');
+        const rl = readline.createInterface({ input: process.stdin });
+        rl.on('line', (line) => {
+            const msg = JSON.parse(line);
+            if (msg.msg_type === 'request') {
+                console.log(JSON.stringify({
+                    id: msg.id, msg_type: 'response', event: msg.event, payload: { pid: process.pid }, error: null
+                }));
+            }
+        });
+        rl.on('close', () => process.exit(1));
+    "#;
+    std::fs::write("test_app_backend.js", script).unwrap();
+
+    let pm = ProcessManager::new("test_app_backend.js".to_string(), ".".to_string());
+    let mut backend = AppBackend::new(IPCBridge::new(), pm);
+    backend.start().unwrap();
+
+    let result = backend.bridge().request_blocking("status", serde_json::json!({}), Duration::from_secs(5)).unwrap();
+    assert_eq!(result["pid"], backend.process().get_pid().unwrap());
+
+    backend.shutdown().unwrap();
+    thread::sleep(Duration::from_millis(1500));
+    assert!(!backend.process().is_running(), "backend must not be restarted after shutdown");
+    assert_eq!(backend.process().get_restart_attempts(), 0);
+
+    // Cleanup
+    std::fs::remove_file("test_app_backend.js").ok();
+}
+
+#[test]
+fn test_reset_restart_attempts_after_giving_up() {
+    std::fs::write("test_reset_attempts.js", "process.exit(1);").unwrap();
+
+    let mut pm = ProcessManager::new("test_reset_attempts.js".to_string(), ".".to_string())
+        .with_restart_backoff(RestartBackoff::new(
+            Duration::from_millis(10),
+            Duration::from_millis(10),
+            Duration::from_secs(60),
+        ));
+    pm.start_node_backend().unwrap();
+    pm.restart_on_crash();
+
+    // The monitor polls once a second, so five restarts take a few seconds
+    let deadline = std::time::Instant::now() + Duration::from_secs(15);
+    while pm.get_restart_attempts() < 5 {
+        assert!(std::time::Instant::now() < deadline, "restart attempts should reach the maximum");
+        thread::sleep(Duration::from_millis(100));
+    }
+    assert!(pm.wait_for_monitors(Duration::from_secs(3)), "monitor should give up");
+
+    pm.reset_restart_attempts();
+    assert_eq!(pm.get_restart_attempts(), 0);
+
+    // The operator fixes the cause and brings the backend back
+    std::fs::write("test_reset_attempts.js", "setTimeout(() => {}, 10000);").unwrap();
+    pm.start_node_backend().unwrap();
+    pm.restart_on_crash();
+    thread::sleep(Duration::from_millis(300));
+    assert!(pm.is_running());
+    assert_eq!(pm.get_restart_attempts(), 0);
+
+    pm.shutdown_gracefully().unwrap();
+
+    // Cleanup
+    std::fs::remove_file("test_reset_attempts.js").ok();
+}
+
+#[test]
+fn test_supervisor_request_all_fans_out() {
+    // Answers every request after `delay` ms with its own name
+    let responder = |name: &str, delay: u64| {
+        format!(
+            r#"
+            const readline = require('readline');
+            const rl = readline.createInterface({{ input: process.stdin }});
+            rl.on('line', (line) => {{
+                const msg = JSON.parse(line);
+                if (msg.msg_type === 'request') {{
+                    setTimeout(() => console.log(JSON.stringify({{
+                        id: msg.id, msg_type: 'response', event: msg.event, payload: {{ from: '{}' }}, error: null
+                    }})), {});
+                }}
+            }});
+            rl.on('close', () => process.exit(0));
+            "#,
+            name, delay
+        )
+    };
+
+    let mut supervisor = ProcessSupervisor::new().with_fan_out_timeout(Duration::from_secs(5));
+    for (name, delay) in [("fast", 50), ("slow", 500), ("down", 0)] {
+        let script = format!("test_fan_out_{}.js", name);
+        std::fs::write(&script, responder(name, delay)).unwrap();
+        supervisor.spawn_with(name, ProcessManager::new(script, ".".to_string())).unwrap();
+
+        let pm = supervisor.get(name).unwrap();
+        let bridge = Arc::new(IPCBridge::new());
+        bridge.set_stdin(pm.take_stdin().unwrap());
+        bridge.start_stdout_listener(pm.take_stdout().unwrap(), |_| {});
+        supervisor.attach_bridge(name, bridge).unwrap();
+    }
+    supervisor.get_mut("down").unwrap().shutdown_gracefully().unwrap();
+
+    let all = supervisor.request_all("search", serde_json::json!({"q": "x"}), FanOutMode::All);
+    let names: Vec<&str> = all.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, vec!["down", "fast", "slow"]);
+    assert!(all[0].1.is_err(), "a stopped backend should fail");
+    assert_eq!(all[1].1.as_ref().unwrap()["from"], "fast");
+    assert_eq!(all[2].1.as_ref().unwrap()["from"], "slow");
+
+    let first = supervisor.request_all("search", serde_json::json!({"q": "x"}), FanOutMode::FirstSuccess);
+    assert_eq!(first.len(), 1);
+    assert_eq!(first[0].0, "fast");
+    assert_eq!(first[0].1.as_ref().unwrap()["from"], "fast");
+    assert_eq!(supervisor.bridge("slow").unwrap().pending_request_count(), 0, "the slower request should be cancelled");
+
+    supervisor.shutdown_all().unwrap();
+
+    // Cleanup
+    for name in ["fast", "slow", "down"] {
+        std::fs::remove_file(format!("test_fan_out_{}.js", name)).ok();
+    }
+}